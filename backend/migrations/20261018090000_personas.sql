@@ -0,0 +1,13 @@
+-- Replace the `is_male` flag with an optional pronoun set and an avatar.
+alter table users add column if not exists pronouns text;
+alter table users add column if not exists avatar text not null default 'neutral';
+update users set avatar = case when is_male then 'man' else 'woman' end;
+alter table users drop column if exists is_male;
+
+-- Create avatars table for uploaded avatar images.
+create table if not exists avatars
+(
+    user_id int8 primary key not null references users (id) on delete cascade,
+    mime text not null,
+    data bytea not null
+);
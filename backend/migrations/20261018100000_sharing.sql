@@ -0,0 +1,33 @@
+-- Create teams table.
+create table if not exists teams
+(
+    id int8 generated by default as identity primary key,
+    name text not null,
+    owner int8 not null references users (id) on delete cascade
+);
+
+-- Create team_members table.
+create table if not exists team_members
+(
+    team_id int8 not null references teams (id) on delete cascade,
+    user_id int8 not null references users (id) on delete cascade,
+    primary key (team_id, user_id)
+);
+
+-- Create shares table, granting a role on a zerra to either a user or a team.
+create table if not exists shares
+(
+    zerra_id text not null references vus (id) on delete cascade,
+    grantee_user int8 references users (id) on delete cascade,
+    grantee_team int8 references teams (id) on delete cascade,
+    role text not null check (role in ('viewer', 'editor', 'conductor')),
+    check ((grantee_user is null) <> (grantee_team is null))
+);
+
+create unique index if not exists shares_user_idx
+    on shares (zerra_id, grantee_user) where grantee_user is not null;
+create unique index if not exists shares_team_idx
+    on shares (zerra_id, grantee_team) where grantee_team is not null;
+
+-- Remember who actually conducted a zerra, which may differ from its owner.
+alter table vus add column if not exists conducted_by int8 references users (id) on delete set null;
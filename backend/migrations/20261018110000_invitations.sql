@@ -0,0 +1,20 @@
+-- Create secrets table for server-side keys that must survive restarts.
+create table if not exists secrets
+(
+    name text primary key not null,
+    value bytea not null
+);
+
+-- Create invitations table.
+create table if not exists invitations
+(
+    id text primary key not null,
+    zerra_id text not null references vus (id) on delete cascade,
+    created_by int8 not null references users (id) on delete cascade,
+    expires_at int8 not null,
+    single_use boolean not null,
+    passcode_hash text,
+    invitee_name text,
+    -- Attendee UUID of whoever redeemed a single-use invitation first.
+    claimed_by text
+);
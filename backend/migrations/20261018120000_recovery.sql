@@ -0,0 +1,7 @@
+-- Create recovery_codes table, holding at most one outstanding code per attended zerra.
+create table if not exists recovery_codes
+(
+    zerra_id text primary key not null references vus (id) on delete cascade,
+    participant_uuid text not null,
+    code_hash text not null
+);
@@ -0,0 +1,16 @@
+-- Create login_throttles table, counting recent failed logins per username and per client address.
+create table if not exists login_throttles
+(
+    key text primary key not null,
+    failures int4 not null,
+    last_failure int8 not null
+);
+
+-- Create failed_logins table for auditing.
+create table if not exists failed_logins
+(
+    id int8 generated always as identity primary key,
+    username text not null,
+    client text not null,
+    attempted_at int8 not null
+);
@@ -0,0 +1,16 @@
+-- Create totp table for second factors, enabled only once a code has been confirmed.
+create table if not exists totp
+(
+    user_id int8 primary key not null references users (id) on delete cascade,
+    secret bytea not null,
+    enabled boolean not null default false,
+    -- The last time step a code was accepted for, so no code is accepted twice.
+    last_step int8
+);
+
+-- Create backup_codes table.
+create table if not exists backup_codes
+(
+    user_id int8 not null references users (id) on delete cascade,
+    code_hash text not null
+);
@@ -0,0 +1,11 @@
+-- Create api_tokens table. Only a SHA-256 digest of each token is kept.
+create table if not exists api_tokens
+(
+    id int8 generated always as identity primary key,
+    user_id int8 not null references users (id) on delete cascade,
+    name text not null,
+    scope text not null check (scope in ('read', 'write')),
+    token_hash bytea not null unique,
+    created_at int8 not null,
+    last_used_at int8
+);
@@ -0,0 +1,16 @@
+-- Create audit_log table, recording who did what to which zerra, when and from where. Actors are
+-- not foreign keys so that the trail outlives the users and zerrae it mentions.
+create table if not exists audit_log
+(
+    id int8 generated always as identity primary key,
+    at int8 not null,
+    actor int8,
+    action text not null,
+    zerra_id text,
+    client text not null,
+    user_agent text not null,
+    detail text
+);
+
+create index if not exists audit_log_actor_idx on audit_log (actor, at);
+create index if not exists audit_log_zerra_idx on audit_log (zerra_id, at);
@@ -0,0 +1,2 @@
+-- Remember when a zerra was moved to the trash. Trashed zerrae are purged after a retention period.
+alter table vus add column if not exists deleted_at int8;
@@ -0,0 +1,25 @@
+-- Create folders table. Every user files zerrae into folders of their own.
+create table if not exists folders
+(
+    id int8 generated always as identity primary key,
+    user_id int8 not null references users (id) on delete cascade,
+    name text not null,
+    unique (user_id, name)
+);
+
+-- Create filings table, keeping each zerra in at most one folder of each user.
+create table if not exists filings
+(
+    user_id int8 not null references users (id) on delete cascade,
+    zerra_id text not null references vus (id) on delete cascade,
+    folder_id int8 not null references folders (id) on delete cascade,
+    primary key (user_id, zerra_id)
+);
+
+-- Create tags table, labelling zerrae for everyone who can reach them.
+create table if not exists tags
+(
+    zerra_id text not null references vus (id) on delete cascade,
+    tag text not null,
+    primary key (zerra_id, tag)
+);
@@ -0,0 +1,20 @@
+-- Set templates apart from the zerrae conducted from them. A template is a script that is edited
+-- like any zerra but never conducted; starting an interview from it copies its current version.
+-- Instances keep the ID of their template without a foreign key, so that interviews run from the
+-- same script stay grouped after the template is purged.
+alter table vus add column if not exists is_template boolean not null default false;
+alter table vus add column if not exists template_id text;
+alter table vus add column if not exists template_version int4;
+
+create index if not exists vus_template_idx on vus (template_id);
+
+-- Create template_versions table, keeping each version of a template that an interview was
+-- started from.
+create table if not exists template_versions
+(
+    template_id text not null references vus (id) on delete cascade,
+    version int4 not null,
+    data bytea not null,
+    created_at int8 not null,
+    primary key (template_id, version)
+);
@@ -0,0 +1,7 @@
+-- Keep what the list of zerrae shows of each beside its blob, so that the list can be filtered,
+-- sorted and paged by the database. Rows written before are summarised when the server starts.
+alter table vus add column if not exists title text;
+alter table vus add column if not exists status text;
+alter table vus add column if not exists participant text;
+
+create index if not exists vus_status_idx on vus (status);
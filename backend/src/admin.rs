@@ -0,0 +1,356 @@
+use std::{
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+};
+
+use anyhow::Context as _;
+use clap::{Parser, Subcommand};
+use sqlx::{PgPool, Row};
+use types::{
+    jiff::Timestamp,
+    zerra::{Progress, Zerra},
+};
+
+use crate::{
+    config::Config,
+    env::{self, Environment},
+    models::{
+        audit::{self, Action, Origin},
+        authenticated::Context,
+        transcription, unauthenticated, user,
+    },
+};
+
+/// Maintains the Zerra database without going through the web UI. The database is found as by
+/// the server: through `zerra.toml`, `CONFIG_FILE` or `DATABASE_URL`.
+#[derive(Debug, Parser)]
+#[command(name = "zerra-admin", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs the database migrations.
+    Migrate,
+    /// Manages users.
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Manages zerrae.
+    #[command(subcommand)]
+    Zerra(ZerraCommand),
+    /// Shows the audit log, newest first.
+    Audit {
+        /// Only the entries of this user.
+        #[arg(long)]
+        user: Option<String>,
+        /// Only the entries about this zerra.
+        #[arg(long)]
+        zerra: Option<String>,
+        /// Only the entries since this instant, e.g. 2026-01-31T00:00:00Z.
+        #[arg(long)]
+        since: Option<Timestamp>,
+        #[arg(long, default_value_t = 100)]
+        limit: i64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum UserCommand {
+    /// Lists all users.
+    List,
+    /// Creates a user, reading their password from standard input.
+    Create {
+        username: String,
+        /// Defaults to the username.
+        #[arg(long)]
+        display_name: Option<String>,
+    },
+    /// Replaces the password of a user, reading it from standard input, and ends their sessions.
+    Passwd { username: String },
+    /// Removes the second factor of a user who lost both their authenticator and backup codes.
+    ResetTotp { username: String },
+    /// Deletes a user who owns no zerrae.
+    Delete { username: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ZerraCommand {
+    /// Lists all zerrae with their owners and progress, or `trashed`.
+    List,
+    /// Imports zerrae from YAML files, or from Markdown outlines ending in `.md` or `.txt`, printing
+    /// the ID given to each.
+    Import {
+        /// Username of the owner of the imported zerrae.
+        #[arg(long)]
+        owner: String,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Exports a zerra as YAML.
+    Export {
+        id: String,
+        /// Defaults to standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Renders the transcript of a finished zerra as PDF.
+    Transcribe {
+        id: String,
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(long, default_value = "en")]
+        lang: String,
+    },
+    /// Forgets the attendee of a zerra and their answers, so that it can be attended afresh. The
+    /// server is not told: a conductor still connected would save their copy over the reset.
+    ResetProgress { id: String },
+}
+
+pub async fn run() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load(&Environment)?;
+    let db = PgPool::connect(
+        config
+            .database_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("DATABASE_URL is not set"))?,
+    )
+    .await?;
+
+    match cli.command {
+        Command::Migrate => {
+            sqlx::migrate!().run(&db).await?;
+            println!("Migrated");
+        }
+        Command::User(command) => run_user(&db, command).await?,
+        Command::Zerra(command) => run_zerra(&db, command).await?,
+        Command::Audit {
+            user,
+            zerra,
+            since,
+            limit,
+        } => {
+            let actor = match user {
+                Some(username) => Some(user_id(&db, &username).await?),
+                None => None,
+            };
+            for entry in audit::entries(&db, actor, zerra.as_deref(), since, limit).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    entry.at,
+                    match (entry.actor, entry.username) {
+                        (_, Some(username)) => username,
+                        (Some(actor), None) => format!("user({actor})"),
+                        (None, None) => "-".to_string(),
+                    },
+                    entry.action,
+                    entry.zerra_id.as_deref().unwrap_or("-"),
+                    entry.client,
+                    entry.user_agent,
+                    entry.detail.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_user(db: &PgPool, command: UserCommand) -> anyhow::Result<()> {
+    match command {
+        UserCommand::List => {
+            let users = sqlx::query(
+                "select id, username, display_name, \
+                 exists (select 1 from totp where user_id = users.id and enabled) as totp \
+                 from users order by id",
+            )
+            .fetch_all(db)
+            .await?;
+            for r in users {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    r.get::<i64, _>("id"),
+                    r.get::<String, _>("username"),
+                    r.get::<String, _>("display_name"),
+                    if r.get("totp") { "totp" } else { "-" },
+                );
+            }
+        }
+        UserCommand::Create {
+            username,
+            display_name,
+        } => {
+            let password = read_password()?;
+            let display_name = display_name.as_deref().unwrap_or(&username);
+            let id = user::create(db, &username, display_name, password).await?;
+            tracing::info!(target: "audit", user = id, username, "created user from CLI");
+            println!("Created user({id}) {username}");
+        }
+        UserCommand::Passwd { username } => {
+            let password = read_password()?;
+            anyhow::ensure!(
+                user::set_password(db, &username, password).await?,
+                "no user named {username}"
+            );
+            tracing::info!(target: "audit", username, "reset password from CLI");
+            println!("Changed the password of {username}");
+        }
+        UserCommand::ResetTotp { username } => {
+            let id = user_id(db, &username).await?;
+            sqlx::query("delete from totp where user_id = $1")
+                .bind(id)
+                .execute(db)
+                .await?;
+            sqlx::query("delete from backup_codes where user_id = $1")
+                .bind(id)
+                .execute(db)
+                .await?;
+            audit::record(
+                db,
+                &Origin::cli(),
+                None,
+                Action::DisableTotp,
+                None,
+                Some(&username),
+            )
+            .await?;
+            println!("Removed the second factor of {username}");
+        }
+        UserCommand::Delete { username } => {
+            let id = user_id(db, &username).await?;
+            let owned: i64 = sqlx::query("select count(1) from vus where owner = $1")
+                .bind(id)
+                .fetch_one(db)
+                .await?
+                .get(0);
+            anyhow::ensure!(owned == 0, "{username} still owns {owned} zerrae");
+            sqlx::query("delete from users where id = $1")
+                .bind(id)
+                .execute(db)
+                .await?;
+            tracing::info!(target: "audit", user = id, username, "deleted user from CLI");
+            println!("Deleted {username}");
+        }
+    }
+    Ok(())
+}
+
+async fn run_zerra(db: &PgPool, command: ZerraCommand) -> anyhow::Result<()> {
+    match command {
+        ZerraCommand::List => {
+            let zerrae = sqlx::query(
+                "select vus.id, vus.data, vus.deleted_at, vus.is_template, users.username from vus \
+                 left join users on vus.owner = users.id order by vus.last_modified desc",
+            )
+            .fetch_all(db)
+            .await?;
+            for r in zerrae {
+                let id: String = r.get("id");
+                let zerra: Zerra = match rmp_serde::from_slice(r.get("data")) {
+                    Ok(zerra) => zerra,
+                    Err(e) => {
+                        tracing::warn!(%e, "skipping zerra {id}, which is corrupt");
+                        continue;
+                    }
+                };
+                let trashed = r.get::<Option<i64>, _>("deleted_at").is_some();
+                let progress = match zerra.progress {
+                    _ if trashed => "trashed",
+                    _ if r.get("is_template") => "template",
+                    Progress::None => "none",
+                    Progress::Intro { .. } => "intro",
+                    Progress::Ongoing { .. } => "ongoing",
+                    Progress::Finished { .. } => "finished",
+                };
+                println!(
+                    "{id}\t{}\t{progress}\t{}",
+                    r.get::<Option<String>, _>("username")
+                        .as_deref()
+                        .unwrap_or("-"),
+                    zerra.title,
+                );
+            }
+        }
+        ZerraCommand::Import { owner, files } => {
+            let ctx = Context {
+                db,
+                user: user_id(db, &owner).await?,
+            };
+            for file in files {
+                let text = std::fs::read_to_string(&file)
+                    .with_context(|| format!("cannot read {}", file.display()))?;
+                let outline = file
+                    .extension()
+                    .is_some_and(|ext| ext == "md" || ext == "markdown" || ext == "txt");
+                let imported = if outline {
+                    ctx.import_outline(&text, &Origin::cli()).await
+                } else {
+                    ctx.import(&text, &Origin::cli()).await
+                };
+                let id = imported.with_context(|| format!("cannot import {}", file.display()))?;
+                println!("{}\t{id}", file.display());
+            }
+        }
+        ZerraCommand::Export { id, output } => {
+            let yaml = owner_context(db, &id).await?.export_yaml(&id).await?;
+            match output {
+                Some(output) => std::fs::write(&output, yaml)
+                    .with_context(|| format!("cannot write {}", output.display()))?,
+                None => print!("{yaml}"),
+            }
+        }
+        ZerraCommand::Transcribe { id, output, lang } => {
+            let pdf = transcription::render(db, &id, env::i18n(&lang)).await?;
+            std::fs::write(&output, pdf)
+                .with_context(|| format!("cannot write {}", output.display()))?;
+        }
+        ZerraCommand::ResetProgress { id } => {
+            unauthenticated::reset_progress(db, &id).await?;
+            audit::record(
+                db,
+                &Origin::cli(),
+                None,
+                Action::ResetProgress,
+                Some(&id),
+                None,
+            )
+            .await?;
+            println!("Reset the progress of {id}");
+        }
+    }
+    Ok(())
+}
+
+async fn user_id(db: &PgPool, username: &str) -> anyhow::Result<i64> {
+    sqlx::query("select id from users where username = $1")
+        .bind(username)
+        .fetch_optional(db)
+        .await?
+        .map(|r| r.get(0))
+        .ok_or_else(|| anyhow::anyhow!("no user named {username}"))
+}
+
+/// Acts as the owner of a zerra, who may do anything with it.
+async fn owner_context<'db>(db: &'db PgPool, zerra_id: &str) -> anyhow::Result<Context<'db>> {
+    let user = sqlx::query("select owner from vus where id = $1")
+        .bind(zerra_id)
+        .fetch_optional(db)
+        .await?
+        .map(|r| r.get(0))
+        .ok_or_else(|| anyhow::anyhow!("no zerra {zerra_id}"))?;
+    Ok(Context { db, user })
+}
+
+/// Prompts for a password on a terminal, or else reads the first line piped in.
+fn read_password() -> anyhow::Result<String> {
+    let password = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password("Password: ")?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    anyhow::ensure!(!password.is_empty(), "password is empty");
+    Ok(password)
+}
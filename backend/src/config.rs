@@ -0,0 +1,457 @@
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use tower_sessions::cookie::{SameSite, time};
+
+use crate::{env::Settings, models::throttle::LoginLimits};
+
+/// Read when `CONFIG_FILE` is not set, if it exists.
+const DEFAULT_FILE: &str = "zerra.toml";
+
+/// Everything about the server that can be tuned without a rebuild. See `zerra.example.toml` for
+/// the defaults.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Only used by the standalone server; Shuttle provides its own database.
+    pub database_url: Option<String>,
+    /// Only used by the standalone server when no socket is passed to it.
+    pub bind_address: String,
+    pub assets_dir: PathBuf,
+    pub request_timeout_secs: u64,
+    /// Bearer token required to scrape `/metrics`. Without one, only the server's own host and
+    /// the trusted proxies may scrape it.
+    pub metrics_token: Option<String>,
+    /// How long the language and attendee cookies last.
+    pub cookie_days: i64,
+    /// Reverse proxies whose `X-Forwarded-For` tells the address of a client. Without any, clients
+    /// are told apart by the address they connect from.
+    pub trusted_proxies: Vec<IpRange>,
+    pub session: SessionOptions,
+    pub login: LoginOptions,
+    pub live: LiveOptions,
+    pub trash: TrashOptions,
+}
+
+/// An address, or a block of them in CIDR notation, e.g. `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionOptions {
+    pub secure: bool,
+    pub same_site: SameSitePolicy,
+    /// How long a session survives without any activity.
+    pub expiry_hours: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSitePolicy {
+    Strict,
+    Lax,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoginOptions {
+    pub max_failures: i32,
+    pub max_failures_per_client: i32,
+    pub lockout_minutes: i64,
+}
+
+/// Tunables of the WebSockets used for conduction and edition.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LiveOptions {
+    /// A socket silent for this long is taken for dead.
+    pub heartbeat_timeout_secs: u64,
+    /// Messages buffered for a participant before senders wait.
+    pub channel_capacity: usize,
+    /// How long a stopping server waits for live sockets to close. Only a standalone server drains
+    /// them, as Shuttle serves the router itself.
+    pub drain_secs: u64,
+    /// How long clients are told to wait before reconnecting to a stopping server.
+    pub reconnect_secs: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashOptions {
+    /// How long a deleted zerra can be restored before it is purged.
+    pub retention_days: i64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database_url: None,
+            bind_address: "0.0.0.0:8000".to_string(),
+            assets_dir: PathBuf::from("frontend/dist"),
+            request_timeout_secs: 30,
+            metrics_token: None,
+            cookie_days: 30,
+            trusted_proxies: Vec::new(),
+            session: SessionOptions::default(),
+            login: LoginOptions::default(),
+            live: LiveOptions::default(),
+            trash: TrashOptions::default(),
+        }
+    }
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        SessionOptions {
+            secure: true,
+            same_site: SameSitePolicy::Lax,
+            expiry_hours: 24,
+        }
+    }
+}
+
+impl Default for LoginOptions {
+    fn default() -> Self {
+        LoginOptions {
+            max_failures: 5,
+            max_failures_per_client: 20,
+            lockout_minutes: 15,
+        }
+    }
+}
+
+impl Default for LiveOptions {
+    fn default() -> Self {
+        LiveOptions {
+            heartbeat_timeout_secs: 5,
+            channel_capacity: 16,
+            drain_secs: 30,
+            reconnect_secs: 5,
+        }
+    }
+}
+
+impl Default for TrashOptions {
+    fn default() -> Self {
+        TrashOptions { retention_days: 30 }
+    }
+}
+
+impl Config {
+    /// Reads the TOML file at `CONFIG_FILE` (default `zerra.toml`, which may be absent), then
+    /// overrides it with the settings named after each key, e.g. `SESSION_EXPIRY_HOURS` for
+    /// `session.expiry_hours`.
+    pub fn load(settings: &impl Settings) -> anyhow::Result<Self> {
+        let mut config = match settings.get("CONFIG_FILE") {
+            Some(path) => Self::read(Path::new(&path))?,
+            None if Path::new(DEFAULT_FILE).exists() => Self::read(Path::new(DEFAULT_FILE))?,
+            None => Config::default(),
+        };
+        config.override_with(settings)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("{} is malformed", path.display()))
+    }
+
+    fn override_with(&mut self, settings: &impl Settings) -> anyhow::Result<()> {
+        if let Some(database_url) = settings.get("DATABASE_URL") {
+            self.database_url = Some(database_url);
+        }
+        set(&mut self.bind_address, settings, "BIND_ADDRESS")?;
+        set(&mut self.assets_dir, settings, "ASSETS_DIR")?;
+        set(
+            &mut self.request_timeout_secs,
+            settings,
+            "REQUEST_TIMEOUT_SECS",
+        )?;
+        if let Some(metrics_token) = settings.get("METRICS_TOKEN") {
+            self.metrics_token = Some(metrics_token);
+        }
+        set(&mut self.cookie_days, settings, "COOKIE_DAYS")?;
+        if let Some(trusted_proxies) = settings.get("TRUSTED_PROXIES") {
+            self.trusted_proxies = trusted_proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(str::parse)
+                .collect::<anyhow::Result<_>>()
+                .context("TRUSTED_PROXIES is malformed")?;
+        }
+
+        set(&mut self.session.secure, settings, "SESSION_SECURE")?;
+        if let Some(same_site) = settings.get("SESSION_SAME_SITE") {
+            self.session.same_site = match same_site.trim() {
+                "strict" => SameSitePolicy::Strict,
+                "lax" => SameSitePolicy::Lax,
+                "none" => SameSitePolicy::None,
+                same_site => anyhow::bail!("SESSION_SAME_SITE is {same_site}"),
+            };
+        }
+        set(
+            &mut self.session.expiry_hours,
+            settings,
+            "SESSION_EXPIRY_HOURS",
+        )?;
+
+        set(&mut self.login.max_failures, settings, "LOGIN_MAX_FAILURES")?;
+        set(
+            &mut self.login.max_failures_per_client,
+            settings,
+            "LOGIN_MAX_FAILURES_PER_CLIENT",
+        )?;
+        set(
+            &mut self.login.lockout_minutes,
+            settings,
+            "LOGIN_LOCKOUT_MINUTES",
+        )?;
+
+        set(
+            &mut self.live.heartbeat_timeout_secs,
+            settings,
+            "LIVE_HEARTBEAT_TIMEOUT_SECS",
+        )?;
+        set(
+            &mut self.live.channel_capacity,
+            settings,
+            "LIVE_CHANNEL_CAPACITY",
+        )?;
+        set(&mut self.live.drain_secs, settings, "LIVE_DRAIN_SECS")?;
+        set(
+            &mut self.live.reconnect_secs,
+            settings,
+            "LIVE_RECONNECT_SECS",
+        )?;
+
+        set(
+            &mut self.trash.retention_days,
+            settings,
+            "TRASH_RETENTION_DAYS",
+        )?;
+        Ok(())
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.request_timeout_secs > 0,
+            "request_timeout_secs must be positive"
+        );
+        anyhow::ensure!(self.cookie_days > 0, "cookie_days must be positive");
+        anyhow::ensure!(
+            self.session.expiry_hours > 0,
+            "session.expiry_hours must be positive"
+        );
+        anyhow::ensure!(
+            self.login.max_failures > 0,
+            "login.max_failures must be positive"
+        );
+        anyhow::ensure!(
+            self.login.max_failures_per_client > 0,
+            "login.max_failures_per_client must be positive"
+        );
+        anyhow::ensure!(
+            self.login.lockout_minutes > 0,
+            "login.lockout_minutes must be positive"
+        );
+        anyhow::ensure!(
+            self.live.heartbeat_timeout_secs > 0,
+            "live.heartbeat_timeout_secs must be positive"
+        );
+        anyhow::ensure!(
+            self.live.channel_capacity > 0,
+            "live.channel_capacity must be positive"
+        );
+        anyhow::ensure!(
+            self.trash.retention_days > 0,
+            "trash.retention_days must be positive"
+        );
+        if self.session.same_site == SameSitePolicy::None && !self.session.secure {
+            anyhow::bail!("session.same_site = \"none\" needs session.secure");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// When a cookie set now should expire.
+    pub(crate) fn cookie_expiry(&self) -> time::OffsetDateTime {
+        time::OffsetDateTime::now_utc() + time::Duration::days(self.cookie_days)
+    }
+}
+
+impl IpRange {
+    pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, ""));
+        let addr = addr
+            .parse::<IpAddr>()
+            .with_context(|| format!("{s} is not an address"))?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = if prefix.is_empty() {
+            max
+        } else {
+            prefix
+                .parse()
+                .ok()
+                .filter(|&prefix| prefix <= max)
+                .with_context(|| format!("{s} has no valid prefix length"))?
+        };
+        Ok(IpRange { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl SessionOptions {
+    pub(crate) fn expiry(&self) -> time::Duration {
+        time::Duration::hours(self.expiry_hours)
+    }
+}
+
+impl From<SameSitePolicy> for SameSite {
+    fn from(policy: SameSitePolicy) -> Self {
+        match policy {
+            SameSitePolicy::Strict => SameSite::Strict,
+            SameSitePolicy::Lax => SameSite::Lax,
+            SameSitePolicy::None => SameSite::None,
+        }
+    }
+}
+
+impl LoginOptions {
+    pub(crate) fn limits(&self) -> LoginLimits {
+        LoginLimits {
+            max_failures: self.max_failures,
+            max_failures_per_client: self.max_failures_per_client,
+            lockout: self.lockout_minutes * 60,
+        }
+    }
+}
+
+impl LiveOptions {
+    pub(crate) fn heartbeat_timeout(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_timeout_secs)
+    }
+
+    pub fn drain(&self) -> Duration {
+        Duration::from_secs(self.drain_secs)
+    }
+}
+
+impl TrashOptions {
+    /// Seconds a zerra stays in the trash.
+    pub(crate) fn retention(&self) -> i64 {
+        self.retention_days * 24 * 60 * 60
+    }
+}
+
+fn set<T: FromStr>(field: &mut T, settings: &impl Settings, name: &str) -> anyhow::Result<()>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Some(value) = settings.get(name) {
+        *field = value
+            .trim()
+            .parse()
+            .with_context(|| format!("{name} is malformed"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    impl Settings for HashMap<&str, &str> {
+        fn get(&self, name: &str) -> Option<String> {
+            HashMap::get(self, name).map(|value| value.to_string())
+        }
+    }
+
+    #[test]
+    fn example_has_the_defaults() {
+        let example: Config = toml::from_str(include_str!("../../zerra.example.toml")).unwrap();
+        assert_eq!(example, Config::default());
+    }
+
+    #[test]
+    fn settings_override_the_file() {
+        let mut config: Config = toml::from_str("[live]\nchannel_capacity = 8").unwrap();
+        let settings = HashMap::from([
+            ("LIVE_HEARTBEAT_TIMEOUT_SECS", "10"),
+            ("SESSION_SAME_SITE", "strict"),
+        ]);
+        config.override_with(&settings).unwrap();
+        assert_eq!(
+            config.live,
+            LiveOptions {
+                heartbeat_timeout_secs: 10,
+                channel_capacity: 8,
+                ..LiveOptions::default()
+            }
+        );
+        assert_eq!(config.session.same_site, SameSitePolicy::Strict);
+
+        config.live.channel_capacity = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn ip_ranges() {
+        let range: IpRange = "10.1.0.0/16".parse().unwrap();
+        assert!(range.contains("10.1.200.3".parse().unwrap()));
+        assert!(range.contains("::ffff:10.1.0.1".parse().unwrap()));
+        assert!(!range.contains("10.2.0.1".parse().unwrap()));
+        let single: IpRange = "::1".parse().unwrap();
+        assert!(single.contains("::1".parse().unwrap()));
+        assert!(!single.contains("127.0.0.1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+    }
+}
@@ -1,46 +1,117 @@
-use std::path::PathBuf;
 use std::sync::Arc;
 
+use anyhow::Context as _;
+use base64::{Engine, engine::general_purpose::STANDARD};
 use dashmap::DashMap;
-use sqlx::PgPool;
+use sqlx::{PgPool, Row};
 use tower_http::services::{ServeDir, ServeFile};
+use tower_sessions::cookie::Key;
 
-use crate::models::conduction::Conduction;
+use crate::{
+    config::Config,
+    models::{collaboration::Edition, conduction::Conduction, unauthenticated},
+    shutdown::Shutdown,
+};
 
 #[derive(Debug, Clone)]
-pub(crate) struct AppState {
-    pub(crate) db: PgPool,
-    pub(crate) assets_dir: Arc<PathBuf>,
+pub struct AppState {
+    pub db: PgPool,
+    pub config: Arc<Config>,
     pub(crate) under_conduction: Arc<DashMap<String, Conduction>>,
+    pub(crate) under_edition: Arc<DashMap<String, Arc<Edition>>>,
+    pub(crate) invitation_key: Arc<[u8]>,
+    pub shutdown: Shutdown,
 }
 
 impl AppState {
-    pub(crate) async fn new(db: sqlx::PgPool, assets_dir: PathBuf) -> anyhow::Result<Self> {
+    pub async fn new(db: sqlx::PgPool, config: Config) -> anyhow::Result<Self> {
+        if !config.assets_dir.join("index.html").is_file() {
+            tracing::warn!("no index.html in {}", config.assets_dir.display());
+        }
         sqlx::migrate!().run(&db).await?;
+        unauthenticated::summarize_all(&db).await?;
+        let invitation_key = secret(&db, "invitation").await?.into();
         Ok(AppState {
             db,
-            assets_dir: Arc::new(assets_dir),
+            config: Arc::new(config),
             under_conduction: Arc::new(DashMap::new()),
+            under_edition: Arc::new(DashMap::new()),
+            invitation_key,
+            shutdown: Shutdown::new(),
         })
     }
 
     pub(crate) fn serve_index(&self) -> ServeFile {
-        ServeFile::new(self.assets_dir.join("index.html"))
+        ServeFile::new(self.config.assets_dir.join("index.html"))
             .precompressed_br()
             .precompressed_gzip()
     }
 
     pub(crate) fn serve_assets(&self) -> ServeDir {
-        ServeDir::new(&*self.assets_dir)
+        ServeDir::new(&self.config.assets_dir)
             .precompressed_br()
             .precompressed_gzip()
     }
 }
 
+/// Where configuration is read from: Shuttle's secrets, or the environment when standalone.
+pub trait Settings {
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+#[cfg(feature = "shuttle")]
+impl Settings for shuttle_runtime::SecretStore {
+    fn get(&self, name: &str) -> Option<String> {
+        shuttle_runtime::SecretStore::get(self, name)
+    }
+}
+
+/// Reads settings from environment variables.
+pub struct Environment;
+
+impl Settings for Environment {
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// Reads `SESSION_KEY` (base64 of at least 64 bytes) from the settings, falling back to a key
+/// generated and kept in the database.
+pub async fn session_key(settings: &impl Settings, db: &PgPool) -> anyhow::Result<Key> {
+    let key = match settings.get("SESSION_KEY") {
+        Some(key) => STANDARD
+            .decode(key.trim())
+            .context("SESSION_KEY is not base64")?,
+        None => {
+            tracing::warn!("SESSION_KEY is not configured; signing with a generated key");
+            secret(db, "session").await?
+        }
+    };
+    Key::try_from(key.as_slice()).context("SESSION_KEY is too short")
+}
+
+/// Fetches a named server secret, generating it on first use.
+async fn secret(db: &PgPool, name: &str) -> sqlx::Result<Vec<u8>> {
+    sqlx::query("insert into secrets (name, value) values ($1, $2) on conflict do nothing")
+        .bind(name)
+        .bind(rand::random::<[u8; 64]>().as_slice())
+        .execute(db)
+        .await?;
+    sqlx::query("select value from secrets where name = $1")
+        .bind(name)
+        .fetch_one(db)
+        .await
+        .map(|r| r.get(0))
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct I18n {
     pub(crate) index_format: &'static str,
     pub(crate) page_number_format: &'static str,
+    /// Labels of the report summary.
+    pub(crate) interviews: &'static str,
+    pub(crate) answered: &'static str,
+    pub(crate) skipped: &'static str,
 }
 
 pub(crate) const I18N: &[(&str, I18n)] = &[
@@ -49,6 +120,9 @@ pub(crate) const I18N: &[(&str, I18n)] = &[
         I18n {
             index_format: "1.",
             page_number_format: "1",
+            interviews: "interviews",
+            answered: "answered",
+            skipped: "skipped",
         },
     ),
     (
@@ -56,6 +130,9 @@ pub(crate) const I18N: &[(&str, I18n)] = &[
         I18n {
             index_format: "১.১)",
             page_number_format: "১",
+            interviews: "সাক্ষাৎকার",
+            answered: "উত্তর",
+            skipped: "বাদ",
         },
     ),
 ];
@@ -0,0 +1,36 @@
+//! The Zerra server, shared by the `backend` binary that serves it and the `zerra-admin` tool.
+
+pub mod admin;
+pub mod config;
+pub mod env;
+pub mod routes;
+pub mod shutdown;
+
+pub(crate) mod models {
+    pub(crate) mod audit;
+    pub(crate) mod authenticated;
+    pub(crate) mod collaboration;
+    pub(crate) mod conduction;
+    pub(crate) mod error;
+    pub(crate) mod folder;
+    pub(crate) mod import;
+    pub(crate) mod invitation;
+    pub(crate) mod outline;
+    pub(crate) mod recovery;
+    pub(crate) mod report;
+    pub(crate) mod sharing;
+    pub(crate) mod spreadsheet;
+    pub(crate) mod template;
+    pub(crate) mod throttle;
+    pub(crate) mod token;
+    pub(crate) mod totp;
+    pub(crate) mod transcription;
+    pub(crate) mod trash;
+    pub(crate) mod unauthenticated;
+    pub(crate) mod user;
+
+    pub(crate) type Result<T = (), E = error::Error> = std::result::Result<T, E>;
+}
+
+pub(crate) mod telemetry;
+pub(crate) mod utils;
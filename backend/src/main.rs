@@ -1,36 +1,104 @@
-pub(crate) mod env;
-pub(crate) mod routes;
-
-pub(crate) mod models {
-    pub(crate) mod authenticated;
-    pub(crate) mod conduction;
-    pub(crate) mod error;
-    pub(crate) mod transcription;
-    pub(crate) mod unauthenticated;
-    pub(crate) mod user;
-
-    pub(crate) type Result<T = (), E = error::Error> = std::result::Result<T, E>;
-}
+use backend::{config, env, routes};
+
+#[cfg(all(feature = "shuttle", feature = "standalone"))]
+compile_error!("features `shuttle` and `standalone` are mutually exclusive");
+
+/// Serves on `bind_address`, or on a socket passed by systemd or `listenfd`, with the database at
+/// `database_url`. The configuration is read as for Shuttle, with environment variables in place of
+/// its secrets.
+#[cfg(feature = "standalone")]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let settings = env::Environment;
+    let config = config::Config::load(&settings)?;
+    let db = sqlx::PgPool::connect(
+        config
+            .database_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("DATABASE_URL is not set"))?,
+    )
+    .await?;
+    let bind_address = config.bind_address.clone();
 
-pub(crate) mod utils;
+    let state = env::AppState::new(db, config).await?;
+    let session_key = env::session_key(&settings, &state.db).await?;
 
-// #[tokio::main]
-// async fn main() -> anyhow::Result<()> {
-//     tracing_subscriber::fmt()
-//         .with_max_level(tracing::Level::DEBUG)
-//         .init();
+    let listener = match listenfd::ListenFd::from_env().take_tcp_listener(0)? {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener)?
+        }
+        None => tokio::net::TcpListener::bind(bind_address).await?,
+    };
 
-//     let (listener, state) = env::AppState::get_or_default().await?;
+    tracing::info!("Listening on {}", listener.local_addr()?);
+    tracing::info!("Serving files in {}", state.config.assets_dir.display());
 
-//     tracing::info!("Listening on {:?}", listener);
-//     tracing::info!("Serving files in {}", state.assets_dir.display());
+    let shutdown = state.shutdown.clone();
+    let drain = state.config.live.drain();
+    let router = routes::router(state.clone(), session_key).await?;
+    routes::spawn_housekeeping(&state);
+    // The address of each connection tells clients apart, e.g. for throttling logins.
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        shutdown.drain(drain).await;
+    })
+    .await?;
+    Ok(())
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM from a service manager.
+#[cfg(feature = "standalone")]
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(?e, "cannot listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
 
-//     axum::serve(listener, routes::router(state).await?).await?;
-//     Ok(())
-// }
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(?e, "cannot listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => {}
+        () = terminate => {}
+    }
+    tracing::info!("Shutting down");
+}
 
+/// Serves on Shuttle, which decides how connections are accepted and when they are dropped. Unless
+/// it passes on the address of each connection, clients are told apart only through
+/// `trusted_proxies`, and live sockets are not drained when it stops the server.
+#[cfg(feature = "shuttle")]
 #[shuttle_runtime::main]
-async fn main(#[shuttle_shared_db::Postgres] db: sqlx::PgPool) -> shuttle_axum::ShuttleAxum {
-    let state = env::AppState::new(db, std::path::PathBuf::from("frontend/dist")).await?;
-    Ok(routes::router(state).await?.into())
+async fn main(
+    #[shuttle_shared_db::Postgres] db: sqlx::PgPool,
+    #[shuttle_runtime::Secrets] secrets: shuttle_runtime::SecretStore,
+) -> shuttle_axum::ShuttleAxum {
+    let state = env::AppState::new(db, config::Config::load(&secrets)?).await?;
+    let session_key = env::session_key(&secrets, &state.db).await?;
+    let router = routes::router(state.clone(), session_key).await?;
+    routes::spawn_housekeeping(&state);
+    Ok(router.into())
 }
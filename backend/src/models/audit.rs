@@ -0,0 +1,336 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+use sqlx::{PgPool, Row};
+use types::jiff::Timestamp;
+
+use crate::config::IpRange;
+
+/// The client of a request whose address is not known, e.g. because the server was not given the
+/// addresses of its connections.
+pub(crate) const UNKNOWN_CLIENT: &str = "unknown";
+
+/// The reverse proxies whose `X-Forwarded-For` is believed, as configured, for [`Origin`] to find in
+/// the extensions of a request.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrustedProxies(pub(crate) Arc<[IpRange]>);
+
+/// Where a request came from, as recorded in the audit log.
+#[derive(Debug, Clone)]
+pub(crate) struct Origin {
+    pub(crate) client: String,
+    pub(crate) user_agent: String,
+}
+
+impl Origin {
+    /// Stands for whoever runs `zerra-admin`.
+    pub(crate) fn cli() -> Self {
+        Origin {
+            client: "cli".to_string(),
+            user_agent: concat!("zerra-admin/", env!("CARGO_PKG_VERSION")).to_string(),
+        }
+    }
+
+    /// Stands for the server acting on its own, e.g. purging the trash.
+    pub(crate) fn server() -> Self {
+        Origin {
+            client: "server".to_string(),
+            user_agent: concat!("zerra/", env!("CARGO_PKG_VERSION")).to_string(),
+        }
+    }
+
+    fn of(parts: &Parts) -> Self {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let trusted = parts
+            .extensions
+            .get::<TrustedProxies>()
+            .map_or(&[][..], |TrustedProxies(trusted)| trusted);
+        let forwarded_for = parts
+            .headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+        let client = peer.map_or_else(
+            || UNKNOWN_CLIENT.to_string(),
+            |peer| client_of(peer, forwarded_for, trusted).to_string(),
+        );
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+        Origin { client, user_agent }
+    }
+}
+
+/// The address of the client behind `peer`. Each trusted proxy appends the address it was connected
+/// from to `X-Forwarded-For`, so the hops are followed back from the last for as long as they pass
+/// through trusted proxies. Anything before that may have been made up by the client.
+fn client_of<'h>(
+    peer: IpAddr,
+    forwarded_for: impl DoubleEndedIterator<Item = &'h str>,
+    trusted: &[IpRange],
+) -> IpAddr {
+    let mut client = peer.to_canonical();
+    for hop in forwarded_for.rev() {
+        if !trusted.iter().any(|range| range.contains(client)) {
+            break;
+        }
+        match hop.trim().parse::<IpAddr>() {
+            Ok(hop) => client = hop.to_canonical(),
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Origin {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Infallible> {
+        Ok(Origin::of(parts))
+    }
+}
+
+/// Something worth answering for later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    Login,
+    FailedLogin,
+    Logout,
+    CreateZerra,
+    CopyZerra,
+    ImportZerra,
+    CreateTemplate,
+    StartInterview,
+    TrashZerra,
+    RestoreZerra,
+    DeleteZerra,
+    ClaimAttendance,
+    RejectAttendance,
+    DownloadTranscript,
+    ResetProgress,
+    EnableTotp,
+    DisableTotp,
+    UseBackupCode,
+    MintToken,
+    RevokeToken,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Login => "login",
+            Action::FailedLogin => "failed_login",
+            Action::Logout => "logout",
+            Action::CreateZerra => "create_zerra",
+            Action::CopyZerra => "copy_zerra",
+            Action::ImportZerra => "import_zerra",
+            Action::CreateTemplate => "create_template",
+            Action::StartInterview => "start_interview",
+            Action::TrashZerra => "trash_zerra",
+            Action::RestoreZerra => "restore_zerra",
+            Action::DeleteZerra => "delete_zerra",
+            Action::ClaimAttendance => "claim_attendance",
+            Action::RejectAttendance => "reject_attendance",
+            Action::DownloadTranscript => "download_transcript",
+            Action::ResetProgress => "reset_progress",
+            Action::EnableTotp => "enable_totp",
+            Action::DisableTotp => "disable_totp",
+            Action::UseBackupCode => "use_backup_code",
+            Action::MintToken => "mint_token",
+            Action::RevokeToken => "revoke_token",
+        }
+    }
+}
+
+/// Appends to the audit log. `actor` is the user responsible, if any is logged in, and `detail`
+/// what else identifies the event, such as the username of a failed login or the UUID of an
+/// attendee.
+pub(crate) async fn record(
+    db: impl sqlx::PgExecutor<'_>,
+    origin: &Origin,
+    actor: Option<i64>,
+    action: Action,
+    zerra_id: Option<&str>,
+    detail: Option<&str>,
+) -> sqlx::Result<()> {
+    tracing::info!(
+        target: "audit",
+        actor,
+        action = action.name(),
+        zerra = zerra_id,
+        client = origin.client,
+        detail,
+    );
+    sqlx::query(
+        "insert into audit_log (at, actor, action, zerra_id, client, user_agent, detail) \
+         values ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(Timestamp::now().as_second())
+    .bind(actor)
+    .bind(action.name())
+    .bind(zerra_id)
+    .bind(&origin.client)
+    .bind(&origin.user_agent)
+    .bind(detail)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Appends to the audit log, as [`record`] does, unless the same event from the same client was
+/// recorded in the last `window` seconds, so that a client retrying again and again leaves one
+/// entry rather than one per attempt. Returns whether it was recorded.
+pub(crate) async fn record_unless_recent(
+    db: impl sqlx::PgExecutor<'_>,
+    origin: &Origin,
+    action: Action,
+    zerra_id: Option<&str>,
+    detail: Option<&str>,
+    window: i64,
+) -> sqlx::Result<bool> {
+    let now = Timestamp::now().as_second();
+    let recorded = sqlx::query(
+        "insert into audit_log (at, actor, action, zerra_id, client, user_agent, detail) \
+         select $1, null, $2, $3, $4, $5, $6 \
+         where not exists (select 1 from audit_log \
+             where action = $2 and zerra_id is not distinct from $3 and client = $4 \
+             and detail is not distinct from $6 and at > $1 - $7)",
+    )
+    .bind(now)
+    .bind(action.name())
+    .bind(zerra_id)
+    .bind(&origin.client)
+    .bind(&origin.user_agent)
+    .bind(detail)
+    .bind(window)
+    .execute(db)
+    .await?
+    .rows_affected()
+        > 0;
+    if recorded {
+        tracing::info!(
+            target: "audit",
+            action = action.name(),
+            zerra = zerra_id,
+            client = origin.client,
+            detail,
+        );
+    }
+    Ok(recorded)
+}
+
+/// An entry of the audit log, with its actor named if they still exist.
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) at: Timestamp,
+    pub(crate) actor: Option<i64>,
+    pub(crate) username: Option<String>,
+    pub(crate) action: String,
+    pub(crate) zerra_id: Option<String>,
+    pub(crate) client: String,
+    pub(crate) user_agent: String,
+    pub(crate) detail: Option<String>,
+}
+
+/// The latest entries, newest first, optionally only those of an actor or about a zerra.
+pub(crate) async fn entries(
+    db: &PgPool,
+    actor: Option<i64>,
+    zerra_id: Option<&str>,
+    since: Option<Timestamp>,
+    limit: i64,
+) -> sqlx::Result<Vec<Entry>> {
+    sqlx::query(
+        "select audit_log.*, users.username from audit_log \
+         left join users on users.id = audit_log.actor \
+         where ($1::int8 is null or audit_log.actor = $1) \
+         and ($2::text is null or audit_log.zerra_id = $2) \
+         and audit_log.at >= $3 \
+         order by audit_log.at desc, audit_log.id desc limit $4",
+    )
+    .bind(actor)
+    .bind(zerra_id)
+    .bind(since.map_or(i64::MIN, |since| since.as_second()))
+    .bind(limit)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|r| {
+        Ok(Entry {
+            at: Timestamp::from_second(r.try_get("at")?)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?,
+            actor: r.try_get("actor")?,
+            username: r.try_get("username")?,
+            action: r.try_get("action")?,
+            zerra_id: r.try_get("zerra_id")?,
+            client: r.try_get("client")?,
+            user_agent: r.try_get("user_agent")?,
+            detail: r.try_get("detail")?,
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_only_by_trusted_proxies() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        let client = |peer: &str, forwarded_for: &str| {
+            client_of(
+                peer.parse().unwrap(),
+                forwarded_for.split(',').filter(|hop| !hop.is_empty()),
+                &trusted,
+            )
+            .to_string()
+        };
+        // Whoever connects directly cannot pass for anyone else.
+        assert_eq!(client("203.0.113.7", "198.51.100.1"), "203.0.113.7");
+        assert_eq!(client("10.0.0.2", ""), "10.0.0.2");
+        assert_eq!(
+            client("10.0.0.2", "198.51.100.1, 203.0.113.7"),
+            "203.0.113.7"
+        );
+        assert_eq!(
+            client("10.0.0.2", "198.51.100.1, 203.0.113.7, 10.0.0.3"),
+            "203.0.113.7"
+        );
+        assert_eq!(client("10.0.0.2", "nonsense"), "10.0.0.2");
+    }
+
+    #[sqlx::test]
+    async fn repeated_events_recorded_once(db: PgPool) -> sqlx::Result<()> {
+        let origin = Origin::server();
+        let reject = |detail| {
+            record_unless_recent(
+                &db,
+                &origin,
+                Action::RejectAttendance,
+                Some("zerra"),
+                Some(detail),
+                60,
+            )
+        };
+        assert!(reject("already attended").await?);
+        assert!(!reject("already attended").await?);
+        assert!(reject("no invitation").await?);
+        assert_eq!(entries(&db, None, Some("zerra"), None, 10).await?.len(), 2);
+        Ok(())
+    }
+}
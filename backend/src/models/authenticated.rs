@@ -1,12 +1,38 @@
+use std::collections::HashSet;
 use std::pin::Pin;
 
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::WebSocket;
 use axum_extra::response::Attachment;
-use futures_util::{SinkExt, StreamExt};
-use sqlx::{PgPool, Row};
-use types::ListItem;
+use dashmap::DashMap;
+use sqlx::{PgPool, Row, postgres::PgRow};
+use types::{
+    ListItem, ListPage, ListQuery, ListSort, LiveConduction, ZerraStatus,
+    sharing::Role,
+    zerra::{Avatar, Persona},
+};
 
-use crate::models::{Result, error::Error, unauthenticated::update_blob, user::User};
+use crate::{
+    env::AppState,
+    models::{
+        Result,
+        audit::{self, Action, Origin},
+        collaboration::collaborate,
+        conduction::Conduction,
+        error::Error,
+        import,
+        sharing::{GRANTS, decode_role},
+        template::refuse_template,
+        unauthenticated::{Summary, check_persona, fetch_blob, persona_of},
+        user::User,
+    },
+    shutdown::Draining,
+};
+
+const DEFAULT_PAGE: usize = 50;
+const MAX_PAGE: usize = 200;
+/// Roles from weakest to strongest, and statuses in the order zerrae go through them, for SQL.
+const ROLES: &str = "array['viewer', 'editor', 'conductor', 'owner']";
+const STATUSES: &str = "array['draft', 'live', 'finished']";
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct Context<'db> {
@@ -21,15 +47,98 @@ impl<'db> Context<'db> {
         Ok((user, ctx))
     }
 
-    pub(crate) async fn fetch_zerrae(self) -> Result<Vec<ListItem>> {
-        Ok(sqlx::query("select * from vus where owner = $1")
-            .bind(self.user)
+    pub(crate) async fn fetch_zerrae(
+        self,
+        under_conduction: &DashMap<String, Conduction>,
+        query: &ListQuery,
+    ) -> Result<ListPage> {
+        // A zerra reachable through several grants is listed once, with the strongest role.
+        let matching = format!(
+            "from vus join ( \
+                 select distinct on (zerra_id) zerra_id, role from ({GRANTS}) g \
+                 order by zerra_id, array_position({ROLES}, role) desc \
+             ) g on g.zerra_id = vus.id \
+             left join filings f on f.zerra_id = vus.id and f.user_id = $1 \
+             where not vus.is_template \
+             and ($2::int8 is null or f.folder_id = $2) \
+             and ($3::text is null or exists \
+                 (select 1 from tags where tags.zerra_id = vus.id and tags.tag = $3)) \
+             and ($4::text is null or vus.template_id = $4) \
+             and ($5::text is null or vus.status = $5) \
+             and ($6::text is null or strpos(lower(vus.title), $6) > 0 \
+                 or strpos(lower(vus.participant), $6) > 0)"
+        );
+        let search = query.search.as_deref().map(str::to_lowercase);
+        let filtered = |sql| {
+            sqlx::query(sql)
+                .bind(self.user)
+                .bind(query.folder)
+                .bind(query.tag.as_deref())
+                .bind(query.template.as_deref())
+                .bind(query.status.map(ZerraStatus::as_str))
+                .bind(search.as_deref())
+        };
+
+        let sort = query.sort.unwrap_or_default();
+        let key = match sort {
+            ListSort::LastModified => "vus.last_modified".to_string(),
+            ListSort::Name => "lower(vus.title)".to_string(),
+            ListSort::Status => format!("array_position({STATUSES}, vus.status)"),
+        };
+        let direction = if query.descending.unwrap_or(sort == ListSort::LastModified) {
+            "desc"
+        } else {
+            "asc"
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE).min(MAX_PAGE);
+        let page = format!(
+            "select vus.id, vus.title, vus.status, vus.participant, vus.last_modified, \
+             vus.template_id, vus.template_version, g.role, f.folder_id, \
+             array(select tag from tags where tags.zerra_id = vus.id order by tag) as tags \
+             {matching} order by {key} {direction}, vus.id {direction} limit $7 offset $8"
+        );
+        let items = filtered(&page)
+            .bind(limit as i64)
+            .bind(query.offset.unwrap_or(0) as i64)
             .try_map(try_from_row)
             .fetch_all(self.db)
-            .await?)
+            .await?
+            .into_iter()
+            .map(|item| ListItem {
+                live: under_conduction.contains_key(&item.id),
+                ..item
+            })
+            .collect();
+        let count = format!("select count(*) {matching}");
+        let total: i64 = filtered(&count).fetch_one(self.db).await?.try_get(0)?;
+
+        Ok(ListPage {
+            items,
+            total: total as usize,
+        })
+    }
+
+    /// The conductions in progress of the zerrae the user has access to.
+    pub(crate) async fn live_conductions(
+        self,
+        under_conduction: &DashMap<String, Conduction>,
+    ) -> Result<Vec<LiveConduction>> {
+        let query = format!("select distinct g.zerra_id from ({GRANTS}) g");
+        let accessible: HashSet<String> = sqlx::query(&query)
+            .bind(self.user)
+            .try_map(|r: PgRow| r.try_get(0))
+            .fetch_all(self.db)
+            .await?
+            .into_iter()
+            .collect();
+        Ok(under_conduction
+            .iter()
+            .filter(|entry| accessible.contains(entry.key()))
+            .map(|entry| entry.describe(entry.key()))
+            .collect())
     }
 
-    pub(crate) async fn new_zerra(self) -> Result {
+    pub(crate) async fn new_zerra(self, origin: &Origin) -> Result {
         let id = uuid::Uuid::new_v4().hyphenated().to_string();
 
         let zerra = types::zerra::Zerra {
@@ -48,159 +157,233 @@ impl<'db> Context<'db> {
             }],
         };
 
-        sqlx::query("insert into vus (id, owner, data, last_modified) values ($1, $2, $3, $4)")
-            .bind(&zerra.id)
-            .bind(self.user)
-            .bind(rmp_serde::to_vec_named(&zerra)?)
-            .bind(types::jiff::Timestamp::now().as_second())
-            .execute(self.db)
-            .await?;
+        let summary = Summary::of(&zerra);
+        sqlx::query(
+            "insert into vus (id, owner, data, last_modified, title, status, participant) \
+             values ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&zerra.id)
+        .bind(self.user)
+        .bind(rmp_serde::to_vec_named(&zerra)?)
+        .bind(types::jiff::Timestamp::now().as_second())
+        .bind(summary.title)
+        .bind(summary.status)
+        .bind(summary.participant)
+        .execute(self.db)
+        .await?;
 
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::CreateZerra,
+            Some(&zerra.id),
+            None,
+        )
+        .await?;
         Ok(())
     }
 
-    pub(crate) async fn copy(self, from_id: &str) -> Result {
-        let from_blob = sqlx::query("select data from vus where owner = $1 and id = $2")
-            .bind(self.user)
-            .bind(from_id)
-            .fetch_one(self.db)
-            .await
-            .map(|r| r.get::<Vec<u8>, _>(0))?;
+    pub(crate) async fn copy(self, from_id: &str, origin: &Origin) -> Result {
+        self.authorize(from_id, Role::Viewer).await?;
+        let from_blob = fetch_blob(self.db, from_id).await?;
 
         let new_zerra = types::zerra::Zerra {
             id: uuid::Uuid::new_v4().hyphenated().to_string(),
             ..rmp_serde::from_slice(&from_blob)?
         };
 
-        sqlx::query("insert into vus (id, owner, data, last_modified) values ($1, $2, $3, $4)")
-            .bind(&new_zerra.id)
-            .bind(self.user)
-            .bind(rmp_serde::to_vec_named(&new_zerra)?)
-            .bind(types::jiff::Timestamp::now().as_second())
-            .execute(self.db)
-            .await?;
+        let summary = Summary::of(&new_zerra);
+        sqlx::query(
+            "insert into vus (id, owner, data, last_modified, title, status, participant) \
+             values ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&new_zerra.id)
+        .bind(self.user)
+        .bind(rmp_serde::to_vec_named(&new_zerra)?)
+        .bind(types::jiff::Timestamp::now().as_second())
+        .bind(summary.title)
+        .bind(summary.status)
+        .bind(summary.participant)
+        .execute(self.db)
+        .await?;
 
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::CopyZerra,
+            Some(&new_zerra.id),
+            Some(&format!("copied from {from_id}")),
+        )
+        .await?;
         Ok(())
     }
 
     pub(crate) async fn export(self, id: &str) -> Result<Attachment<String>> {
-        let blob = sqlx::query("select data from vus where owner = $1 and id = $2")
-            .bind(self.user)
-            .bind(id)
-            .fetch_one(self.db)
-            .await
-            .map(|r| r.get::<Vec<u8>, _>(0))?;
-        let zerra: types::zerra::Zerra = rmp_serde::from_slice(&blob)?;
-        let yaml = serde_yaml2::to_string(&zerra)?;
+        let yaml = self.export_yaml(id).await?;
         Ok(Attachment::new(yaml)
             .content_type("application/yaml")
             .filename(format!("{id}.yaml")))
     }
 
-    pub(crate) async fn import(self, yaml: &str) -> Result {
+    pub(crate) async fn export_yaml(self, id: &str) -> Result<String> {
+        self.authorize(id, Role::Viewer).await?;
+        let blob = fetch_blob(self.db, id).await?;
+        let zerra: types::zerra::Zerra = rmp_serde::from_slice(&blob)?;
+        Ok(serde_yaml2::to_string(&zerra)?)
+    }
+
+    /// Stores a zerra exported as YAML under a new ID, which is returned.
+    pub(crate) async fn import(self, yaml: &str, origin: &Origin) -> Result<String> {
+        self.store_import(import::from_yaml(yaml)?, origin).await
+    }
+
+    /// Stores a script drafted as an outline under a new ID, which is returned.
+    pub(crate) async fn import_outline(self, text: &str, origin: &Origin) -> Result<String> {
+        self.store_import(import::from_outline(text)?, origin).await
+    }
+
+    /// Stores a zerra read for import under a new ID, which is returned.
+    pub(crate) async fn store_import(
+        self,
+        zerra: types::zerra::Zerra,
+        origin: &Origin,
+    ) -> Result<String> {
         let zerra = types::zerra::Zerra {
             id: uuid::Uuid::new_v4().hyphenated().to_string(),
-            ..serde_yaml2::from_str(yaml)?
+            ..zerra
         };
 
-        sqlx::query("insert into vus (id, owner, data, last_modified) values ($1, $2, $3, $4)")
-            .bind(&zerra.id)
+        let summary = Summary::of(&zerra);
+        sqlx::query(
+            "insert into vus (id, owner, data, last_modified, title, status, participant) \
+             values ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&zerra.id)
+        .bind(self.user)
+        .bind(rmp_serde::to_vec_named(&zerra)?)
+        .bind(types::jiff::Timestamp::now().as_second())
+        .bind(summary.title)
+        .bind(summary.status)
+        .bind(summary.participant)
+        .execute(self.db)
+        .await?;
+
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::ImportZerra,
+            Some(&zerra.id),
+            None,
+        )
+        .await?;
+        Ok(zerra.id)
+    }
+
+    pub(crate) async fn persona(self) -> Result<Persona> {
+        let user: User = sqlx::query_as("select * from users where id = $1")
             .bind(self.user)
-            .bind(rmp_serde::to_vec_named(&zerra)?)
-            .bind(types::jiff::Timestamp::now().as_second())
-            .execute(self.db)
+            .fetch_one(self.db)
             .await?;
-
-        Ok(())
+        Ok(persona_of(self.db, &user).await?)
     }
 
-    pub(crate) async fn delete(self, id: &str) -> Result {
-        sqlx::query("delete from vus where owner = $1 and id = $2")
+    pub(crate) async fn set_persona(self, Persona { pronouns, avatar }: Persona) -> Result {
+        let persona = Persona {
+            pronouns: pronouns
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty()),
+            avatar,
+        };
+        check_persona(&persona).map_err(Error::BadRequest)?;
+
+        let Persona { pronouns, avatar } = persona;
+        let (kind, image) = match avatar {
+            Avatar::Neutral => ("neutral", None),
+            Avatar::Man => ("man", None),
+            Avatar::Woman => ("woman", None),
+            Avatar::Image { mime, data } => ("image", Some((mime, data))),
+        };
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("update users set pronouns = $1, avatar = $2 where id = $3")
+            .bind(pronouns)
+            .bind(kind)
             .bind(self.user)
-            .bind(id)
-            .execute(self.db)
+            .execute(&mut *tx)
+            .await?;
+        if let Some((mime, data)) = image {
+            sqlx::query(
+                "insert into avatars (user_id, mime, data) values ($1, $2, $3) \
+                 on conflict (user_id) do update set mime = excluded.mime, data = excluded.data",
+            )
+            .bind(self.user)
+            .bind(mime)
+            .bind(data)
+            .execute(&mut *tx)
             .await?;
+        } else {
+            sqlx::query("delete from avatars where user_id = $1")
+                .bind(self.user)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
         Ok(())
     }
 
     pub(crate) fn edit(
         self,
         id: String,
+        name: String,
         blob: Vec<u8>,
+        state: AppState,
+        draining: Draining,
     ) -> impl FnOnce(WebSocket) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> + Send + 'static
     {
-        let db = self.db.clone();
-
-        move |ws| {
-            Box::pin(async move {
-                let (mut writer, mut reader) = ws.split();
-
-                let blob_len = blob.len();
-                if let Err(e) = writer.send(Message::binary(blob)).await {
-                    tracing::error!(%e);
-                    if let Err(e) = writer.close().await {
-                        tracing::error!(%e);
-                    }
-                    return;
-                }
-                tracing::debug!("Sent {blob_len} bytes");
-
-                while let Some(msg) = reader.next().await {
-                    match msg {
-                        Ok(Message::Binary(blob)) => {
-                            if let Err(e) = update_blob(&db, &id, &blob).await {
-                                tracing::error!(%e);
-                                if let Err(e) = writer.close().await {
-                                    tracing::error!(%e);
-                                }
-                                break;
-                            }
-                        }
-                        Ok(_) => {}
-                        Err(e) => {
-                            tracing::error!(%e);
-                            if let Err(e) = writer.close().await {
-                                tracing::error!(%e);
-                            }
-                            break;
-                        }
-                    }
-                }
-            })
-        }
+        move |ws| Box::pin(collaborate(state, id, name, blob, draining, ws))
     }
 
-    pub(crate) async fn owns(self, zerra_id: &str) -> Result {
-        sqlx::query("select count(1) from vus where owner = $1 and id = $2")
+    pub(crate) async fn conducts(self, zerra_id: &str) -> Result {
+        refuse_template(self.db, zerra_id).await?;
+        sqlx::query("update vus set conducted_by = $1 where id = $2")
             .bind(self.user)
             .bind(zerra_id)
-            .fetch_one(self.db)
-            .await
-            .map(|r| r.get::<i64, _>(0) != 0)?
-            .then_some(())
-            .ok_or_else(|| {
-                Error::Unauthorized(format!("user({}) does not own zerra {zerra_id}", self.user))
-            })
+            .execute(self.db)
+            .await?;
+        Ok(())
     }
 }
 
 fn try_from_row(row: sqlx::postgres::PgRow) -> sqlx::Result<ListItem> {
     let id: String = row.try_get("id")?;
-    let blob: Vec<u8> = row.try_get("data")?;
     let last_modified: i64 = row.try_get("last_modified")?;
-
-    #[derive(serde::Deserialize)]
-    struct Metadata {
-        title: String,
-    }
-    let metadata: Metadata =
-        rmp_serde::from_read(blob.as_slice()).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+    let role = decode_role(row.try_get("role")?)?;
+    // Zerrae that could not be summarised are listed as untitled drafts.
+    let status = match row.try_get::<Option<&str>, _>("status")? {
+        Some(status) => ZerraStatus::from_name(status)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown status {status}").into()))?,
+        None => ZerraStatus::Draft,
+    };
 
     Ok(ListItem {
         id,
-        name: metadata.title,
+        name: row
+            .try_get::<Option<String>, _>("title")?
+            .unwrap_or_default(),
         last_modified: types::jiff::Timestamp::from_second(last_modified)
             .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        role,
+        live: false,
+        status,
+        participant: row.try_get("participant")?,
+        folder: row.try_get("folder_id")?,
+        tags: row.try_get("tags")?,
+        template: row.try_get("template_id")?,
+        template_version: row.try_get("template_version")?,
     })
 }
 
@@ -224,3 +407,153 @@ fn make_demo() {
     };
     std::fs::write(id, rmp_serde::to_vec_named(&z).unwrap()).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use types::zerra::{Progress, Zerra};
+
+    use super::*;
+
+    async fn zerra(ctx: Context<'_>, title: &str, progress: Progress) -> Result<String> {
+        let zerra = Zerra {
+            id: String::new(),
+            title: title.to_string(),
+            progress,
+            flow: Vec::new(),
+        };
+        ctx.store_import(zerra, &Origin::server()).await
+    }
+
+    async fn list(ctx: Context<'_>, query: ListQuery) -> Result<(Vec<String>, usize)> {
+        let page = ctx.fetch_zerrae(&DashMap::new(), &query).await?;
+        Ok((
+            page.items.into_iter().map(|item| item.name).collect(),
+            page.total,
+        ))
+    }
+
+    #[sqlx::test]
+    async fn filter_sort_and_page(db: PgPool) -> Result {
+        let ctx = Context { db: &db, user: 1 };
+        let beta = zerra(ctx, "beta", Progress::None).await?;
+        zerra(
+            ctx,
+            "Alpha",
+            Progress::Finished {
+                participant_uuid: "p".to_string(),
+                participant_name: "Rahim".to_string(),
+                participant_persona: Persona::default(),
+                printable: true,
+            },
+        )
+        .await?;
+        let gamma = zerra(
+            ctx,
+            "gamma",
+            Progress::Intro {
+                participant_uuid: "q".to_string(),
+            },
+        )
+        .await?;
+        let folder = ctx.new_folder("kept").await?;
+        ctx.file(&beta, Some(folder.id)).await?;
+        ctx.set_tags(&gamma, vec!["urgent".to_string()]).await?;
+
+        let by = |sort| ListQuery {
+            sort: Some(sort),
+            ..ListQuery::default()
+        };
+        assert_eq!(
+            list(ctx, by(ListSort::Name)).await?,
+            (vec!["Alpha".into(), "beta".into(), "gamma".into()], 3)
+        );
+        assert_eq!(
+            list(ctx, by(ListSort::Status)).await?.0,
+            ["beta", "gamma", "Alpha"]
+        );
+        assert_eq!(
+            list(
+                ctx,
+                ListQuery {
+                    descending: Some(true),
+                    ..by(ListSort::Status)
+                }
+            )
+            .await?
+            .0,
+            ["Alpha", "gamma", "beta"]
+        );
+
+        let only = |query| async move { list(ctx, query).await.map(|(names, _)| names) };
+        let folder_query = ListQuery {
+            folder: Some(folder.id),
+            ..ListQuery::default()
+        };
+        assert_eq!(only(folder_query).await?, ["beta"]);
+        let tag_query = ListQuery {
+            tag: Some("urgent".to_string()),
+            ..ListQuery::default()
+        };
+        assert_eq!(only(tag_query).await?, ["gamma"]);
+        let status_query = ListQuery {
+            status: Some(ZerraStatus::Finished),
+            ..ListQuery::default()
+        };
+        assert_eq!(only(status_query).await?, ["Alpha"]);
+        let search_query = ListQuery {
+            search: Some("RAH".to_string()),
+            ..ListQuery::default()
+        };
+        assert_eq!(only(search_query).await?, ["Alpha"]);
+
+        assert_eq!(
+            list(
+                ctx,
+                ListQuery {
+                    offset: Some(1),
+                    limit: Some(1),
+                    ..by(ListSort::Name)
+                }
+            )
+            .await?,
+            (vec!["beta".into()], 3)
+        );
+        assert_eq!(
+            list(
+                ctx,
+                ListQuery {
+                    offset: Some(3),
+                    ..by(ListSort::Name)
+                }
+            )
+            .await?,
+            (Vec::new(), 3)
+        );
+
+        // Zerrae modified in the same second are paged through without repeats or gaps.
+        let mut paged = Vec::new();
+        for offset in 0..3 {
+            let query = ListQuery {
+                offset: Some(offset),
+                limit: Some(1),
+                ..ListQuery::default()
+            };
+            paged.extend(list(ctx, query).await?.0);
+        }
+        paged.sort();
+        assert_eq!(paged, ["Alpha", "beta", "gamma"]);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn unknown_folder(db: PgPool) -> Result {
+        let ctx = Context { db: &db, user: 1 };
+        let folder = ctx.new_folder("kept").await?;
+        ctx.delete_folder(folder.id).await?;
+        assert!(matches!(
+            ctx.delete_folder(folder.id).await,
+            Err(Error::NotFound(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+use std::{
+    collections::HashMap,
+    pin::pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+};
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
+use dashmap::DashMap;
+use futures_util::{SinkExt, StreamExt, stream};
+use scopeguard::guard;
+use sqlx::PgPool;
+use tokio::{
+    spawn,
+    sync::{broadcast, mpsc},
+};
+use types::{
+    tx::{EditorPresence, MessageFromEditor, MessageToClient},
+    zerra::Zerra,
+};
+use yrs::{
+    Any, Doc, In, Map, MapRef, ReadTxn, StateVector, Transact, Update,
+    encoding::serde::{from_any, to_any},
+    types::ToJson,
+    updates::decoder::Decode,
+};
+
+use crate::{
+    env::AppState,
+    models::unauthenticated::update_blob,
+    shutdown::{self, Draining},
+    telemetry,
+    utils::ResultExt as _,
+};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Broadcast {
+    Patch { from: u32, patch: Vec<u8> },
+    Awareness(Vec<EditorPresence>),
+    Evict,
+}
+
+/// An authoring session shared by everyone editing the same zerra.
+///
+/// The session keeps its own Yjs document, seeded from the stored zerra, into which every editor's
+/// patches are merged. Editors start from that document, so their patches apply to the same items
+/// and concurrent changes survive one another. After each patch, the zerra read back from the
+/// document is stored and the patch is relayed to every other editor.
+#[derive(Debug)]
+pub(crate) struct Edition {
+    replica: tokio::sync::Mutex<Replica>,
+    editors: Mutex<HashMap<u32, EditorPresence>>,
+    next_editor: AtomicU32,
+    evicted: AtomicBool,
+    tx_broadcast: broadcast::Sender<Broadcast>,
+}
+
+/// The session's Yjs document along with its last stored state, to which it is rolled back when a
+/// patch cannot be merged or stored.
+#[derive(Debug)]
+struct Replica {
+    doc: Doc,
+    root: MapRef,
+    state: Vec<u8>,
+}
+
+/// Builds the shared types that the frontend makes of plain objects and arrays.
+fn prelim(any: &Any) -> In {
+    match any {
+        Any::Array(items) => In::Array(items.iter().map(prelim).collect()),
+        Any::Map(entries) => In::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.as_str(), prelim(v)))
+                .collect(),
+        ),
+        any => In::Any(any.clone()),
+    }
+}
+
+impl Replica {
+    fn seed(zerra: &Zerra) -> anyhow::Result<Self> {
+        let Any::Map(fields) = to_any(zerra)? else {
+            anyhow::bail!("zerra is not a map");
+        };
+        let (doc, root) = Replica::empty();
+        let mut txn = doc.transact_mut();
+        for (field, value) in fields.iter() {
+            root.insert(&mut txn, field.as_str(), prelim(value));
+        }
+        let state = txn.encode_state_as_update_v1(&StateVector::default());
+        drop(txn);
+        Ok(Replica { doc, root, state })
+    }
+
+    fn restore(state: Vec<u8>) -> anyhow::Result<Self> {
+        let (doc, root) = Replica::empty();
+        doc.transact_mut()
+            .apply_update(Update::decode_v1(&state)?)?;
+        Ok(Replica { doc, root, state })
+    }
+
+    /// A document with the map that a Yjs client binds to by default.
+    fn empty() -> (Doc, MapRef) {
+        let doc = Doc::new();
+        let root = doc.get_or_insert_map("");
+        (doc, root)
+    }
+
+    /// Merges a patch, returning the zerra it leaves and the document's new state.
+    fn merge(&self, patch: &[u8]) -> anyhow::Result<(Zerra, Vec<u8>)> {
+        let mut txn = self.doc.transact_mut();
+        txn.apply_update(Update::decode_v1(patch)?)?;
+        let zerra = from_any(&self.root.to_json(&txn))?;
+        Ok((
+            zerra,
+            txn.encode_state_as_update_v1(&StateVector::default()),
+        ))
+    }
+}
+
+impl Edition {
+    fn new(blob: &[u8]) -> anyhow::Result<Self> {
+        let zerra = rmp_serde::from_slice(blob)?;
+        Ok(Edition {
+            replica: tokio::sync::Mutex::new(Replica::seed(&zerra)?),
+            editors: Mutex::new(HashMap::new()),
+            next_editor: AtomicU32::new(0),
+            evicted: AtomicBool::new(false),
+            tx_broadcast: broadcast::channel(64).0,
+        })
+    }
+
+    fn join(
+        under_edition: &DashMap<String, Arc<Edition>>,
+        zerra_id: &str,
+        blob: &[u8],
+        name: String,
+    ) -> anyhow::Result<(Arc<Edition>, u32, broadcast::Receiver<Broadcast>)> {
+        // Registering while holding the entry keeps a concurrent `leave` from dropping the session.
+        let entry = under_edition
+            .entry(zerra_id.to_string())
+            .or_try_insert_with(|| Edition::new(blob).map(Arc::new))?;
+        let me = entry.next_editor.fetch_add(1, Ordering::Relaxed);
+        let rx_broadcast = entry.tx_broadcast.subscribe();
+        entry.editors.lock().unwrap().insert(
+            me,
+            EditorPresence {
+                id: me,
+                name,
+                focus: None,
+            },
+        );
+        Ok((Arc::clone(&*entry), me, rx_broadcast))
+    }
+
+    fn leave(&self, under_edition: &DashMap<String, Arc<Edition>>, zerra_id: &str, me: u32) {
+        let emptied = {
+            let mut editors = self.editors.lock().unwrap();
+            editors.remove(&me);
+            editors.is_empty()
+        };
+        if emptied {
+            // An evicted session may already have been replaced by a newer one.
+            under_edition.remove_if(zerra_id, |_, edition| {
+                std::ptr::eq(self, &**edition) && edition.editors.lock().unwrap().is_empty()
+            });
+        } else {
+            self.announce();
+        }
+    }
+
+    /// Closes every editor of a zerra, e.g. because its conduction has begun.
+    pub(crate) fn evict(under_edition: &DashMap<String, Arc<Edition>>, zerra_id: &str) {
+        if let Some((_, edition)) = under_edition.remove(zerra_id) {
+            edition.evicted.store(true, Ordering::Relaxed);
+            edition.tx_broadcast.send(Broadcast::Evict).void();
+        }
+    }
+
+    pub(crate) fn editors(&self) -> Vec<EditorPresence> {
+        self.editors.lock().unwrap().values().cloned().collect()
+    }
+
+    /// The whole document, as a Yjs update.
+    async fn snapshot(&self) -> Vec<u8> {
+        self.replica.lock().await.state.clone()
+    }
+
+    fn announce(&self) {
+        // Failing to send only means nobody is listening anymore.
+        self.tx_broadcast
+            .send(Broadcast::Awareness(self.editors()))
+            .void();
+    }
+
+    fn focus(&self, me: u32, focus: Option<Vec<usize>>) {
+        if let Some(editor) = self.editors.lock().unwrap().get_mut(&me) {
+            editor.focus = focus;
+        }
+        self.announce();
+    }
+
+    async fn edit(
+        &self,
+        db: &PgPool,
+        zerra_id: &str,
+        me: u32,
+        patch: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let mut replica = self.replica.lock().await;
+        let merged = async {
+            let (zerra, state) = replica.merge(&patch)?;
+            update_blob(db, zerra_id, &rmp_serde::to_vec_named(&zerra)?).await?;
+            Ok::<_, anyhow::Error>(state)
+        }
+        .await;
+        match merged {
+            Ok(state) => replica.state = state,
+            Err(e) => {
+                // Other editors never see the patch, so neither may the document.
+                *replica = Replica::restore(std::mem::take(&mut replica.state))?;
+                return Err(e);
+            }
+        }
+        metrics::counter!("zerra_patch_bytes_total", "party" => "editor")
+            .increment(patch.len() as u64);
+        self.tx_broadcast
+            .send(Broadcast::Patch { from: me, patch })
+            .void();
+        Ok(())
+    }
+}
+
+pub(crate) async fn collaborate(
+    state: AppState,
+    zerra_id: String,
+    name: String,
+    blob: Vec<u8>,
+    mut draining: Draining,
+    ws: WebSocket,
+) {
+    let AppState {
+        db,
+        config,
+        under_edition,
+        ..
+    } = state;
+    let live = config.live;
+    let (edition, me, mut rx_broadcast) =
+        match Edition::join(&under_edition, &zerra_id, &blob, name) {
+            Ok(joined) => joined,
+            Err(e) => {
+                tracing::error!(%e, "cannot edit {zerra_id}");
+                return;
+            }
+        };
+    let _connection = telemetry::connection("editor");
+    let _membership = guard(
+        (edition.clone(), under_edition, zerra_id.clone()),
+        move |(edition, under_edition, zerra_id)| edition.leave(&under_edition, &zerra_id, me),
+    );
+
+    let (mut tx_ws_sink, rx_ws) = ws.split();
+    let mut rx_ws = pin!(tokio_stream::StreamExt::timeout(
+        rx_ws,
+        live.heartbeat_timeout()
+    ));
+
+    let (tx_ws, mut ts_ws_pump) = mpsc::channel::<Message>(live.channel_capacity);
+    let mut ts_ws_pump = stream::poll_fn(move |cx| ts_ws_pump.poll_recv(cx)).map(Ok);
+    spawn(async move {
+        tx_ws_sink.send_all(&mut ts_ws_pump).await.traced().void();
+        tx_ws_sink.close().await.traced().void();
+    });
+
+    if let Err(e) = async {
+        let document = edition.snapshot().await;
+        let document_len = document.len();
+        let message = rmp_serde::to_vec_named(&MessageToClient::Document(document))?;
+        tx_ws.send(Message::binary(message)).await?;
+        tracing::debug!("Sent {document_len} bytes");
+
+        let message = rmp_serde::to_vec_named(&MessageToClient::Presence(0))?;
+        tx_ws.send(Message::binary(message)).await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await
+    {
+        tracing::error!(%e);
+        return;
+    }
+    edition.announce();
+
+    let tx_ws_broadcast = tx_ws.clone();
+    let edition_broadcast = edition.clone();
+    let _relay = guard(
+        spawn(async move {
+            loop {
+                let msg = match rx_broadcast.recv().await {
+                    Ok(Broadcast::Patch { from, .. }) if from == me => continue,
+                    Ok(Broadcast::Patch { patch, .. }) => MessageToClient::Patch(patch),
+                    Ok(Broadcast::Awareness(editors)) => {
+                        MessageToClient::Awareness { you: me, editors }
+                    }
+                    Ok(Broadcast::Evict) => {
+                        tx_ws_broadcast
+                            .send(Message::Close(Some(CloseFrame {
+                                code: close_code::POLICY,
+                                reason: "zerra is live".into(),
+                            })))
+                            .await
+                            .traced()
+                            .void();
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        // The whole document merges into what the editor has, however far behind.
+                        tracing::warn!(missed, "editor lagged behind; resending document");
+                        MessageToClient::Patch(edition_broadcast.snapshot().await)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let msg = rmp_serde::to_vec_named(&msg).traced().ok()?;
+                tx_ws_broadcast
+                    .send(Message::binary(msg))
+                    .await
+                    .traced()
+                    .ok()?;
+            }
+            Some(())
+        }),
+        |relay| relay.abort(),
+    );
+
+    // Once the server starts shutting down, the editor is told so but still heard until it closes,
+    // so that its last edits are saved.
+    let mut told = false;
+    loop {
+        let msg = tokio::select! {
+            msg = rx_ws.next() => msg,
+            () = draining.begun(), if !told => {
+                told = true;
+                if let Some(msg) = shutdown::restarting(live.reconnect_secs)
+                    && tx_ws.send(msg).await.traced().is_err()
+                {
+                    break;
+                }
+                continue;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+        match msg {
+            Ok(Ok(Message::Binary(msg))) => match rmp_serde::from_slice(&msg) {
+                Ok(MessageFromEditor::Patch(_)) if edition.evicted.load(Ordering::Relaxed) => {
+                    tracing::debug!("dropping edit to live zerra {zerra_id}");
+                    break;
+                }
+                Ok(MessageFromEditor::Patch(patch)) => {
+                    // An editor whose patch is refused reconnects and starts over from the document.
+                    if let Err(e) = edition.edit(&db, &zerra_id, me, patch).await {
+                        tracing::error!(%e, "closing editor");
+                        break;
+                    }
+                }
+                Ok(MessageFromEditor::Focus(focus)) => edition.focus(me, focus),
+                Err(e) => tracing::error!(%e),
+            },
+            Ok(Ok(Message::Close(_))) => {
+                tracing::debug!("closing editor upon request");
+                break;
+            }
+            Ok(Ok(Message::Text(p))) if p == "ping" => {
+                if tx_ws.send(Message::text("pong")).await.traced().is_err() {
+                    break;
+                }
+            }
+            Ok(Ok(msg)) => {
+                tracing::warn!(?msg, "unexpected message from editor")
+            }
+            Ok(Err(e)) => {
+                tracing::error!(%e, "closing editor");
+                break;
+            }
+            Err(_) => {
+                tracing::error!("missed editor heartbeat");
+                metrics::counter!("zerra_missed_heartbeats_total", "party" => "editor")
+                    .increment(1);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::unauthenticated::fetch_blob;
+    use types::zerra::{Progress, Query};
+    use yrs::{Array, ArrayRef};
+
+    /// An editor's own copy of the session's document.
+    async fn replicate(edition: &Edition) -> (Doc, MapRef) {
+        let (doc, root) = Replica::empty();
+        let update = Update::decode_v1(&edition.snapshot().await).unwrap();
+        doc.transact_mut().apply_update(update).unwrap();
+        (doc, root)
+    }
+
+    #[sqlx::test]
+    async fn concurrent_edits_survive(db: PgPool) -> anyhow::Result<()> {
+        let zerra_id = "collaboration-test";
+        let blob = rmp_serde::to_vec_named(&Zerra {
+            id: zerra_id.to_string(),
+            title: "Before".to_string(),
+            progress: Progress::None,
+            flow: Vec::new(),
+        })?;
+        sqlx::query("insert into vus (id, owner, data, last_modified) values ($1, 1, $2, 0)")
+            .bind(zerra_id)
+            .bind(&blob)
+            .execute(&db)
+            .await?;
+        let under_edition = DashMap::new();
+        let (alice, a, _) = Edition::join(&under_edition, zerra_id, &blob, "A".into())?;
+        let (bob, b, _) = Edition::join(&under_edition, zerra_id, &blob, "B".into())?;
+        assert!(Arc::ptr_eq(&alice, &bob));
+
+        // Both start from the same document and change different parts of it.
+        let (alice_doc, alice_root) = replicate(&alice).await;
+        let (bob_doc, bob_root) = replicate(&bob).await;
+        let renamed = {
+            let mut txn = alice_doc.transact_mut();
+            alice_root.insert(&mut txn, "title", "After");
+            txn.encode_update_v1()
+        };
+        let asked = {
+            let mut txn = bob_doc.transact_mut();
+            let flow: ArrayRef = bob_root.get(&txn, "flow").unwrap().cast().unwrap();
+            let query = to_any(&Query {
+                key: 1,
+                question: "Why?".to_string(),
+                revelation: String::new(),
+                answer: None,
+                comment: None,
+                subflow: Vec::new(),
+                skippable: false,
+                visible: true,
+            })?;
+            flow.push_back(&mut txn, prelim(&query));
+            txn.encode_update_v1()
+        };
+        let (edited_a, edited_b) = tokio::join!(
+            alice.edit(&db, zerra_id, a, renamed),
+            bob.edit(&db, zerra_id, b, asked),
+        );
+        edited_a?;
+        edited_b?;
+
+        let stored: Zerra = rmp_serde::from_slice(&fetch_blob(&db, zerra_id).await?)?;
+        assert_eq!(stored.title, "After");
+        assert_eq!(stored.flow.len(), 1);
+        let (late_doc, late_root) = replicate(&alice).await;
+        let late: Zerra = from_any(&late_root.to_json(&late_doc.transact()))?;
+        assert_eq!((late.title, late.flow.len()), (stored.title, 1));
+
+        // A patch that leaves no zerra behind changes nothing.
+        let (bad_doc, bad_root) = replicate(&alice).await;
+        let untitled = {
+            let mut txn = bad_doc.transact_mut();
+            bad_root.insert(&mut txn, "title", 5);
+            txn.encode_update_v1()
+        };
+        assert!(alice.edit(&db, zerra_id, a, untitled).await.is_err());
+        let replica = alice.replica.lock().await;
+        let kept: Zerra = from_any(&replica.root.to_json(&replica.doc.transact()))?;
+        assert_eq!(kept.title, "After");
+        Ok(())
+    }
+}
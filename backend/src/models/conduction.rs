@@ -3,7 +3,6 @@ use std::{
     mem,
     pin::{Pin, pin},
     sync::Arc,
-    time::Duration,
 };
 
 use axum::extract::ws::{Message, WebSocket};
@@ -12,9 +11,24 @@ use futures_util::{SinkExt, StreamExt, stream};
 use scopeguard::guard;
 use sqlx::PgPool;
 use tokio::{spawn, sync::mpsc};
-use types::tx::{MessageFromAttendee, MessageFromConductor, MessageToClient};
+use types::{
+    LiveConduction,
+    jiff::Timestamp,
+    tx::{MessageFromAttendee, MessageFromConductor, MessageToClient},
+    zerra::Progress,
+};
 
-use crate::{models::unauthenticated::update_blob, utils::ResultExt as _};
+use crate::{
+    config::LiveOptions,
+    models::{
+        Result,
+        error::Error,
+        unauthenticated::{Metadata, check_persona, update_blob},
+    },
+    shutdown::{self, Draining},
+    telemetry,
+    utils::ResultExt as _,
+};
 
 #[derive(Debug)]
 pub(crate) enum MessageEnRoute {
@@ -49,6 +63,36 @@ pub(crate) enum ConnectionStatus {
 #[derive(Debug)]
 pub(crate) struct Conduction {
     pub(crate) status: ConnectionStatus,
+    /// When the first party connected.
+    pub(crate) since: Timestamp,
+    /// When either party was last heard from.
+    pub(crate) last_heartbeat: Timestamp,
+}
+
+impl Conduction {
+    fn new(status: ConnectionStatus) -> Self {
+        let now = Timestamp::now();
+        Conduction {
+            status,
+            since: now,
+            last_heartbeat: now,
+        }
+    }
+
+    pub(crate) fn describe(&self, zerra_id: &str) -> LiveConduction {
+        let (connected, waiting): (&[&str], _) = match self.status {
+            ConnectionStatus::WaitingForConductor(..) => (&[Attendee::NAME], true),
+            ConnectionStatus::WaitingForAttedee(..) => (&[Conductor::NAME], true),
+            ConnectionStatus::Established => (&[Conductor::NAME, Attendee::NAME], false),
+        };
+        LiveConduction {
+            zerra_id: zerra_id.to_string(),
+            connected: connected.iter().map(|party| party.to_string()).collect(),
+            since: self.since,
+            waiting_secs: waiting.then(|| Timestamp::now().as_second() - self.since.as_second()),
+            last_heartbeat: self.last_heartbeat,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -57,15 +101,19 @@ pub(crate) enum MsgError {
     Channel(#[from] mpsc::error::SendError<MessageEnRoute>),
     #[error("database error: {0}")]
     Db(#[from] sqlx::Error),
+    #[error("rejected blob: {0}")]
+    Rejected(String),
 }
 
 pub(crate) trait Party: Sized + Send + Sync {
     const NAME: &str;
     type Message: Send + for<'a> serde::Deserialize<'a>;
 
+    /// Joins the conduction of a zerra, failing if this party has already joined it.
     fn init(
         entry: dashmap::Entry<'_, String, Conduction>,
-    ) -> (mpsc::Sender<MessageEnRoute>, mpsc::Receiver<MessageEnRoute>);
+        capacity: usize,
+    ) -> Result<(mpsc::Sender<MessageEnRoute>, mpsc::Receiver<MessageEnRoute>)>;
     fn on_message(
         participant: &Participant<Self>,
         msg: Self::Message,
@@ -84,26 +132,29 @@ impl Party for Conductor {
 
     fn init(
         entry: dashmap::Entry<'_, String, Conduction>,
-    ) -> (mpsc::Sender<MessageEnRoute>, mpsc::Receiver<MessageEnRoute>) {
+        capacity: usize,
+    ) -> Result<(mpsc::Sender<MessageEnRoute>, mpsc::Receiver<MessageEnRoute>)> {
         match entry {
             dashmap::Entry::Occupied(mut existing) => {
-                let ConnectionStatus::WaitingForConductor(tx_sig, rx_sig) = mem::replace(
-                    &mut existing.get_mut().status,
-                    ConnectionStatus::Established,
-                ) else {
-                    panic!(
-                        "invalid status on occupied conduction entry in conductor: must be waiting for conductor"
-                    );
+                let status = &mut existing.get_mut().status;
+                if !matches!(status, ConnectionStatus::WaitingForConductor(..)) {
+                    return Err(Error::Conflict("zerra already has a conductor".to_string()));
+                }
+                let ConnectionStatus::WaitingForConductor(tx_sig, rx_sig) =
+                    mem::replace(status, ConnectionStatus::Established)
+                else {
+                    unreachable!();
                 };
-                (tx_sig, rx_sig)
+                Ok((tx_sig, rx_sig))
             }
             dashmap::Entry::Vacant(new) => {
-                let (tx_sig_attendee, rx_sig) = mpsc::channel(16);
-                let (tx_sig, rx_sig_attendee) = mpsc::channel(16);
-                new.insert(Conduction {
-                    status: ConnectionStatus::WaitingForAttedee(tx_sig_attendee, rx_sig_attendee),
-                });
-                (tx_sig, rx_sig)
+                let (tx_sig_attendee, rx_sig) = mpsc::channel(capacity);
+                let (tx_sig, rx_sig_attendee) = mpsc::channel(capacity);
+                new.insert(Conduction::new(ConnectionStatus::WaitingForAttedee(
+                    tx_sig_attendee,
+                    rx_sig_attendee,
+                )));
+                Ok((tx_sig, rx_sig))
             }
         }
     }
@@ -112,8 +163,25 @@ impl Party for Conductor {
         participant: &Participant<Self>,
         msg: Self::Message,
     ) -> Result<(), MsgError> {
+        // The attendee's persona reaches the server only inside the conductor's blob.
+        if let Ok(Metadata {
+            progress:
+                Progress::Ongoing {
+                    participant_persona,
+                    ..
+                }
+                | Progress::Finished {
+                    participant_persona,
+                    ..
+                },
+        }) = rmp_serde::from_slice(&msg.blob)
+        {
+            check_persona(&participant_persona).map_err(MsgError::Rejected)?;
+        }
         update_blob(&participant.db, &participant.zerra_id, &msg.blob).await?;
         if let Some(patch) = msg.patch {
+            metrics::counter!("zerra_patch_bytes_total", "party" => Self::NAME)
+                .increment(patch.len() as u64);
             participant
                 .tx_sig
                 .send(MessageEnRoute::Patch(patch))
@@ -129,29 +197,29 @@ impl Party for Attendee {
 
     fn init(
         entry: dashmap::Entry<'_, String, Conduction>,
-    ) -> (mpsc::Sender<MessageEnRoute>, mpsc::Receiver<MessageEnRoute>) {
+        capacity: usize,
+    ) -> Result<(mpsc::Sender<MessageEnRoute>, mpsc::Receiver<MessageEnRoute>)> {
         match entry {
             dashmap::Entry::Occupied(mut existing) => {
-                let ConnectionStatus::WaitingForAttedee(tx_sig, rx_sig) = mem::replace(
-                    &mut existing.get_mut().status,
-                    ConnectionStatus::Established,
-                ) else {
-                    panic!(
-                        "invalid status on occupied conduction entry in attendee: must be waiting for attendee"
-                    );
+                let status = &mut existing.get_mut().status;
+                if !matches!(status, ConnectionStatus::WaitingForAttedee(..)) {
+                    return Err(Error::Conflict("zerra already has a attendee".to_string()));
+                }
+                let ConnectionStatus::WaitingForAttedee(tx_sig, rx_sig) =
+                    mem::replace(status, ConnectionStatus::Established)
+                else {
+                    unreachable!();
                 };
-                (tx_sig, rx_sig)
+                Ok((tx_sig, rx_sig))
             }
             dashmap::Entry::Vacant(new) => {
-                let (tx_sig_conductor, rx_sig) = mpsc::channel(16);
-                let (tx_sig, rx_sig_conductor) = mpsc::channel(16);
-                new.insert(Conduction {
-                    status: ConnectionStatus::WaitingForConductor(
-                        tx_sig_conductor,
-                        rx_sig_conductor,
-                    ),
-                });
-                (tx_sig, rx_sig)
+                let (tx_sig_conductor, rx_sig) = mpsc::channel(capacity);
+                let (tx_sig, rx_sig_conductor) = mpsc::channel(capacity);
+                new.insert(Conduction::new(ConnectionStatus::WaitingForConductor(
+                    tx_sig_conductor,
+                    rx_sig_conductor,
+                )));
+                Ok((tx_sig, rx_sig))
             }
         }
     }
@@ -160,6 +228,8 @@ impl Party for Attendee {
         participant: &Participant<Self>,
         msg: Self::Message,
     ) -> Result<(), MsgError> {
+        metrics::counter!("zerra_patch_bytes_total", "party" => Self::NAME)
+            .increment(msg.patch.len() as u64);
         participant
             .tx_sig
             .send(MessageEnRoute::Patch(msg.patch))
@@ -175,6 +245,8 @@ pub(crate) struct Participant<P> {
     tx_sig: mpsc::Sender<MessageEnRoute>,
     rx_sig: Option<mpsc::Receiver<MessageEnRoute>>,
     under_conduction: Arc<DashMap<String, Conduction>>,
+    live: LiveOptions,
+    draining: Option<Draining>,
     _party: PhantomData<P>,
 }
 
@@ -183,15 +255,28 @@ impl<P: Party + 'static> Participant<P> {
         db: PgPool,
         zerra_id: String,
         under_conduction: Arc<DashMap<String, Conduction>>,
-    ) -> Self {
-        let (tx_sig, rx_sig) = P::init(under_conduction.entry(zerra_id.clone()));
-        Participant {
+        live: LiveOptions,
+        draining: Draining,
+    ) -> Result<Self> {
+        let (tx_sig, rx_sig) = P::init(
+            under_conduction.entry(zerra_id.clone()),
+            live.channel_capacity,
+        )?;
+        Ok(Participant {
             db,
             zerra_id,
             tx_sig,
             rx_sig: Some(rx_sig),
             under_conduction,
+            live,
+            draining: Some(draining),
             _party: PhantomData,
+        })
+    }
+
+    fn heard(&self) {
+        if let Some(mut conduction) = self.under_conduction.get_mut(&self.zerra_id) {
+            conduction.last_heartbeat = Timestamp::now();
         }
     }
 
@@ -202,6 +287,7 @@ impl<P: Party + 'static> Participant<P> {
     {
         move |ws| {
             Box::pin(async move {
+                let _connection = telemetry::connection(P::NAME);
                 let tx_sig = self.tx_sig.clone();
                 let tx_sig = guard(tx_sig, |tx_sig| {
                     spawn(async move { tx_sig.send(MessageEnRoute::Kill).await.traced().void() });
@@ -217,10 +303,10 @@ impl<P: Party + 'static> Participant<P> {
                 });
                 let mut rx_ws = pin!(tokio_stream::StreamExt::timeout(
                     rx_ws,
-                    Duration::from_secs(5)
+                    self.live.heartbeat_timeout()
                 ));
 
-                let (tx_ws, mut ts_ws_pump) = mpsc::channel::<Message>(16);
+                let (tx_ws, mut ts_ws_pump) = mpsc::channel::<Message>(self.live.channel_capacity);
                 let mut ts_ws_pump = stream::poll_fn(move |cx| ts_ws_pump.poll_recv(cx)).map(Ok);
                 spawn(async move { tx_ws_sink.send_all(&mut ts_ws_pump).await });
 
@@ -252,7 +338,32 @@ impl<P: Party + 'static> Participant<P> {
                     Some(())
                 });
 
-                while let Some(msg) = rx_ws.next().await {
+                // Once the server starts shutting down, the client is told so but still heard until it
+                // closes, so that its last changes are saved.
+                let mut draining = self
+                    .draining
+                    .take()
+                    .expect("participant to have `draining`");
+                let mut told = false;
+                loop {
+                    let msg = tokio::select! {
+                        msg = rx_ws.next() => msg,
+                        () = draining.begun(), if !told => {
+                            told = true;
+                            if let Some(msg) = shutdown::restarting(self.live.reconnect_secs)
+                                && tx_ws.send(msg).await.traced().is_err()
+                            {
+                                break;
+                            }
+                            continue;
+                        }
+                    };
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    if let Ok(Ok(_)) = msg {
+                        self.heard();
+                    }
                     match msg {
                         Ok(Ok(Message::Binary(blob))) => match rmp_serde::from_slice(&blob) {
                             Ok(msg) => {
@@ -280,6 +391,8 @@ impl<P: Party + 'static> Participant<P> {
                         }
                         Err(_) => {
                             tracing::error!("missed {} heartbeat", P::NAME);
+                            metrics::counter!("zerra_missed_heartbeats_total", "party" => P::NAME)
+                                .increment(1);
                             break;
                         }
                     }
@@ -288,3 +401,28 @@ impl<P: Party + 'static> Participant<P> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_party_is_refused() {
+        let under_conduction = DashMap::new();
+        Conductor::init(under_conduction.entry("z".to_string()), 1).unwrap();
+        assert!(matches!(
+            Conductor::init(under_conduction.entry("z".to_string()), 1),
+            Err(Error::Conflict(_))
+        ));
+        assert!(matches!(
+            under_conduction.get("z").unwrap().status,
+            ConnectionStatus::WaitingForAttedee(..)
+        ));
+
+        Attendee::init(under_conduction.entry("z".to_string()), 1).unwrap();
+        assert!(matches!(
+            Attendee::init(under_conduction.entry("z".to_string()), 1),
+            Err(Error::Conflict(_))
+        ));
+    }
+}
@@ -1,4 +1,8 @@
-use axum::http::StatusCode;
+use axum::{
+    Json,
+    http::{StatusCode, header},
+};
+use types::ImportProblem;
 
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum Error {
@@ -6,8 +10,19 @@ pub(crate) enum Error {
     Unauthorized(String),
     #[error("forbidden access: {0}")]
     Forbidden(String),
+    #[error("bad request: {0}")]
+    BadRequest(String),
     #[error("not found: {0}")]
     NotFound(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("unavailable: {0}")]
+    Unavailable(String),
+    /// Too many attempts were made lately; the caller may try again after this many seconds.
+    #[error("throttled for {0} s")]
+    Throttled(i64),
+    #[error("unprocessable: {}", list(.0))]
+    Unprocessable(Vec<ImportProblem>),
     #[error("database error: {0}")]
     Db(sqlx::Error),
     #[error("blob encode error: {0}")]
@@ -16,25 +31,62 @@ pub(crate) enum Error {
     Decode(#[from] rmp_serde::decode::Error),
     #[error("typst compilation error: {0}")]
     Typst(#[from] tokape::CompilationError),
-    #[error("yaml decode error: {0}")]
-    YamlDecode(#[from] serde::de::value::Error),
     #[error("yaml encode error: {0}")]
     YamlEncode(#[from] serde_yaml2::ser::Errors),
+    #[error("csv encode error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("xlsx encode error: {0}")]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+    #[error("background task error: {0}")]
+    Task(#[from] tokio::task::JoinError),
 }
 
 impl axum::response::IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        tracing::error!(%self);
+        // Client errors are the client's business, and only the server's own failures are errors.
         match self {
+            Self::Unauthorized(_)
+            | Self::Forbidden(_)
+            | Self::BadRequest(_)
+            | Self::NotFound(_)
+            | Self::Conflict(_)
+            | Self::Unprocessable(_)
+            | Self::Throttled(_) => tracing::debug!(%self),
+            Self::Unavailable(_) => tracing::warn!(%self),
+            _ => tracing::error!(%self),
+        }
+        match self {
+            // The problems are for the author to fix, so they are told in full.
+            Self::Unprocessable(problems) => {
+                return (StatusCode::UNPROCESSABLE_ENTITY, Json(problems)).into_response();
+            }
             Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
             Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Throttled(retry_after) => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                )
+                    .into_response();
+            }
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
         .into_response()
     }
 }
 
+fn list(problems: &[ImportProblem]) -> String {
+    problems
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 impl From<sqlx::Error> for Error {
     fn from(e: sqlx::Error) -> Self {
         match e {
@@ -0,0 +1,162 @@
+use std::collections::BTreeSet;
+
+use sqlx::Row;
+use types::{Folder, sharing::Role};
+
+use crate::models::{Result, authenticated::Context, error::Error, sharing::GRANTS};
+
+const MAX_FOLDER_NAME_LEN: usize = 64;
+const MAX_TAG_LEN: usize = 32;
+const MAX_TAGS: usize = 16;
+
+fn folder_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_FOLDER_NAME_LEN {
+        return Err(Error::BadRequest(format!(
+            "folder name must have 1 to {MAX_FOLDER_NAME_LEN} characters"
+        )));
+    }
+    Ok(name)
+}
+
+impl Context<'_> {
+    pub(crate) async fn folders(self) -> Result<Vec<Folder>> {
+        Ok(
+            sqlx::query("select id, name from folders where user_id = $1 order by name")
+                .bind(self.user)
+                .try_map(|r: sqlx::postgres::PgRow| {
+                    Ok(Folder {
+                        id: r.try_get("id")?,
+                        name: r.try_get("name")?,
+                    })
+                })
+                .fetch_all(self.db)
+                .await?,
+        )
+    }
+
+    pub(crate) async fn new_folder(self, name: &str) -> Result<Folder> {
+        let name = folder_name(name)?;
+        let id: i64 = sqlx::query(
+            "insert into folders (user_id, name) values ($1, $2) \
+             on conflict do nothing returning id",
+        )
+        .bind(self.user)
+        .bind(name)
+        .fetch_optional(self.db)
+        .await?
+        .map(|r| r.get(0))
+        .ok_or_else(|| Error::Conflict(format!("folder {name} already exists")))?;
+        Ok(Folder {
+            id,
+            name: name.to_string(),
+        })
+    }
+
+    pub(crate) async fn rename_folder(self, folder_id: i64, name: &str) -> Result {
+        let name = folder_name(name)?;
+        let renamed = sqlx::query("update folders set name = $1 where id = $2 and user_id = $3")
+            .bind(name)
+            .bind(folder_id)
+            .bind(self.user)
+            .execute(self.db)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(e) if e.is_unique_violation() => {
+                    Error::Conflict(format!("folder {name} already exists"))
+                }
+                e => e.into(),
+            })?;
+        if renamed.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("folder({folder_id})")));
+        }
+        Ok(())
+    }
+
+    /// Deletes a folder of the user. The zerrae in it are left unfiled.
+    pub(crate) async fn delete_folder(self, folder_id: i64) -> Result {
+        let deleted = sqlx::query("delete from folders where id = $1 and user_id = $2")
+            .bind(folder_id)
+            .bind(self.user)
+            .execute(self.db)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("folder({folder_id})")));
+        }
+        Ok(())
+    }
+
+    /// Keeps a zerra in a folder of the user, or in none. Other users file it apart.
+    pub(crate) async fn file(self, zerra_id: &str, folder_id: Option<i64>) -> Result {
+        self.authorize(zerra_id, Role::Viewer).await?;
+        let Some(folder_id) = folder_id else {
+            sqlx::query("delete from filings where user_id = $1 and zerra_id = $2")
+                .bind(self.user)
+                .bind(zerra_id)
+                .execute(self.db)
+                .await?;
+            return Ok(());
+        };
+
+        let filed = sqlx::query(
+            "insert into filings (user_id, zerra_id, folder_id) \
+             select $1, $2, id from folders where id = $3 and user_id = $1 \
+             on conflict (user_id, zerra_id) do update set folder_id = excluded.folder_id",
+        )
+        .bind(self.user)
+        .bind(zerra_id)
+        .bind(folder_id)
+        .execute(self.db)
+        .await?;
+        if filed.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("folder({folder_id})")));
+        }
+        Ok(())
+    }
+
+    /// Every tag on the zerrae the user can reach, for filtering by.
+    pub(crate) async fn tags(self) -> Result<Vec<String>> {
+        let query = format!(
+            "select distinct tag from tags where zerra_id in (select zerra_id from ({GRANTS}) g) \
+             order by tag"
+        );
+        Ok(sqlx::query(&query)
+            .bind(self.user)
+            .try_map(|r: sqlx::postgres::PgRow| r.try_get(0))
+            .fetch_all(self.db)
+            .await?)
+    }
+
+    /// Replaces the tags of a zerra, which everyone who can reach it sees.
+    pub(crate) async fn set_tags(self, zerra_id: &str, tags: Vec<String>) -> Result {
+        self.authorize(zerra_id, Role::Editor).await?;
+        let tags: BTreeSet<_> = tags
+            .iter()
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        if tags.len() > MAX_TAGS {
+            return Err(Error::BadRequest(format!(
+                "a zerra can have at most {MAX_TAGS} tags"
+            )));
+        }
+        if let Some(tag) = tags.iter().find(|tag| tag.chars().count() > MAX_TAG_LEN) {
+            return Err(Error::BadRequest(format!(
+                "tag {tag} is longer than {MAX_TAG_LEN} characters"
+            )));
+        }
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("delete from tags where zerra_id = $1")
+            .bind(zerra_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("insert into tags (zerra_id, tag) select $1, unnest($2::text[])")
+            .bind(zerra_id)
+            .bind(tags.into_iter().collect::<Vec<_>>())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,580 @@
+//! Zerrae imported from YAML or from outlines, read leniently and checked before they are stored.
+//!
+//! YAML may leave out anything but the questions: keys are generated, texts default to empty and
+//! queries to visible. Whatever is wrong is reported all at once, with the path to it, e.g.
+//! `flow[0].subflow[2].key`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use types::{
+    ImportProblem,
+    zerra::{Progress, Query, Zerra},
+};
+
+use crate::models::{Result, error::Error, outline};
+
+/// Largest document that can be imported, in bytes.
+const MAX_IMPORT_LEN: usize = 1024 * 1024;
+/// Most queries a zerra may have, counting those nested. Far fewer than there are keys, so that
+/// keys left out can always be generated.
+pub(crate) const MAX_QUERIES: usize = 1000;
+/// Deepest a query may be nested, counting the top of the flow as 1.
+const MAX_DEPTH: usize = 8;
+/// Longest any text of a zerra may be, in characters.
+const MAX_TEXT_LEN: usize = 10_000;
+/// Deepest YAML values may be nested, well beyond what queries nested `MAX_DEPTH` deep need.
+const MAX_NESTING: usize = 64;
+
+const ZERRA_FIELDS: &[&str] = &["id", "title", "progress", "flow"];
+const QUERY_FIELDS: &[&str] = &[
+    "key",
+    "question",
+    "revelation",
+    "answer",
+    "comment",
+    "subflow",
+    "skippable",
+    "visible",
+];
+
+/// Reads a zerra exported as YAML, with an empty ID.
+pub(crate) fn from_yaml(yaml: &str) -> Result<Zerra> {
+    check_len(yaml)?;
+    let node: Node = serde_yaml2::from_str(yaml).map_err(|e| {
+        Error::Unprocessable(vec![ImportProblem {
+            line: None,
+            path: None,
+            message: e.to_string(),
+        }])
+    })?;
+
+    let mut reader = Reader::default();
+    let zerra = reader.zerra(&node, yaml);
+    let mut problems = reader.problems;
+    if let Some(zerra) = &zerra {
+        problems.extend(validate(zerra));
+    }
+    match zerra {
+        Some(zerra) if problems.is_empty() => Ok(zerra),
+        _ => Err(Error::Unprocessable(problems)),
+    }
+}
+
+/// Reads a zerra drafted as an outline, with an empty ID.
+pub(crate) fn from_outline(text: &str) -> Result<Zerra> {
+    check_len(text)?;
+    let zerra = outline::parse(text).map_err(Error::Unprocessable)?;
+    let problems = validate(&zerra);
+    if problems.is_empty() {
+        Ok(zerra)
+    } else {
+        Err(Error::Unprocessable(problems))
+    }
+}
+
+fn check_len(text: &str) -> Result {
+    if text.len() > MAX_IMPORT_LEN {
+        return Err(Error::Unprocessable(vec![ImportProblem {
+            line: None,
+            path: None,
+            message: format!("the document is larger than {MAX_IMPORT_LEN} bytes"),
+        }]));
+    }
+    Ok(())
+}
+
+fn problem(path: impl Into<String>, message: impl Into<String>) -> ImportProblem {
+    ImportProblem {
+        line: None,
+        path: Some(path.into()),
+        message: message.into(),
+    }
+}
+
+/// Checks what a zerra holds, however it was written.
+fn validate(zerra: &Zerra) -> Vec<ImportProblem> {
+    let mut problems = Vec::new();
+    check_text(&mut problems, "title", &zerra.title);
+    if zerra.flow.is_empty() {
+        problems.push(problem("flow", "a zerra needs at least one question"));
+    }
+    let mut count = 0;
+    validate_flow(&mut problems, &mut count, &zerra.flow, "flow", 1);
+    if count > MAX_QUERIES {
+        problems.push(problem(
+            "flow",
+            format!("a zerra cannot have more than {MAX_QUERIES} queries"),
+        ));
+    }
+    problems
+}
+
+fn validate_flow(
+    problems: &mut Vec<ImportProblem>,
+    count: &mut usize,
+    flow: &[Query],
+    path: &str,
+    depth: usize,
+) {
+    for (i, query) in flow.iter().enumerate() {
+        let path = format!("{path}[{i}]");
+        *count += 1;
+        if depth > MAX_DEPTH {
+            problems.push(problem(
+                path,
+                format!("queries cannot be nested more than {MAX_DEPTH} deep"),
+            ));
+            continue;
+        }
+        if query.question.trim().is_empty() {
+            problems.push(problem(format!("{path}.question"), "the question is empty"));
+        }
+        check_text(problems, &format!("{path}.question"), &query.question);
+        check_text(problems, &format!("{path}.revelation"), &query.revelation);
+        for (field, value) in [("answer", &query.answer), ("comment", &query.comment)] {
+            if let Some(value) = value {
+                check_text(problems, &format!("{path}.{field}"), value);
+            }
+        }
+        validate_flow(
+            problems,
+            count,
+            &query.subflow,
+            &format!("{path}.subflow"),
+            depth + 1,
+        );
+    }
+}
+
+fn check_text(problems: &mut Vec<ImportProblem>, path: &str, text: &str) {
+    if text.chars().count() > MAX_TEXT_LEN {
+        problems.push(problem(
+            path,
+            format!("longer than {MAX_TEXT_LEN} characters"),
+        ));
+    }
+}
+
+/// Any YAML value, read before it is known to make a zerra.
+#[derive(Debug)]
+enum Node {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Str(String),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Nested(0).deserialize(deserializer)
+    }
+}
+
+/// Reads a node nested this deep, refusing to go deeper than `MAX_NESTING`.
+#[derive(Clone, Copy)]
+struct Nested(usize);
+
+impl<'de> DeserializeSeed<'de> for Nested {
+    type Value = Node;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Node, D::Error> {
+        if self.0 > MAX_NESTING {
+            return Err(de::Error::custom(format!(
+                "values cannot be nested more than {MAX_NESTING} deep"
+            )));
+        }
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Nested {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any YAML value")
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Node, E> {
+        Ok(Node::Null)
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<Node, E> {
+        Ok(Node::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> std::result::Result<Node, D::Error> {
+        self.deserialize(d)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Node, E> {
+        Ok(Node::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Node, E> {
+        Ok(Node::Int(v.into()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<Node, E> {
+        Ok(Node::Int(v.into()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Node, E> {
+        Ok(Node::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Node, E> {
+        Ok(Node::Str(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> std::result::Result<Node, E> {
+        Ok(Node::Str(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Node, A::Error> {
+        let inner = Nested(self.0 + 1);
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(inner)? {
+            items.push(item);
+        }
+        Ok(Node::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Node, A::Error> {
+        let inner = Nested(self.0 + 1);
+        let mut entries = Vec::new();
+        while let Some((key, value)) = map.next_entry_seed(inner, inner)? {
+            let key = match key {
+                Node::Str(key) => key,
+                Node::Bool(key) => key.to_string(),
+                Node::Int(key) => key.to_string(),
+                Node::Float(key) => key.to_string(),
+                Node::Null | Node::Seq(_) | Node::Map(_) => "?".to_string(),
+            };
+            entries.push((key, value));
+        }
+        Ok(Node::Map(entries))
+    }
+}
+
+/// A query as read, whose key may be left to generate.
+struct Draft {
+    key: Option<u16>,
+    query: Query,
+    subflow: Vec<Draft>,
+}
+
+/// Turns nodes into a zerra, noting every problem on the way. Queries past `MAX_QUERIES` or
+/// nested deeper than `MAX_DEPTH` are not read, so that keys can be generated for those read.
+#[derive(Default)]
+struct Reader {
+    problems: Vec<ImportProblem>,
+    /// The keys given so far, with where each was first given.
+    keys: HashMap<u16, String>,
+    /// The queries read so far.
+    count: usize,
+}
+
+impl Reader {
+    fn zerra(&mut self, node: &Node, yaml: &str) -> Option<Zerra> {
+        let Node::Map(fields) = node else {
+            self.problems
+                .push(problem("", "a zerra must be a mapping of its fields"));
+            return None;
+        };
+        self.unknown_fields(fields, ZERRA_FIELDS, "");
+
+        let title = self
+            .text(field(fields, "title"), "title")
+            .unwrap_or_else(|| "Title".to_string());
+
+        // Progress is read as it is stored, which leaves nothing to be lenient about.
+        let progress = match field(fields, "progress") {
+            None | Some(Node::Null) => Progress::None,
+            Some(_) => {
+                #[derive(serde::Deserialize)]
+                struct Progressed {
+                    progress: Progress,
+                }
+                match serde_yaml2::from_str::<Progressed>(yaml) {
+                    Ok(Progressed { progress }) => progress,
+                    Err(e) => {
+                        self.problems.push(problem("progress", e.to_string()));
+                        Progress::None
+                    }
+                }
+            }
+        };
+
+        let drafts = self.flow(field(fields, "flow"), "flow", 1);
+        let mut used: HashSet<u16> = self.keys.keys().copied().collect();
+        Some(Zerra {
+            id: String::new(),
+            title,
+            progress,
+            flow: assign_keys(drafts, &mut used),
+        })
+    }
+
+    /// Reads a flow whose queries are nested `depth` deep.
+    fn flow(&mut self, node: Option<&Node>, path: &str, depth: usize) -> Vec<Draft> {
+        match node {
+            None | Some(Node::Null) => Vec::new(),
+            Some(Node::Seq(items)) if !items.is_empty() && depth > MAX_DEPTH => {
+                self.problems.push(problem(
+                    path,
+                    format!("queries cannot be nested more than {MAX_DEPTH} deep"),
+                ));
+                Vec::new()
+            }
+            Some(Node::Seq(items)) => {
+                let mut drafts = Vec::new();
+                for (i, item) in items.iter().enumerate() {
+                    let path = format!("{path}[{i}]");
+                    if self.count == MAX_QUERIES {
+                        self.problems.push(problem(
+                            path,
+                            format!("a zerra cannot have more than {MAX_QUERIES} queries"),
+                        ));
+                        break;
+                    }
+                    drafts.extend(self.query(item, &path, depth));
+                }
+                drafts
+            }
+            Some(_) => {
+                self.problems
+                    .push(problem(path, "must be a list of queries"));
+                Vec::new()
+            }
+        }
+    }
+
+    fn query(&mut self, node: &Node, path: &str, depth: usize) -> Option<Draft> {
+        let Node::Map(fields) = node else {
+            self.problems
+                .push(problem(path, "a query must be a mapping of its fields"));
+            return None;
+        };
+        self.count += 1;
+        self.unknown_fields(fields, QUERY_FIELDS, path);
+
+        let key = match field(fields, "key") {
+            None | Some(Node::Null) => None,
+            Some(Node::Int(key)) if u16::try_from(*key).is_ok() => {
+                let key = *key as u16;
+                match self.keys.get(&key) {
+                    Some(first) => {
+                        let message = format!("key {key} is already given to {first}");
+                        self.problems.push(problem(format!("{path}.key"), message));
+                    }
+                    None => {
+                        self.keys.insert(key, path.to_string());
+                    }
+                }
+                Some(key)
+            }
+            Some(_) => {
+                self.problems.push(problem(
+                    format!("{path}.key"),
+                    format!("must be a whole number from 0 to {}", u16::MAX),
+                ));
+                None
+            }
+        };
+
+        let text = |reader: &mut Self, name: &str| {
+            reader.text(field(fields, name), &format!("{path}.{name}"))
+        };
+        let flag = |reader: &mut Self, name: &str, default: bool| {
+            reader.flag(field(fields, name), &format!("{path}.{name}"), default)
+        };
+        let query = Query {
+            key: 0,
+            question: text(self, "question").unwrap_or_default(),
+            revelation: text(self, "revelation").unwrap_or_default(),
+            answer: text(self, "answer"),
+            comment: text(self, "comment"),
+            subflow: Vec::new(),
+            skippable: flag(self, "skippable", false),
+            visible: flag(self, "visible", true),
+        };
+        let subflow = self.flow(
+            field(fields, "subflow"),
+            &format!("{path}.subflow"),
+            depth + 1,
+        );
+        Some(Draft {
+            key,
+            query,
+            subflow,
+        })
+    }
+
+    /// Reads text, taking numbers and the like for what they spell.
+    fn text(&mut self, node: Option<&Node>, path: &str) -> Option<String> {
+        match node? {
+            Node::Null => None,
+            Node::Str(text) => Some(text.clone()),
+            Node::Bool(b) => Some(b.to_string()),
+            Node::Int(i) => Some(i.to_string()),
+            Node::Float(f) => Some(f.to_string()),
+            Node::Seq(_) | Node::Map(_) => {
+                self.problems.push(problem(path, "must be text"));
+                None
+            }
+        }
+    }
+
+    fn flag(&mut self, node: Option<&Node>, path: &str, default: bool) -> bool {
+        match node {
+            None | Some(Node::Null) => default,
+            Some(Node::Bool(flag)) => *flag,
+            Some(_) => {
+                self.problems.push(problem(path, "must be true or false"));
+                default
+            }
+        }
+    }
+
+    fn unknown_fields(&mut self, fields: &[(String, Node)], known: &[&str], path: &str) {
+        for (name, _) in fields {
+            if !known.contains(&name.as_str()) {
+                let at = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path}.{name}")
+                };
+                self.problems.push(problem(
+                    at,
+                    format!("unknown field; expected one of {}", known.join(", ")),
+                ));
+            }
+        }
+    }
+}
+
+fn field<'n>(fields: &'n [(String, Node)], name: &str) -> Option<&'n Node> {
+    fields
+        .iter()
+        .find_map(|(field, node)| (field == name).then_some(node))
+}
+
+/// Gives every query its key, generating those left out so that none is given twice. There are far
+/// fewer queries than keys, as [`Reader`] reads no more than `MAX_QUERIES`.
+fn assign_keys(drafts: Vec<Draft>, used: &mut HashSet<u16>) -> Vec<Query> {
+    drafts
+        .into_iter()
+        .map(
+            |Draft {
+                 key,
+                 query,
+                 subflow,
+             }| {
+                let key = key.unwrap_or_else(|| {
+                    loop {
+                        let key = rand::random();
+                        if used.insert(key) {
+                            break key;
+                        }
+                    }
+                });
+                Query {
+                    key,
+                    subflow: assign_keys(subflow, used),
+                    ..query
+                }
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(yaml: &str) -> Vec<(Option<String>, String)> {
+        match from_yaml(yaml) {
+            Err(Error::Unprocessable(problems)) => problems
+                .into_iter()
+                .map(|problem| (problem.path, problem.message))
+                .collect(),
+            other => panic!("expected problems, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let zerra =
+            from_yaml(r#"{"flow": [{"question": "Who?", "subflow": [{"question": "Why?"}]}]}"#)
+                .unwrap();
+        assert_eq!(zerra.title, "Title");
+        assert!(matches!(zerra.progress, Progress::None));
+        let who = &zerra.flow[0];
+        assert!(who.visible && !who.skippable && who.answer.is_none());
+        assert_ne!(who.key, who.subflow[0].key);
+    }
+
+    #[test]
+    fn every_problem_has_its_path() {
+        let problems = problems(
+            r#"{"flow": [
+                {"key": 7, "question": "Who?", "colour": "red"},
+                {"question": " ", "subflow": [{"key": 7, "question": "Why?", "visible": "no"}]}
+            ]}"#,
+        );
+        let paths: Vec<_> = problems.iter().map(|(path, _)| path.as_deref()).collect();
+        assert_eq!(
+            paths,
+            [
+                Some("flow[0].colour"),
+                Some("flow[1].subflow[0].key"),
+                Some("flow[1].subflow[0].visible"),
+                Some("flow[1].question"),
+            ]
+        );
+        assert!(problems[1].1.contains("flow[0]"));
+    }
+
+    #[test]
+    fn limits_stop_reading() {
+        let many = format!(
+            r#"{{"flow": [{}]}}"#,
+            vec![r#"{"question": "Why?"}"#; MAX_QUERIES + 1].join(",")
+        );
+        assert_eq!(
+            problems(&many),
+            [(
+                Some(format!("flow[{MAX_QUERIES}]")),
+                format!("a zerra cannot have more than {MAX_QUERIES} queries")
+            )]
+        );
+
+        let mut deep = r#"{"question": "Why?"}"#.to_string();
+        for _ in 0..MAX_DEPTH {
+            deep = format!(r#"{{"question": "Why?", "subflow": [{deep}]}}"#);
+        }
+        let deep = format!(r#"{{"flow": [{deep}]}}"#);
+        let too_deep = format!("flow[0]{}.subflow", ".subflow[0]".repeat(MAX_DEPTH - 1));
+        let paths: Vec<_> = problems(&deep).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, [Some(too_deep)]);
+
+        let nested = "[".repeat(MAX_NESTING + 2) + &"]".repeat(MAX_NESTING + 2);
+        let problems = problems(&nested);
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0]
+                .1
+                .contains(&format!("nested more than {MAX_NESTING} deep"))
+        );
+    }
+}
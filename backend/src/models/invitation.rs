@@ -0,0 +1,285 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{PgPool, Row};
+use tokio::task;
+use types::{
+    invitation::{Invitation, InvitationInfo, InvitationRequest},
+    jiff::Timestamp,
+    sharing::Role,
+};
+
+use crate::models::{Result, authenticated::Context, error::Error, template::refuse_template};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Longest an invitation may stay valid: two weeks.
+const MAX_VALIDITY_MINUTES: u32 = 14 * 24 * 60;
+
+/// The signed claims of an invitation token, `{invitation_id}.{zerra_id}.{expires_at}.{signature}`.
+#[derive(Debug, PartialEq, Eq)]
+struct Claims<'t> {
+    invitation_id: &'t str,
+    zerra_id: &'t str,
+    expires_at: i64,
+}
+
+fn sign(key: &[u8], payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn issue(key: &[u8], claims: &Claims) -> String {
+    let payload = format!(
+        "{}.{}.{}",
+        claims.invitation_id, claims.zerra_id, claims.expires_at
+    );
+    let signature = URL_SAFE_NO_PAD.encode(sign(key, &payload).finalize().into_bytes());
+    format!("{payload}.{signature}")
+}
+
+fn verify<'t>(key: &[u8], token: &'t str, now: i64) -> Result<Claims<'t>> {
+    let invalid = || Error::Unauthorized("invalid invitation".into());
+
+    let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+    sign(key, payload)
+        .verify_slice(&signature)
+        .map_err(|_| invalid())?;
+
+    let mut parts = payload.splitn(3, '.');
+    let (Some(invitation_id), Some(zerra_id), Some(expires_at)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let expires_at = expires_at.parse().map_err(|_| invalid())?;
+    if expires_at <= now {
+        return Err(Error::Unauthorized(format!(
+            "invitation {invitation_id} has expired"
+        )));
+    }
+
+    Ok(Claims {
+        invitation_id,
+        zerra_id,
+        expires_at,
+    })
+}
+
+impl Context<'_> {
+    pub(crate) async fn invite(
+        self,
+        key: &[u8],
+        zerra_id: &str,
+        InvitationRequest {
+            valid_for,
+            single_use,
+            passcode,
+            invitee_name,
+        }: InvitationRequest,
+    ) -> Result<Invitation> {
+        self.authorize(zerra_id, Role::Conductor).await?;
+        refuse_template(self.db, zerra_id).await?;
+        if valid_for == 0 || valid_for > MAX_VALIDITY_MINUTES {
+            return Err(Error::BadRequest(format!(
+                "invitation must be valid for 1 to {MAX_VALIDITY_MINUTES} minutes"
+            )));
+        }
+
+        let passcode_hash = match passcode.filter(|p| !p.is_empty()) {
+            Some(passcode) => {
+                Some(task::spawn_blocking(|| password_auth::generate_hash(passcode)).await?)
+            }
+            None => None,
+        };
+
+        let invitation_id = uuid::Uuid::new_v4().simple().to_string();
+        let expires_at = Timestamp::now().as_second() + i64::from(valid_for) * 60;
+        sqlx::query(
+            "insert into invitations \
+             (id, zerra_id, created_by, expires_at, single_use, passcode_hash, invitee_name) \
+             values ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&invitation_id)
+        .bind(zerra_id)
+        .bind(self.user)
+        .bind(expires_at)
+        .bind(single_use)
+        .bind(passcode_hash)
+        .bind(invitee_name.filter(|n| !n.trim().is_empty()))
+        .execute(self.db)
+        .await?;
+
+        Ok(Invitation {
+            token: issue(
+                key,
+                &Claims {
+                    invitation_id: &invitation_id,
+                    zerra_id,
+                    expires_at,
+                },
+            ),
+            expires_at: Timestamp::from_second(expires_at).expect("expiry to be in range"),
+        })
+    }
+
+    pub(crate) async fn revoke_invitations(self, zerra_id: &str) -> Result {
+        self.authorize(zerra_id, Role::Conductor).await?;
+        sqlx::query("delete from invitations where zerra_id = $1")
+            .bind(zerra_id)
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+async fn lookup(
+    db: &PgPool,
+    key: &[u8],
+    zerra_id: &str,
+    token: &str,
+) -> Result<(String, sqlx::postgres::PgRow)> {
+    let claims = verify(key, token, Timestamp::now().as_second())?;
+    if claims.zerra_id != zerra_id {
+        return Err(Error::Unauthorized(format!(
+            "invitation {} is not for zerra {zerra_id}",
+            claims.invitation_id
+        )));
+    }
+
+    // Revoked invitations no longer have a row, even if their signature is still valid.
+    let row = sqlx::query(
+        "select single_use, passcode_hash, invitee_name, claimed_by \
+         from invitations where id = $1 and zerra_id = $2",
+    )
+    .bind(claims.invitation_id)
+    .bind(zerra_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| {
+        Error::Unauthorized(format!("invitation {} was revoked", claims.invitation_id))
+    })?;
+
+    Ok((claims.invitation_id.to_string(), row))
+}
+
+pub(crate) async fn inspect(
+    db: &PgPool,
+    key: &[u8],
+    zerra_id: &str,
+    token: &str,
+) -> Result<InvitationInfo> {
+    let (_, row) = lookup(db, key, zerra_id, token).await?;
+    Ok(InvitationInfo {
+        needs_passcode: row.get::<Option<String>, _>("passcode_hash").is_some(),
+        invitee_name: row.get("invitee_name"),
+    })
+}
+
+/// Proves that the passcode of an invitation was entered, so that it need not travel with the
+/// request that redeems the invitation.
+fn passcode_proof(key: &[u8], invitation_id: &str) -> HmacSha256 {
+    sign(key, &format!("passcode.{invitation_id}"))
+}
+
+/// Checks the passcode of an invitation, returning a proof of it for [`redeem`].
+pub(crate) async fn enter_passcode(
+    db: &PgPool,
+    key: &[u8],
+    zerra_id: &str,
+    token: &str,
+    passcode: String,
+) -> Result<String> {
+    let (invitation_id, row) = lookup(db, key, zerra_id, token).await?;
+    if let Some(passcode_hash) = row.get::<Option<String>, _>("passcode_hash") {
+        task::spawn_blocking(move || password_auth::verify_password(passcode, &passcode_hash))
+            .await?
+            .map_err(|_| Error::Unauthorized(format!("wrong passcode for {invitation_id}")))?;
+    }
+    Ok(URL_SAFE_NO_PAD.encode(passcode_proof(key, &invitation_id).finalize().into_bytes()))
+}
+
+/// Checks an invitation on behalf of the attendee identified by `attendee_uuid`, claiming it for
+/// them if it is single-use. An invitation with a passcode needs the proof [`enter_passcode`]
+/// gave. Returns whether a single-use invitation was claimed just now, rather than before by the
+/// same attendee.
+pub(crate) async fn redeem(
+    db: &PgPool,
+    key: &[u8],
+    zerra_id: &str,
+    token: &str,
+    passcode_proof_given: Option<&str>,
+    attendee_uuid: &str,
+) -> Result<bool> {
+    let (invitation_id, row) = lookup(db, key, zerra_id, token).await?;
+
+    if row.get::<Option<String>, _>("passcode_hash").is_some() {
+        let proof = passcode_proof_given
+            .and_then(|proof| URL_SAFE_NO_PAD.decode(proof).ok())
+            .unwrap_or_default();
+        passcode_proof(key, &invitation_id)
+            .verify_slice(&proof)
+            .map_err(|_| Error::Unauthorized(format!("no passcode entered for {invitation_id}")))?;
+    }
+
+    if !row.get::<bool, _>("single_use") {
+        return Ok(false);
+    }
+    let claimed =
+        sqlx::query("update invitations set claimed_by = $1 where id = $2 and claimed_by is null")
+            .bind(attendee_uuid)
+            .bind(&invitation_id)
+            .execute(db)
+            .await?;
+    if claimed.rows_affected() > 0 {
+        return Ok(true);
+    }
+    let reclaimed = sqlx::query("select 1 from invitations where id = $1 and claimed_by = $2")
+        .bind(&invitation_id)
+        .bind(attendee_uuid)
+        .fetch_optional(db)
+        .await?;
+    match reclaimed {
+        Some(_) => Ok(false),
+        None => Err(Error::Unauthorized(format!(
+            "invitation {invitation_id} was already used"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"not so secret";
+
+    #[test]
+    fn token_roundtrip() {
+        let claims = Claims {
+            invitation_id: "0123456789abcdef",
+            zerra_id: "7544143b-2ee3-486f-a29c-7870f5ba405c",
+            expires_at: 1_000,
+        };
+        let token = issue(KEY, &claims);
+        assert_eq!(verify(KEY, &token, 999).unwrap(), claims);
+        assert!(verify(KEY, &token, 1_000).is_err());
+        assert!(verify(b"another key", &token, 999).is_err());
+    }
+
+    #[test]
+    fn tampered_token() {
+        let token = issue(
+            KEY,
+            &Claims {
+                invitation_id: "0123456789abcdef",
+                zerra_id: "7544143b-2ee3-486f-a29c-7870f5ba405c",
+                expires_at: 1_000,
+            },
+        );
+        let tampered = token.replacen(".1000.", ".9999.", 1);
+        assert_ne!(token, tampered);
+        assert!(verify(KEY, &tampered, 999).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Scripts drafted as Markdown outlines, or as plain indented ones:
+//!
+//! ```markdown
+//! # Title of the zerra
+//!
+//! - What is your name?
+//!   > I am the conductor.
+//!   - [skip] Does it have a story?
+//! - [hidden] A question only the conductor sees
+//! ```
+//!
+//! Every list item, or every line of a plain outline, is a question, nested under the one above it
+//! that is indented less. Quoted lines reveal something after the question they follow. `[skip]`
+//! lets the attendee skip a question and `[hidden]` keeps it from them. Keys are generated.
+
+use std::collections::HashSet;
+
+use types::{
+    ImportProblem,
+    zerra::{Progress, Query, Zerra},
+};
+
+use crate::models::import::MAX_QUERIES;
+
+/// Columns a tab indents by.
+const TAB_WIDTH: usize = 4;
+
+/// A question being parsed, with the indentation of its line.
+struct Item {
+    indent: usize,
+    query: Query,
+}
+
+/// Reads an outline into a zerra, with an empty ID, or else reports every line it cannot make sense
+/// of.
+pub(crate) fn parse(text: &str) -> Result<Zerra, Vec<ImportProblem>> {
+    let mut title = None;
+    let mut problems = Vec::new();
+    let mut keys = HashSet::new();
+    let mut flow = Vec::new();
+    // The questions enclosing the line being read, outermost first.
+    let mut open: Vec<Item> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let problem = |message: &str| ImportProblem {
+            line: Some(line_no),
+            path: None,
+            message: message.to_string(),
+        };
+        let (indent, content) = indentation(line);
+        if content.is_empty() {
+            continue;
+        }
+
+        let hashes = content.len() - content.trim_start_matches('#').len();
+        if hashes > 0
+            && content[hashes..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace)
+        {
+            let heading = content[hashes..].trim();
+            match title {
+                None if !heading.is_empty() => title = Some(heading.to_string()),
+                None => problems.push(problem("the title is empty")),
+                Some(_) => problems.push(problem("an outline has a single title")),
+            }
+            continue;
+        }
+
+        if let Some(revelation) = content.strip_prefix('>') {
+            let revelation = revelation.trim();
+            match open.last_mut() {
+                Some(Item { query, .. }) if query.revelation.is_empty() => {
+                    query.revelation = revelation.to_string();
+                }
+                Some(Item { query, .. }) => {
+                    query.revelation.push('\n');
+                    query.revelation.push_str(revelation);
+                }
+                None => problems.push(problem("a revelation must follow its question")),
+            }
+            continue;
+        }
+
+        let (skippable, visible, question) = markers(strip_bullet(content));
+        if question.is_empty() {
+            problems.push(problem("the question is empty"));
+            continue;
+        }
+        if keys.len() == MAX_QUERIES {
+            problems.push(problem(&format!(
+                "an outline cannot have more than {MAX_QUERIES} questions"
+            )));
+            break;
+        }
+
+        while open.last().is_some_and(|item| item.indent >= indent) {
+            close(&mut open, &mut flow);
+        }
+        open.push(Item {
+            indent,
+            query: Query {
+                key: fresh_key(&mut keys),
+                question: question.to_string(),
+                revelation: String::new(),
+                answer: None,
+                comment: None,
+                subflow: Vec::new(),
+                skippable,
+                visible,
+            },
+        });
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut flow);
+    }
+
+    if flow.is_empty() && problems.is_empty() {
+        problems.push(ImportProblem {
+            line: None,
+            path: None,
+            message: "the outline has no questions".to_string(),
+        });
+    }
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Ok(Zerra {
+        id: String::new(),
+        title: title.unwrap_or_else(|| "Title".to_string()),
+        progress: Progress::None,
+        flow,
+    })
+}
+
+/// Finishes the innermost open question, adding it to its parent or else to the flow.
+fn close(open: &mut Vec<Item>, flow: &mut Vec<Query>) {
+    if let Some(Item { query, .. }) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.query.subflow.push(query),
+            None => flow.push(query),
+        }
+    }
+}
+
+/// Measures the leading whitespace of a line in columns, returning the rest of it.
+fn indentation(line: &str) -> (usize, &str) {
+    let content = line.trim_start();
+    let indent = line[..line.len() - content.len()]
+        .chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum();
+    (indent, content.trim_end())
+}
+
+/// Takes away a `-`, `*` or `+` bullet, or a `1.` or `1)` number, if the line has one.
+fn strip_bullet(content: &str) -> &str {
+    if let Some(rest) = content
+        .strip_prefix(['-', '*', '+'])
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    {
+        return rest.trim_start();
+    }
+    let digits = content.len()
+        - content
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if digits > 0
+        && let Some(rest) = content[digits..]
+            .strip_prefix(['.', ')'])
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    {
+        return rest.trim_start();
+    }
+    content
+}
+
+/// Reads the `[skip]` and `[hidden]` markers heading a question, in any order, returning whether it
+/// is skippable and visible.
+fn markers(mut question: &str) -> (bool, bool, &str) {
+    let (mut skippable, mut visible) = (false, true);
+    loop {
+        if let Some(rest) = question
+            .strip_prefix("[skip]")
+            .or_else(|| question.strip_prefix("[skippable]"))
+        {
+            skippable = true;
+            question = rest.trim_start();
+        } else if let Some(rest) = question.strip_prefix("[hidden]") {
+            visible = false;
+            question = rest.trim_start();
+        } else {
+            return (skippable, visible, question);
+        }
+    }
+}
+
+/// Generates a key not in `keys`, which must have room for it.
+fn fresh_key(keys: &mut HashSet<u16>) -> u16 {
+    loop {
+        let key = rand::random();
+        if keys.insert(key) {
+            return key;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_outline() {
+        let zerra = parse(
+            "# Life\n\
+             \n\
+             - Where were you born?\n\
+             \x20 > In a village.\n\
+             \x20 > By a river.\n\
+             \x20 1. [skip] [hidden] Which one?\n\
+             - What do you do?\n",
+        )
+        .unwrap();
+        assert_eq!(zerra.title, "Life");
+        assert_eq!(zerra.flow.len(), 2);
+        let born = &zerra.flow[0];
+        assert_eq!(born.revelation, "In a village.\nBy a river.");
+        assert_eq!(born.subflow.len(), 1);
+        assert_eq!(born.subflow[0].question, "Which one?");
+        assert!(born.subflow[0].skippable && !born.subflow[0].visible);
+        assert_ne!(born.key, zerra.flow[1].key);
+    }
+
+    #[test]
+    fn problems_carry_line_numbers() {
+        let problems = parse("> Too early\n# Title\n- \n# Another\n").unwrap_err();
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| problem.line)
+                .collect::<Vec<_>>(),
+            [Some(1), Some(3), Some(4)]
+        );
+    }
+
+    #[test]
+    fn too_many_questions() {
+        let problems = parse(&"- Why?\n".repeat(MAX_QUERIES + 10)).unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, Some(MAX_QUERIES + 1));
+    }
+}
@@ -0,0 +1,120 @@
+use rand::seq::IndexedRandom;
+use sqlx::{PgPool, Row};
+use tokio::task;
+use types::{sharing::Role, zerra::Progress};
+
+use crate::models::{
+    Result,
+    authenticated::Context,
+    error::Error,
+    unauthenticated::{Metadata, fetch_blob},
+};
+
+/// Unambiguous when read aloud or copied by hand.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 10;
+
+async fn participant_of(db: &PgPool, zerra_id: &str) -> Result<String> {
+    let Metadata { progress } = rmp_serde::from_slice(&fetch_blob(db, zerra_id).await?)?;
+    match progress {
+        Progress::None => Err(Error::NotFound(format!("attendee of zerra {zerra_id}"))),
+        Progress::Intro { participant_uuid }
+        | Progress::Ongoing {
+            participant_uuid, ..
+        }
+        | Progress::Finished {
+            participant_uuid, ..
+        } => Ok(participant_uuid),
+    }
+}
+
+/// A random code of `CODE_LEN` characters, fit to be read aloud or copied by hand.
+pub(crate) fn random_code() -> String {
+    let mut rng = rand::rng();
+    (0..CODE_LEN)
+        .map(|_| *CODE_ALPHABET.choose(&mut rng).unwrap() as char)
+        .collect()
+}
+
+/// Replaces the outstanding code of a zerra with a fresh one.
+async fn reissue(db: &PgPool, zerra_id: &str, participant_uuid: &str) -> Result<String> {
+    let code = random_code();
+    let code_hash = {
+        let code = code.clone();
+        task::spawn_blocking(move || password_auth::generate_hash(code)).await?
+    };
+
+    sqlx::query(
+        "insert into recovery_codes (zerra_id, participant_uuid, code_hash) values ($1, $2, $3) \
+         on conflict (zerra_id) do update \
+         set participant_uuid = excluded.participant_uuid, code_hash = excluded.code_hash",
+    )
+    .bind(zerra_id)
+    .bind(participant_uuid)
+    .bind(code_hash)
+    .execute(db)
+    .await?;
+
+    Ok(code)
+}
+
+/// Issues a recovery code to the attendee holding the zerra's attendee cookie.
+pub(crate) async fn recovery_code(
+    db: &PgPool,
+    zerra_id: &str,
+    cookie_uuid: Option<&str>,
+) -> Result<String> {
+    let participant_uuid = participant_of(db, zerra_id).await?;
+    if cookie_uuid != Some(participant_uuid.as_str()) {
+        return Err(Error::Unauthorized(format!(
+            "zerra {zerra_id} attended by {participant_uuid}: {} cannot recover",
+            cookie_uuid.unwrap_or("nobody"),
+        )));
+    }
+    reissue(db, zerra_id, &participant_uuid).await
+}
+
+impl Context<'_> {
+    /// Issues a recovery code on behalf of an attendee who lost theirs.
+    pub(crate) async fn rebind(self, zerra_id: &str) -> Result<String> {
+        self.authorize(zerra_id, Role::Conductor).await?;
+        let participant_uuid = participant_of(self.db, zerra_id).await?;
+        reissue(self.db, zerra_id, &participant_uuid).await
+    }
+}
+
+/// Consumes a recovery code, returning the attendee identity it stands for.
+pub(crate) async fn recover(db: &PgPool, zerra_id: &str, code: String) -> Result<String> {
+    let invalid = || Error::Unauthorized(format!("invalid recovery code for zerra {zerra_id}"));
+
+    let row =
+        sqlx::query("select participant_uuid, code_hash from recovery_codes where zerra_id = $1")
+            .bind(zerra_id)
+            .fetch_optional(db)
+            .await?
+            .ok_or_else(invalid)?;
+    let participant_uuid: String = row.get("participant_uuid");
+    let code_hash: String = row.get("code_hash");
+
+    let code = code.trim().to_uppercase();
+    let matched = {
+        let code_hash = code_hash.clone();
+        task::spawn_blocking(move || password_auth::verify_password(code, &code_hash).is_ok())
+            .await?
+    };
+    if !matched {
+        return Err(invalid());
+    }
+
+    // Whoever deletes the row first gets to use the code.
+    let consumed = sqlx::query("delete from recovery_codes where zerra_id = $1 and code_hash = $2")
+        .bind(zerra_id)
+        .bind(code_hash)
+        .execute(db)
+        .await?;
+    if consumed.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    Ok(participant_uuid)
+}
@@ -0,0 +1,343 @@
+use std::{collections::HashMap, time::Instant};
+
+use sqlx::{Row, postgres::PgRow};
+use types::{
+    report::{AnswerCount, Answers, QuestionReport, Report},
+    sharing::Role,
+    zerra::{Progress, Query, Zerra},
+};
+
+use crate::{
+    env::I18n,
+    models::{Result, authenticated::Context, sharing::GRANTS, unauthenticated::fetch_blob},
+    utils::{csv_cell, typst_str},
+};
+
+/// Longest answer that may count as a choice rather than free text.
+const MAX_CHOICE_LEN: usize = 64;
+
+impl Context<'_> {
+    /// Aggregates the answers of the finished interviews the user can reach that were started from
+    /// a template.
+    pub(crate) async fn report(self, template_id: &str) -> Result<Report> {
+        self.authorize(template_id, Role::Viewer).await?;
+        let template: Zerra = rmp_serde::from_slice(&fetch_blob(self.db, template_id).await?)?;
+
+        let query = format!(
+            "select vus.data from vus where vus.template_id = $2 \
+             and vus.id in (select zerra_id from ({GRANTS}) g) \
+             order by vus.last_modified"
+        );
+        let interviews = sqlx::query(&query)
+            .bind(self.user)
+            .bind(template_id)
+            .try_map(|r: PgRow| {
+                rmp_serde::from_slice::<Zerra>(r.try_get("data")?)
+                    .map_err(|e| sqlx::Error::Decode(e.into()))
+            })
+            .fetch_all(self.db)
+            .await?
+            .into_iter()
+            .filter(|zerra| matches!(zerra.progress, Progress::Finished { .. }))
+            .collect::<Vec<_>>();
+
+        let mut tally = Tally::default();
+        tally.script(&template.flow, &mut Vec::new(), true);
+        for interview in &interviews {
+            tally.script(&interview.flow, &mut Vec::new(), false);
+            tally.interview(&interview.flow);
+        }
+
+        Ok(Report {
+            template: template.id,
+            title: template.title,
+            interviews: interviews.len(),
+            questions: tally.into_questions(),
+        })
+    }
+}
+
+/// The questions met so far, in the order of the template and then of the interviews.
+#[derive(Default)]
+struct Tally {
+    order: Vec<u16>,
+    questions: HashMap<u16, (QuestionReport, Vec<String>)>,
+}
+
+impl Tally {
+    /// Notes the questions of a flow. Those of the template take precedence, so that reports
+    /// follow its current order and wording.
+    fn script(&mut self, flow: &[Query], path: &mut Vec<usize>, authoritative: bool) {
+        for (i, query) in flow.iter().enumerate() {
+            path.push(i);
+            if authoritative || !self.questions.contains_key(&query.key) {
+                self.order.push(query.key);
+                self.questions.insert(
+                    query.key,
+                    (
+                        QuestionReport {
+                            key: query.key,
+                            path: path.clone(),
+                            question: query.question.clone(),
+                            skippable: query.skippable,
+                            asked: 0,
+                            answered: 0,
+                            skipped: 0,
+                            answers: Answers::FreeText {
+                                answers: Vec::new(),
+                            },
+                        },
+                        Vec::new(),
+                    ),
+                );
+            }
+            self.script(&query.subflow, path, authoritative);
+            path.pop();
+        }
+    }
+
+    /// Counts the answers of a finished interview. Hidden questions were never asked.
+    fn interview(&mut self, flow: &[Query]) {
+        for query in flow.iter().filter(|query| query.visible) {
+            if let Some((report, answers)) = self.questions.get_mut(&query.key) {
+                report.asked += 1;
+                match answer_of(query) {
+                    Some(answer) => {
+                        report.answered += 1;
+                        answers.push(answer.to_string());
+                    }
+                    None if query.skippable => report.skipped += 1,
+                    None => {}
+                }
+            }
+            self.interview(&query.subflow);
+        }
+    }
+
+    fn into_questions(mut self) -> Vec<QuestionReport> {
+        // A key noted twice by the template, which a faulty import may produce, is reported once.
+        let mut seen = std::collections::HashSet::new();
+        self.order.retain(|key| seen.insert(*key));
+        self.order
+            .into_iter()
+            .filter_map(|key| self.questions.remove(&key))
+            .map(|(report, answers)| QuestionReport {
+                answers: aggregate(answers),
+                ..report
+            })
+            .collect()
+    }
+}
+
+/// Answers count as choices when each is a short line and some recur; otherwise they are listed
+/// as free text.
+fn aggregate(answers: Vec<String>) -> Answers {
+    let mut counts = HashMap::<&str, usize>::new();
+    for answer in &answers {
+        *counts.entry(answer).or_default() += 1;
+    }
+    let short = answers
+        .iter()
+        .all(|answer| !answer.contains('\n') && answer.chars().count() <= MAX_CHOICE_LEN);
+    if !short || counts.len() == answers.len() {
+        return Answers::FreeText { answers };
+    }
+
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(answer, count)| AnswerCount {
+            answer: answer.to_string(),
+            count,
+        })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.answer.cmp(&b.answer)));
+    Answers::Choices { counts }
+}
+
+/// The answer to a query, unless it was left blank.
+pub(crate) fn answer_of(query: &Query) -> Option<&str> {
+    query
+        .answer
+        .as_deref()
+        .map(str::trim)
+        .filter(|answer| !answer.is_empty())
+}
+
+/// Numbers a question the way the editor does, e.g. `1 : 2`.
+pub(crate) fn display_of(path: &[usize]) -> String {
+    path.iter()
+        .map(|i| (i + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(" : ")
+}
+
+/// Lays the report out as CSV, one row per distinct answer of each question.
+pub(crate) fn to_csv(report: &Report) -> Result<Vec<u8>> {
+    let mut csv = csv::Writer::from_writer(Vec::new());
+    csv.write_record([
+        "path",
+        "key",
+        "question",
+        "asked",
+        "answered",
+        "skipped",
+        "skip_rate",
+        "answer",
+        "count",
+    ])?;
+    for question in &report.questions {
+        let answers: Vec<(&str, usize)> = match &question.answers {
+            Answers::Choices { counts } => counts
+                .iter()
+                .map(|AnswerCount { answer, count }| (answer.as_str(), *count))
+                .collect(),
+            Answers::FreeText { answers } => answers.iter().map(|a| (a.as_str(), 1)).collect(),
+        };
+        let skip_rate = question
+            .skip_rate()
+            .map_or_else(String::new, |rate| format!("{rate:.3}"));
+        // A question nobody answered still gets its row of counts.
+        let answers = if answers.is_empty() {
+            vec![("", 0)]
+        } else {
+            answers
+        };
+        for (answer, count) in answers {
+            csv.write_record([
+                display_of(&question.path).as_str(),
+                &question.key.to_string(),
+                &csv_cell(&question.question),
+                &question.asked.to_string(),
+                &question.answered.to_string(),
+                &question.skipped.to_string(),
+                &skip_rate,
+                &csv_cell(answer),
+                &count.to_string(),
+            ])?;
+        }
+    }
+    Ok(csv
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?)
+}
+
+/// Typesets a summary of the report as PDF.
+pub(crate) fn render(
+    report: &Report,
+    I18n {
+        interviews,
+        answered,
+        skipped,
+        page_number_format,
+        ..
+    }: &I18n,
+) -> Result<Vec<u8>> {
+    let questions: String = report
+        .questions
+        .iter()
+        .map(|question| {
+            let (kind, answers) = match &question.answers {
+                Answers::Choices { counts } => (
+                    "choices",
+                    counts
+                        .iter()
+                        .map(|c| format!("({}, {}),", typst_str(&c.answer), c.count))
+                        .collect::<String>(),
+                ),
+                Answers::FreeText { answers } => (
+                    "text",
+                    answers
+                        .iter()
+                        .map(|a| format!("{},", typst_str(a)))
+                        .collect::<String>(),
+                ),
+            };
+            format!(
+                "(index: {}, question: {}, asked: {}, answered: {}, skipped: {}, kind: \"{kind}\", \
+                 answers: ({answers})),",
+                typst_str(&display_of(&question.path)),
+                typst_str(&question.question),
+                question.asked,
+                question.answered,
+                question.skipped,
+            )
+        })
+        .collect();
+
+    let typst_doc = format!(
+        r#"
+#let title = {title}
+#let interviews = {interview_count}
+#let questions = ({questions})
+
+#set page(paper: "a4", numbering: "{page_number_format}")
+#show heading.where(level: 1): set align(center)
+
+= #title
+
+#align(center)[#interviews {interviews}]
+
+#for q in questions [
+  == #q.index #q.question
+
+  #text(size: 0.9em, style: "italic")[
+    #q.answered {answered} · #q.skipped {skipped}
+    #if q.asked > 0 [(#calc.round(q.skipped / q.asked * 100)%)]
+  ]
+
+  #if q.kind == "choices" {{
+    let most = calc.max(1, ..q.answers.map(a => a.at(1)))
+    table(
+      columns: (auto, 1fr, auto),
+      stroke: none,
+      ..q.answers.map(a => (
+        a.at(0),
+        box(width: 100% * a.at(1) / most, height: 0.8em, fill: luma(160)),
+        str(a.at(1)),
+      )).flatten()
+    )
+  }} else {{
+    for a in q.answers [- #a]
+  }}
+]
+"#,
+        title = typst_str(&report.title),
+        interview_count = report.interviews,
+    );
+    let compiler = tokape::TypstCompiler::new()?;
+    let started = Instant::now();
+    let pdf = compiler.compile_to_pdf(typst_doc);
+    metrics::histogram!("zerra_typst_compile_seconds").record(started.elapsed());
+    if pdf.is_err() {
+        metrics::counter!("zerra_typst_failures_total").increment(1);
+    }
+    Ok(pdf?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recurring_short_answers_are_choices() {
+        let answers = |answers: &[&str]| answers.iter().map(|a| a.to_string()).collect();
+        let Answers::Choices { counts } = aggregate(answers(&["yes", "no", "yes"])) else {
+            panic!("short recurring answers should be choices");
+        };
+        assert_eq!(
+            counts
+                .iter()
+                .map(|c| (c.answer.as_str(), c.count))
+                .collect::<Vec<_>>(),
+            [("yes", 2), ("no", 1)]
+        );
+        assert!(matches!(
+            aggregate(answers(&["yes", "no"])),
+            Answers::FreeText { .. }
+        ));
+        assert!(matches!(
+            aggregate(answers(&["a\nlong story", "a\nlong story"])),
+            Answers::FreeText { .. }
+        ));
+    }
+}
@@ -0,0 +1,266 @@
+use sqlx::{Row, postgres::PgRow};
+use types::sharing::{Grant, Grantee, Role, Team};
+
+use crate::models::{Result, authenticated::Context, error::Error};
+
+/// Every zerra a user (bound as `$1`) can reach, once per grant, with the role that grant confers.
+/// Trashed zerrae are out of reach, even of their owner, until restored.
+pub(crate) const GRANTS: &str = "\
+    select g.zerra_id, g.role from ( \
+        select id as zerra_id, 'owner' as role from vus where owner = $1 \
+        union all \
+        select zerra_id, role from shares where grantee_user = $1 \
+        union all \
+        select s.zerra_id, s.role from shares s \
+            join team_members m on s.grantee_team = m.team_id \
+            where m.user_id = $1 \
+    ) g join vus on vus.id = g.zerra_id where vus.deleted_at is null";
+
+pub(crate) fn decode_role(role: &str) -> sqlx::Result<Role> {
+    Role::from_name(role).ok_or_else(|| sqlx::Error::Decode(format!("unknown role {role}").into()))
+}
+
+impl Context<'_> {
+    pub(crate) async fn role_in(self, zerra_id: &str) -> Result<Option<Role>> {
+        let query = format!("select role from ({GRANTS}) g where g.zerra_id = $2");
+        let roles = sqlx::query(&query)
+            .bind(self.user)
+            .bind(zerra_id)
+            .try_map(|r: PgRow| decode_role(r.try_get(0)?))
+            .fetch_all(self.db)
+            .await?;
+        Ok(roles.into_iter().max())
+    }
+
+    pub(crate) async fn authorize(self, zerra_id: &str, at_least: Role) -> Result<Role> {
+        match self.role_in(zerra_id).await? {
+            Some(role) if role >= at_least => Ok(role),
+            Some(role) => Err(Error::Forbidden(format!(
+                "user({}) is only {} of zerra {zerra_id}; needs to be {}",
+                self.user,
+                role.as_str(),
+                at_least.as_str(),
+            ))),
+            None => Err(Error::Unauthorized(format!(
+                "user({}) has no access to zerra {zerra_id}",
+                self.user
+            ))),
+        }
+    }
+
+    pub(crate) async fn grants(self, zerra_id: &str) -> Result<Vec<Grant>> {
+        self.authorize(zerra_id, Role::Owner).await?;
+        Ok(sqlx::query(
+            "select s.role, u.username, s.grantee_team from shares s \
+             left join users u on s.grantee_user = u.id \
+             where s.zerra_id = $1",
+        )
+        .bind(zerra_id)
+        .try_map(|r: PgRow| {
+            let grantee = match r.try_get::<Option<String>, _>("username")? {
+                Some(username) => Grantee::User(username),
+                None => Grantee::Team(r.try_get("grantee_team")?),
+            };
+            Ok(Grant {
+                grantee,
+                role: decode_role(r.try_get("role")?)?,
+            })
+        })
+        .fetch_all(self.db)
+        .await?)
+    }
+
+    pub(crate) async fn share(self, zerra_id: &str, Grant { grantee, role }: Grant) -> Result {
+        self.authorize(zerra_id, Role::Owner).await?;
+        if role == Role::Owner {
+            return Err(Error::BadRequest("ownership cannot be shared".into()));
+        }
+
+        match grantee {
+            Grantee::User(username) => {
+                let grantee: i64 = sqlx::query("select id from users where username = $1")
+                    .bind(&username)
+                    .fetch_optional(self.db)
+                    .await?
+                    .map(|r| r.get(0))
+                    .ok_or_else(|| Error::NotFound(format!("user {username}")))?;
+                if grantee == self.user {
+                    return Err(Error::BadRequest(
+                        "cannot share a zerra with its owner".into(),
+                    ));
+                }
+                sqlx::query(
+                    "insert into shares (zerra_id, grantee_user, role) values ($1, $2, $3) \
+                     on conflict (zerra_id, grantee_user) where grantee_user is not null \
+                     do update set role = excluded.role",
+                )
+                .bind(zerra_id)
+                .bind(grantee)
+                .bind(role.as_str())
+                .execute(self.db)
+                .await?;
+            }
+            Grantee::Team(team_id) => {
+                self.member_of(team_id).await?;
+                sqlx::query(
+                    "insert into shares (zerra_id, grantee_team, role) values ($1, $2, $3) \
+                     on conflict (zerra_id, grantee_team) where grantee_team is not null \
+                     do update set role = excluded.role",
+                )
+                .bind(zerra_id)
+                .bind(team_id)
+                .bind(role.as_str())
+                .execute(self.db)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn unshare(self, zerra_id: &str, grantee: Grantee) -> Result {
+        self.authorize(zerra_id, Role::Owner).await?;
+        match grantee {
+            Grantee::User(username) => sqlx::query(
+                "delete from shares where zerra_id = $1 \
+                 and grantee_user = (select id from users where username = $2)",
+            )
+            .bind(zerra_id)
+            .bind(username),
+            Grantee::Team(team_id) => {
+                sqlx::query("delete from shares where zerra_id = $1 and grantee_team = $2")
+                    .bind(zerra_id)
+                    .bind(team_id)
+            }
+        }
+        .execute(self.db)
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn teams(self) -> Result<Vec<Team>> {
+        Ok(sqlx::query(
+            "select t.id, t.name, t.owner = $1 as owned, \
+                 array_agg(u.username order by u.username) as members \
+             from teams t \
+             join team_members m on m.team_id = t.id \
+             join users u on u.id = m.user_id \
+             where t.id in (select team_id from team_members where user_id = $1) \
+             group by t.id",
+        )
+        .bind(self.user)
+        .try_map(|r: PgRow| {
+            Ok(Team {
+                id: r.try_get("id")?,
+                name: r.try_get("name")?,
+                owned: r.try_get("owned")?,
+                members: r.try_get("members")?,
+            })
+        })
+        .fetch_all(self.db)
+        .await?)
+    }
+
+    pub(crate) async fn new_team(self, name: &str) -> Result {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::BadRequest("team name must not be empty".into()));
+        }
+
+        let mut tx = self.db.begin().await?;
+        let team_id: i64 =
+            sqlx::query("insert into teams (name, owner) values ($1, $2) returning id")
+                .bind(name)
+                .bind(self.user)
+                .fetch_one(&mut *tx)
+                .await
+                .map(|r| r.get(0))?;
+        sqlx::query("insert into team_members (team_id, user_id) values ($1, $2)")
+            .bind(team_id)
+            .bind(self.user)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn add_member(self, team_id: i64, username: &str) -> Result {
+        self.owns_team(team_id).await?;
+        let added = sqlx::query(
+            "insert into team_members (team_id, user_id) \
+             select $1, id from users where username = $2 \
+             on conflict do nothing",
+        )
+        .bind(team_id)
+        .bind(username)
+        .execute(self.db)
+        .await?;
+        if added.rows_affected() == 0 {
+            sqlx::query("select 1 from users where username = $1")
+                .bind(username)
+                .fetch_optional(self.db)
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("user {username}")))?;
+        }
+        Ok(())
+    }
+
+    /// Removes a member from a team. Owners may remove anybody but themselves; members may only
+    /// leave.
+    pub(crate) async fn remove_member(self, team_id: i64, username: &str) -> Result {
+        let member: i64 = sqlx::query("select id from users where username = $1")
+            .bind(username)
+            .fetch_optional(self.db)
+            .await?
+            .map(|r| r.get(0))
+            .ok_or_else(|| Error::NotFound(format!("user {username}")))?;
+
+        if member == self.user {
+            self.member_of(team_id).await?;
+            if self.owns_team(team_id).await.is_ok() {
+                return Err(Error::BadRequest(format!(
+                    "owner cannot leave team({team_id})"
+                )));
+            }
+        } else {
+            self.owns_team(team_id).await?;
+        }
+
+        sqlx::query("delete from team_members where team_id = $1 and user_id = $2")
+            .bind(team_id)
+            .bind(member)
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn owns_team(self, team_id: i64) -> Result {
+        sqlx::query("select count(1) from teams where id = $1 and owner = $2")
+            .bind(team_id)
+            .bind(self.user)
+            .fetch_one(self.db)
+            .await
+            .map(|r| r.get::<i64, _>(0) != 0)?
+            .then_some(())
+            .ok_or_else(|| {
+                Error::Forbidden(format!("user({}) does not own team({team_id})", self.user))
+            })
+    }
+
+    async fn member_of(self, team_id: i64) -> Result {
+        sqlx::query("select count(1) from team_members where team_id = $1 and user_id = $2")
+            .bind(team_id)
+            .bind(self.user)
+            .fetch_one(self.db)
+            .await
+            .map(|r| r.get::<i64, _>(0) != 0)?
+            .then_some(())
+            .ok_or_else(|| {
+                Error::Forbidden(format!(
+                    "user({}) is not a member of team({team_id})",
+                    self.user
+                ))
+            })
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeSet;
+
+use rust_xlsxwriter::{Format, Workbook};
+use sqlx::{Row, postgres::PgRow};
+use types::zerra::{Progress, Query, Zerra};
+
+use crate::{
+    models::{
+        Result,
+        authenticated::Context,
+        error::Error,
+        report::{answer_of, display_of},
+        sharing::GRANTS,
+    },
+    utils::csv_cell,
+};
+
+/// Most zerrae a single export may cover.
+const MAX_EXPORT: usize = 500;
+/// Longest text a spreadsheet cell holds.
+const MAX_CELL_LEN: usize = 32_767;
+
+const HEADER: [&str; 10] = [
+    "zerra",
+    "title",
+    "participant",
+    "path",
+    "question",
+    "answer",
+    "comment",
+    "revelation",
+    "skipped",
+    "visible",
+];
+
+/// A query of a finished zerra, flattened out of its flow.
+#[derive(Debug)]
+pub(crate) struct Line {
+    zerra_id: String,
+    title: String,
+    participant: String,
+    path: Vec<usize>,
+    question: String,
+    answer: Option<String>,
+    comment: Option<String>,
+    revelation: String,
+    skipped: bool,
+    visible: bool,
+}
+
+impl Line {
+    fn cells(&self) -> [String; 8] {
+        [
+            self.zerra_id.clone(),
+            self.title.clone(),
+            self.participant.clone(),
+            display_of(&self.path),
+            self.question.clone(),
+            self.answer.clone().unwrap_or_default(),
+            self.comment.clone().unwrap_or_default(),
+            self.revelation.clone(),
+        ]
+    }
+}
+
+impl Context<'_> {
+    /// Flattens the finished zerrae among those given, and among the interviews started from a
+    /// template, into one line per query. Zerrae not finished yet are left out.
+    pub(crate) async fn tabulate(
+        self,
+        ids: &[String],
+        template: Option<&str>,
+    ) -> Result<Vec<Line>> {
+        let ids: BTreeSet<&str> = ids.iter().map(String::as_str).collect();
+        if ids.is_empty() && template.is_none() {
+            return Err(Error::BadRequest("nothing to export".into()));
+        }
+        let too_many = || {
+            Error::BadRequest(format!(
+                "at most {MAX_EXPORT} zerrae can be exported at once"
+            ))
+        };
+        if ids.len() > MAX_EXPORT {
+            return Err(too_many());
+        }
+
+        // One more than may be exported is fetched, to tell that there are too many.
+        let query = format!(
+            "select vus.id, vus.data from vus \
+             where (vus.id = any($2) or vus.template_id = $3) \
+             and vus.id in (select zerra_id from ({GRANTS}) g) \
+             order by vus.last_modified limit {}",
+            MAX_EXPORT + 1
+        );
+        let zerrae = sqlx::query(&query)
+            .bind(self.user)
+            .bind(ids.iter().copied().collect::<Vec<_>>())
+            .bind(template)
+            .try_map(|r: PgRow| {
+                rmp_serde::from_slice::<Zerra>(r.try_get("data")?)
+                    .map_err(|e| sqlx::Error::Decode(e.into()))
+            })
+            .fetch_all(self.db)
+            .await?;
+
+        if zerrae.len() > MAX_EXPORT {
+            return Err(too_many());
+        }
+        if let Some(missing) = ids
+            .iter()
+            .find(|id| !zerrae.iter().any(|zerra| zerra.id == **id))
+        {
+            return Err(Error::Unauthorized(format!(
+                "user({}) has no access to zerra {missing}",
+                self.user
+            )));
+        }
+
+        let mut lines = Vec::new();
+        for zerra in zerrae {
+            let Progress::Finished {
+                participant_name, ..
+            } = &zerra.progress
+            else {
+                continue;
+            };
+            flatten(
+                &zerra,
+                participant_name,
+                &zerra.flow,
+                &mut Vec::new(),
+                &mut lines,
+            );
+        }
+        Ok(lines)
+    }
+}
+
+fn flatten(
+    zerra: &Zerra,
+    participant: &str,
+    flow: &[Query],
+    path: &mut Vec<usize>,
+    lines: &mut Vec<Line>,
+) {
+    for (i, query) in flow.iter().enumerate() {
+        path.push(i);
+        lines.push(Line {
+            zerra_id: zerra.id.clone(),
+            title: zerra.title.clone(),
+            participant: participant.to_string(),
+            path: path.clone(),
+            question: query.question.clone(),
+            answer: query.answer.clone(),
+            comment: query.comment.clone(),
+            revelation: query.revelation.clone(),
+            skipped: query.visible && query.skippable && answer_of(query).is_none(),
+            visible: query.visible,
+        });
+        flatten(zerra, participant, &query.subflow, path, lines);
+        path.pop();
+    }
+}
+
+pub(crate) fn to_csv(lines: &[Line]) -> Result<Vec<u8>> {
+    let mut csv = csv::Writer::from_writer(Vec::new());
+    csv.write_record(HEADER)?;
+    for line in lines {
+        let flags = [line.skipped.to_string(), line.visible.to_string()];
+        let cells = line.cells().map(|cell| csv_cell(&cell).into_owned());
+        csv.write_record(cells.into_iter().chain(flags))?;
+    }
+    Ok(csv
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?)
+}
+
+pub(crate) fn to_xlsx(lines: &[Line]) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let bold = Format::new().set_bold();
+    for (col, name) in (0..).zip(HEADER) {
+        sheet.write_string_with_format(0, col, name, &bold)?;
+    }
+    for (row, line) in (1..).zip(lines) {
+        for (col, cell) in (0..).zip(line.cells()) {
+            let end = cell
+                .char_indices()
+                .nth(MAX_CELL_LEN)
+                .map_or(cell.len(), |(end, _)| end);
+            sheet.write_string(row, col, &cell[..end])?;
+        }
+        sheet.write_boolean(row, 8, line.skipped)?;
+        sheet.write_boolean(row, 9, line.visible)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, lines.len() as u32, HEADER.len() as u16 - 1)?;
+    Ok(workbook.save_to_buffer()?)
+}
@@ -0,0 +1,256 @@
+use sqlx::{PgPool, Row, postgres::PgRow};
+use types::{
+    TemplateItem,
+    jiff::Timestamp,
+    sharing::Role,
+    zerra::{Progress, Query, Zerra},
+};
+
+use crate::models::{
+    Result,
+    audit::{self, Action, Origin},
+    authenticated::Context,
+    error::Error,
+    sharing::{GRANTS, decode_role},
+    unauthenticated::{Summary, clear_answers, fetch_blob},
+};
+
+/// Templates are only ever edited. Interviews are started from them instead of conducting them.
+pub(crate) async fn refuse_template(db: &PgPool, zerra_id: &str) -> Result {
+    let is_template: bool = sqlx::query("select is_template from vus where id = $1")
+        .bind(zerra_id)
+        .fetch_optional(db)
+        .await?
+        .is_some_and(|r| r.get(0));
+    if is_template {
+        return Err(Error::Conflict(format!(
+            "zerra {zerra_id} is a template; start an interview from it instead"
+        )));
+    }
+    Ok(())
+}
+
+impl Context<'_> {
+    /// The templates the user can reach, most recently modified first.
+    pub(crate) async fn templates(self) -> Result<Vec<TemplateItem>> {
+        #[derive(serde::Deserialize)]
+        struct Metadata {
+            title: String,
+        }
+
+        let query = format!(
+            "select vus.id, vus.data, vus.last_modified, array_agg(g.role) as roles, \
+             (select max(version) from template_versions where template_id = vus.id) as version, \
+             (select count(1) from vus i where i.template_id = vus.id and i.deleted_at is null) \
+             as instances \
+             from vus join ({GRANTS}) g on g.zerra_id = vus.id \
+             where vus.is_template group by vus.id order by vus.last_modified desc"
+        );
+        let items = sqlx::query(&query)
+            .bind(self.user)
+            .try_map(|r: PgRow| {
+                let metadata: Metadata = rmp_serde::from_slice(r.try_get("data")?)
+                    .map_err(|e| sqlx::Error::Decode(e.into()))?;
+                // A template reachable through several grants is listed with the strongest role.
+                let roles: Vec<String> = r.try_get("roles")?;
+                let role = roles
+                    .iter()
+                    .map(|role| decode_role(role))
+                    .collect::<sqlx::Result<Vec<_>>>()?
+                    .into_iter()
+                    .max()
+                    .unwrap_or(Role::Viewer);
+                Ok(TemplateItem {
+                    id: r.try_get("id")?,
+                    name: metadata.title,
+                    last_modified: Timestamp::from_second(r.try_get("last_modified")?)
+                        .map_err(|e| sqlx::Error::Decode(e.into()))?,
+                    role,
+                    version: r.try_get("version")?,
+                    instances: r.try_get("instances")?,
+                })
+            })
+            .fetch_all(self.db)
+            .await?;
+        Ok(items)
+    }
+
+    /// Creates a template, blank or with the script of a zerra the user can view, whose answers are
+    /// left behind. The ID of the template is returned.
+    pub(crate) async fn new_template(
+        self,
+        from_id: Option<&str>,
+        origin: &Origin,
+    ) -> Result<String> {
+        let id = uuid::Uuid::new_v4().hyphenated().to_string();
+        let template = match from_id {
+            Some(from_id) => {
+                self.authorize(from_id, Role::Viewer).await?;
+                let blob = fetch_blob(self.db, from_id).await?;
+                let mut zerra: Zerra = rmp_serde::from_slice(&blob)?;
+                zerra.progress = Progress::None;
+                clear_answers(&mut zerra.flow);
+                Zerra { id, ..zerra }
+            }
+            None => Zerra {
+                id,
+                title: "Title".to_string(),
+                progress: Progress::None,
+                flow: vec![Query {
+                    key: rand::random(),
+                    question: String::new(),
+                    revelation: String::new(),
+                    answer: None,
+                    comment: None,
+                    subflow: Vec::new(),
+                    skippable: false,
+                    visible: true,
+                }],
+            },
+        };
+
+        let summary = Summary::of(&template);
+        sqlx::query(
+            "insert into vus (id, owner, data, last_modified, is_template, \
+             title, status, participant) values ($1, $2, $3, $4, true, $5, $6, $7)",
+        )
+        .bind(&template.id)
+        .bind(self.user)
+        .bind(rmp_serde::to_vec_named(&template)?)
+        .bind(Timestamp::now().as_second())
+        .bind(summary.title)
+        .bind(summary.status)
+        .bind(summary.participant)
+        .execute(self.db)
+        .await?;
+
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::CreateTemplate,
+            Some(&template.id),
+            from_id.map(|from_id| format!("from {from_id}")).as_deref(),
+        )
+        .await?;
+        Ok(template.id)
+    }
+
+    /// Starts an interview from the current version of a template, as a zerra of the user. The
+    /// version is kept the first time an interview is started from it. The ID of the interview is
+    /// returned.
+    pub(crate) async fn start_interview(
+        self,
+        template_id: &str,
+        origin: &Origin,
+    ) -> Result<String> {
+        self.authorize(template_id, Role::Viewer).await?;
+
+        let mut tx = self.db.begin().await?;
+        // Locking the template keeps concurrent starts from numbering the same version twice.
+        let blob = sqlx::query("select data from vus where id = $1 and is_template for update")
+            .bind(template_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|r| r.get::<Vec<u8>, _>(0))
+            .ok_or_else(|| Error::NotFound(format!("template {template_id}")))?;
+        let latest = sqlx::query(
+            "select version, data from template_versions where template_id = $1 \
+             order by version desc limit 1",
+        )
+        .bind(template_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let version = match latest {
+            Some(r) if r.get::<Vec<u8>, _>("data") == blob => r.get("version"),
+            latest => {
+                let version = latest.map_or(1, |r| r.get::<i32, _>("version") + 1);
+                sqlx::query(
+                    "insert into template_versions (template_id, version, data, created_at) \
+                     values ($1, $2, $3, $4)",
+                )
+                .bind(template_id)
+                .bind(version)
+                .bind(&blob)
+                .bind(Timestamp::now().as_second())
+                .execute(&mut *tx)
+                .await?;
+                version
+            }
+        };
+
+        let template: Zerra = rmp_serde::from_slice(&blob)?;
+        let interview = Zerra {
+            id: uuid::Uuid::new_v4().hyphenated().to_string(),
+            ..template
+        };
+        let summary = Summary::of(&interview);
+        sqlx::query(
+            "insert into vus (id, owner, data, last_modified, template_id, template_version, \
+             title, status, participant) values ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(&interview.id)
+        .bind(self.user)
+        .bind(rmp_serde::to_vec_named(&interview)?)
+        .bind(Timestamp::now().as_second())
+        .bind(template_id)
+        .bind(version)
+        .bind(summary.title)
+        .bind(summary.status)
+        .bind(summary.participant)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::StartInterview,
+            Some(&interview.id),
+            Some(&format!("from template {template_id} version {version}")),
+        )
+        .await?;
+        Ok(interview.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::unauthenticated::update_blob;
+
+    #[sqlx::test]
+    async fn versions_follow_changes(db: PgPool) -> Result {
+        let ctx = Context { db: &db, user: 1 };
+        let origin = Origin::server();
+        let template_id = ctx.new_template(None, &origin).await?;
+        let version_of = |interview_id: String| {
+            let db = &db;
+            async move {
+                sqlx::query("select template_version from vus where id = $1")
+                    .bind(interview_id)
+                    .fetch_one(db)
+                    .await
+                    .map(|r| r.get::<i32, _>(0))
+            }
+        };
+
+        // An unchanged template keeps its version.
+        let first = ctx.start_interview(&template_id, &origin).await?;
+        let second = ctx.start_interview(&template_id, &origin).await?;
+        assert_eq!(version_of(first).await?, 1);
+        assert_eq!(version_of(second).await?, 1);
+
+        let mut template: Zerra = rmp_serde::from_slice(&fetch_blob(&db, &template_id).await?)?;
+        template.title = "Revised".to_string();
+        update_blob(&db, &template_id, &rmp_serde::to_vec_named(&template)?).await?;
+        let third = ctx.start_interview(&template_id, &origin).await?;
+        assert_eq!(version_of(third).await?, 2);
+
+        let templates = ctx.templates().await?;
+        assert_eq!(templates[0].version, Some(2));
+        assert_eq!(templates[0].instances, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use sqlx::{PgPool, Row};
+use types::jiff::Timestamp;
+
+use crate::models::audit::UNKNOWN_CLIENT;
+
+/// How often lockouts that are over and old failed logins are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long failed logins are kept for auditing, in seconds.
+const FAILED_LOGIN_RETENTION: i64 = 90 * 24 * 60 * 60;
+
+/// How many failed logins are tolerated before the username or client is locked out.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LoginLimits {
+    pub(crate) max_failures: i32,
+    pub(crate) max_failures_per_client: i32,
+    /// Seconds a lockout lasts, which is also how long failures are remembered.
+    pub(crate) lockout: i64,
+}
+
+/// Slows down repeated login failures: every failure doubles the wait before the next attempt,
+/// until the limit is reached and the key is locked out altogether.
+#[derive(Debug, Clone)]
+pub(crate) struct Throttle {
+    db: PgPool,
+    limits: LoginLimits,
+    /// What attempts are made at, such as logging in as a user.
+    scope: &'static str,
+}
+
+/// Seconds to wait after `failures` consecutive failures, the last of which happened at
+/// `last_failure`.
+fn retry_after(failures: i32, last_failure: i64, now: i64, max_failures: i32, lockout: i64) -> i64 {
+    let wait = if failures >= max_failures {
+        lockout
+    } else {
+        (1_i64 << (failures - 1).clamp(0, 30)).min(lockout)
+    };
+    (last_failure + wait - now).max(0)
+}
+
+impl Throttle {
+    pub(crate) fn new(db: PgPool, limits: LoginLimits) -> Self {
+        Throttle {
+            db,
+            limits,
+            scope: "user",
+        }
+    }
+
+    /// Throttles attempts at the recovery code of a zerra, which count against the client along
+    /// with its failed logins.
+    pub(crate) fn recovery(db: PgPool, limits: LoginLimits) -> Self {
+        Throttle {
+            db,
+            limits,
+            scope: "recovery",
+        }
+    }
+
+    /// The keys an attempt counts against. Clients whose address is unknown are not told apart,
+    /// so they are not throttled together, lest one lock all of them out.
+    fn keys(&self, subject: &str, client: &str) -> Vec<String> {
+        let mut keys = vec![format!("{}:{subject}", self.scope)];
+        if client != UNKNOWN_CLIENT {
+            keys.push(format!("client:{client}"));
+        }
+        keys
+    }
+
+    /// Seconds the caller must wait before trying to log in again, if any. Otherwise the attempt
+    /// is counted as a failure right away, so that concurrent attempts see one another, and
+    /// taken back once it turns out to succeed.
+    pub(crate) async fn admit(&self, subject: &str, client: &str) -> sqlx::Result<Option<i64>> {
+        let now = Timestamp::now().as_second();
+        let mut keys = self.keys(subject, client);
+        let user_key = keys[0].clone();
+        // Rows are locked in the same order by every attempt, lest two of them deadlock.
+        keys.sort();
+        let mut tx = self.db.begin().await?;
+        for key in &keys {
+            sqlx::query(
+                "insert into login_throttles (key, failures, last_failure) values ($1, 0, 0) \
+                 on conflict (key) do nothing",
+            )
+            .bind(key)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let rows = sqlx::query(
+            "select key, failures, last_failure from login_throttles \
+             where key = any($1) order by key for update",
+        )
+        .bind(&keys)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let wait = rows
+            .iter()
+            .filter(|r| {
+                r.get::<i32, _>("failures") > 0
+                    && r.get::<i64, _>("last_failure") + self.limits.lockout > now
+            })
+            .map(|r| {
+                let max_failures = if r.get::<String, _>("key") == user_key {
+                    self.limits.max_failures
+                } else {
+                    self.limits.max_failures_per_client
+                };
+                retry_after(
+                    r.get("failures"),
+                    r.get("last_failure"),
+                    now,
+                    max_failures,
+                    self.limits.lockout,
+                )
+            })
+            .max()
+            .filter(|&wait| wait > 0);
+        if wait.is_none() {
+            // Failures older than a lockout are forgotten.
+            sqlx::query(
+                "update login_throttles set \
+                 failures = case when last_failure + $3 <= $2 then 1 else failures + 1 end, \
+                 last_failure = $2 \
+                 where key = any($1)",
+            )
+            .bind(&keys)
+            .bind(now)
+            .bind(self.limits.lockout)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(wait)
+    }
+
+    /// Records an admitted attempt that failed, which has already been counted.
+    pub(crate) async fn fail(&self, username: &str, client: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            "insert into failed_logins (username, client, attempted_at) values ($1, $2, $3)",
+        )
+        .bind(username)
+        .bind(client)
+        .bind(Timestamp::now().as_second())
+        .execute(&self.db)
+        .await?;
+
+        tracing::warn!(target: "audit", username, client, "failed login");
+        Ok(())
+    }
+
+    /// Takes back an admitted attempt that did not fail, such as a right password that still
+    /// awaits its second factor.
+    pub(crate) async fn forgive(&self, subject: &str, client: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            "update login_throttles set failures = greatest(failures - 1, 0) where key = any($1)",
+        )
+        .bind(self.keys(subject, client))
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Forgets the failures of a username once its owner logs in. Failures of the client are kept,
+    /// lest an attacker reset them by logging into an account of their own, but for the attempt
+    /// that succeeded.
+    pub(crate) async fn succeed(&self, username: &str, client: &str) -> sqlx::Result<()> {
+        self.forgive(username, client).await?;
+        sqlx::query("delete from login_throttles where key = $1")
+            .bind(format!("{}:{username}", self.scope))
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Forgets lockouts that are over, and failed logins older than `FAILED_LOGIN_RETENTION`, every
+/// `PRUNE_INTERVAL` for as long as the server runs.
+pub(crate) fn spawn_pruner(db: PgPool, limits: LoginLimits) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = prune(&db, limits).await {
+                tracing::error!(?e, "error in pruning login throttles");
+            }
+        }
+    });
+}
+
+async fn prune(db: &PgPool, limits: LoginLimits) -> sqlx::Result<()> {
+    let now = Timestamp::now().as_second();
+    sqlx::query("delete from login_throttles where last_failure + $1 <= $2")
+        .bind(limits.lockout)
+        .bind(now)
+        .execute(db)
+        .await?;
+    sqlx::query("delete from failed_logins where attempted_at <= $1")
+        .bind(now - FAILED_LOGIN_RETENTION)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_then_lockout() {
+        let wait = |failures| retry_after(failures, 100, 100, 5, 900);
+        assert_eq!([1, 2, 3, 4, 5].map(wait), [1, 2, 4, 8, 900]);
+        assert_eq!(retry_after(3, 100, 103, 5, 900), 1);
+        assert_eq!(retry_after(3, 100, 200, 5, 900), 0);
+    }
+
+    #[sqlx::test]
+    async fn concurrent_failures_are_throttled(db: PgPool) -> sqlx::Result<()> {
+        let throttle = Throttle::new(
+            db,
+            LoginLimits {
+                max_failures: 3,
+                max_failures_per_client: 10,
+                lockout: 900,
+            },
+        );
+        let attempts = (0..10).map(|_| async {
+            let admitted = throttle.admit("mallory", "192.0.2.1").await?.is_none();
+            if admitted {
+                throttle.fail("mallory", "192.0.2.1").await?;
+            }
+            Ok::<_, sqlx::Error>(admitted)
+        });
+        let admitted = futures_util::future::try_join_all(attempts).await?;
+        assert_eq!(admitted.iter().filter(|&&admitted| admitted).count(), 1);
+        assert!(throttle.admit("mallory", "192.0.2.2").await?.is_some());
+        Ok(())
+    }
+}
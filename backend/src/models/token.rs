@@ -0,0 +1,187 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool, Row, postgres::PgRow};
+use types::{
+    jiff::Timestamp,
+    token::{ApiToken, MintedToken, TokenRequest, TokenScope},
+};
+
+use crate::{
+    env::AppState,
+    models::{
+        Result,
+        audit::{self, Action, Origin},
+        authenticated::Context,
+        error::Error,
+        user::{AuthSession, User},
+    },
+};
+
+const TOKEN_PREFIX: &str = "zerra_";
+const MAX_TOKEN_NAME_LEN: usize = 64;
+
+fn digest(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+fn decode_scope(scope: &str) -> sqlx::Result<TokenScope> {
+    TokenScope::from_name(scope)
+        .ok_or_else(|| sqlx::Error::Decode(format!("unknown token scope {scope}").into()))
+}
+
+fn try_from_row(r: PgRow) -> sqlx::Result<ApiToken> {
+    let timestamp = |column| {
+        r.try_get::<Option<i64>, _>(column)?
+            .map(Timestamp::from_second)
+            .transpose()
+            .map_err(|e| sqlx::Error::Decode(e.into()))
+    };
+    Ok(ApiToken {
+        id: r.try_get("id")?,
+        name: r.try_get("name")?,
+        scope: decode_scope(r.try_get("scope")?)?,
+        created_at: timestamp("created_at")?.unwrap_or_default(),
+        last_used_at: timestamp("last_used_at")?,
+    })
+}
+
+/// Whoever is calling the API, through a login session or an `Authorization: Bearer` token.
+///
+/// Read-only tokens are refused for anything but safe requests, which includes WebSocket upgrades
+/// since those can edit or conduct.
+#[derive(Debug)]
+pub(crate) struct Caller {
+    pub(crate) user: Option<User>,
+}
+
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let bearer = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let Some(token) = bearer else {
+            let auth_session = AuthSession::from_request_parts(parts, state)
+                .await
+                .map_err(|(_, e)| Error::Unauthorized(e.into()))?;
+            return Ok(Caller {
+                user: auth_session.user,
+            });
+        };
+
+        let (user, scope) = authenticate(&state.db, token.trim()).await?;
+        let safe = parts.method.is_safe() && !parts.headers.contains_key(header::UPGRADE);
+        if scope == TokenScope::Read && !safe {
+            return Err(Error::Forbidden(format!(
+                "read-only token of user({}) cannot {} {}",
+                user.id,
+                parts.method,
+                parts.uri.path()
+            )));
+        }
+        Ok(Caller { user: Some(user) })
+    }
+}
+
+async fn authenticate(db: &PgPool, token: &str) -> Result<(User, TokenScope)> {
+    let row = sqlx::query(
+        "update api_tokens t set last_used_at = $2 from users \
+         where t.token_hash = $1 and users.id = t.user_id \
+         returning users.*, t.scope",
+    )
+    .bind(digest(token))
+    .bind(Timestamp::now().as_second())
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| Error::Unauthorized("unknown API token".into()))?;
+    Ok((User::from_row(&row)?, decode_scope(row.try_get("scope")?)?))
+}
+
+impl Context<'_> {
+    pub(crate) async fn tokens(self) -> Result<Vec<ApiToken>> {
+        Ok(sqlx::query(
+            "select id, name, scope, created_at, last_used_at from api_tokens \
+             where user_id = $1 order by created_at desc",
+        )
+        .bind(self.user)
+        .try_map(try_from_row)
+        .fetch_all(self.db)
+        .await?)
+    }
+
+    pub(crate) async fn mint_token(
+        self,
+        TokenRequest { name, scope }: TokenRequest,
+        origin: &Origin,
+    ) -> Result<MintedToken> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_TOKEN_NAME_LEN {
+            return Err(Error::BadRequest(format!(
+                "token name must be 1 to {MAX_TOKEN_NAME_LEN} bytes long"
+            )));
+        }
+
+        let token = format!(
+            "{TOKEN_PREFIX}{}",
+            URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+        );
+        let info = sqlx::query(
+            "insert into api_tokens (user_id, name, scope, token_hash, created_at) \
+             values ($1, $2, $3, $4, $5) \
+             returning id, name, scope, created_at, last_used_at",
+        )
+        .bind(self.user)
+        .bind(name)
+        .bind(scope.as_str())
+        .bind(digest(&token))
+        .bind(Timestamp::now().as_second())
+        .try_map(try_from_row)
+        .fetch_one(self.db)
+        .await?;
+
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::MintToken,
+            None,
+            Some(&format!(
+                "token({}) {} for {}",
+                info.id,
+                info.name,
+                scope.as_str()
+            )),
+        )
+        .await?;
+        Ok(MintedToken { token, info })
+    }
+
+    pub(crate) async fn revoke_token(self, token_id: i64, origin: &Origin) -> Result {
+        let revoked = sqlx::query("delete from api_tokens where id = $1 and user_id = $2")
+            .bind(token_id)
+            .bind(self.user)
+            .execute(self.db)
+            .await?;
+        if revoked.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("token({token_id})")));
+        }
+
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::RevokeToken,
+            None,
+            Some(&format!("token({token_id})")),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use qrcode::{QrCode, render::svg};
+use sqlx::{PgPool, Row};
+use tokio::task;
+use totp_rs::{Algorithm, TOTP};
+use types::{
+    jiff::Timestamp,
+    totp::{TotpEnrolment, TotpStatus},
+};
+
+use crate::models::{
+    Result,
+    audit::{self, Action, Origin},
+    authenticated::Context,
+    error::Error,
+    recovery::random_code,
+};
+
+const ISSUER: &str = "Zerra";
+const STEP: u64 = 30;
+const BACKUP_CODES: usize = 10;
+
+/// Parameters understood by every common authenticator app. Clock skew is allowed for by
+/// `matching_step` rather than by `TOTP` itself, so that the accepted step is known.
+fn totp(secret: Vec<u8>, username: &str) -> TOTP {
+    TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP,
+        secret,
+        Some(ISSUER.to_string()),
+        username.to_string(),
+    )
+}
+
+/// The time step, one step either side of `now`, for which `code` is valid.
+fn matching_step(totp: &TOTP, code: &str, now: u64) -> Option<u64> {
+    let current = now / STEP;
+    (current.saturating_sub(1)..=current + 1).find(|step| totp.check(code, step * STEP))
+}
+
+fn now() -> u64 {
+    Timestamp::now().as_second().try_into().unwrap_or_default()
+}
+
+impl Context<'_> {
+    pub(crate) async fn totp_status(self) -> Result<TotpStatus> {
+        let row = sqlx::query(
+            "select coalesce((select enabled from totp where user_id = $1), false) as enabled, \
+             (select count(1) from backup_codes where user_id = $1) as backup_codes_left",
+        )
+        .bind(self.user)
+        .fetch_one(self.db)
+        .await?;
+        Ok(TotpStatus {
+            enabled: row.get("enabled"),
+            backup_codes_left: row
+                .get::<i64, _>("backup_codes_left")
+                .try_into()
+                .unwrap_or(0),
+        })
+    }
+
+    /// Generates a new secret, replacing any earlier one that was never confirmed.
+    pub(crate) async fn enrol_totp(self, username: &str) -> Result<TotpEnrolment> {
+        let secret = rand::random::<[u8; 20]>().to_vec();
+        let enrolled = sqlx::query(
+            "insert into totp (user_id, secret) values ($1, $2) \
+             on conflict (user_id) do update set secret = excluded.secret, last_step = null \
+             where not totp.enabled",
+        )
+        .bind(self.user)
+        .bind(&secret)
+        .execute(self.db)
+        .await?;
+        if enrolled.rows_affected() == 0 {
+            return Err(Error::Conflict(format!(
+                "user({}) already has a second factor",
+                self.user
+            )));
+        }
+
+        let totp = totp(secret, username);
+        let url = totp.get_url();
+        let qr_svg = QrCode::new(&url)
+            .map_err(|e| Error::BadRequest(format!("cannot encode {url} as QR: {e}")))?
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+        Ok(TotpEnrolment {
+            secret: totp.get_secret_base32(),
+            url,
+            qr_svg,
+        })
+    }
+
+    /// Enables the enrolled secret once `code` proves the authenticator has it, returning a fresh
+    /// set of backup codes.
+    pub(crate) async fn confirm_totp(self, code: &str, origin: &Origin) -> Result<Vec<String>> {
+        let secret: Vec<u8> =
+            sqlx::query("select secret from totp where user_id = $1 and not enabled")
+                .bind(self.user)
+                .fetch_optional(self.db)
+                .await?
+                .map(|r| r.get(0))
+                .ok_or_else(|| Error::NotFound("TOTP enrolment".into()))?;
+        let step = matching_step(&totp(secret, ""), code.trim(), now())
+            .ok_or_else(|| Error::BadRequest("wrong TOTP code".into()))?;
+
+        let codes: Vec<_> = (0..BACKUP_CODES).map(|_| random_code()).collect();
+        let hashes = {
+            let codes = codes.clone();
+            task::spawn_blocking(move || {
+                codes
+                    .iter()
+                    .map(password_auth::generate_hash)
+                    .collect::<Vec<_>>()
+            })
+            .await?
+        };
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("update totp set enabled = true, last_step = $2 where user_id = $1")
+            .bind(self.user)
+            .bind(step as i64)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("delete from backup_codes where user_id = $1")
+            .bind(self.user)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("insert into backup_codes (user_id, code_hash) select $1, unnest($2::text[])")
+            .bind(self.user)
+            .bind(&hashes)
+            .execute(&mut *tx)
+            .await?;
+        audit::record(
+            &mut *tx,
+            origin,
+            Some(self.user),
+            Action::EnableTotp,
+            None,
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(codes)
+    }
+
+    pub(crate) async fn disable_totp(self, code: &str, origin: &Origin) -> Result {
+        if !verify(self.db, self.user, code, origin).await? {
+            return Err(Error::Unauthorized(format!(
+                "wrong second factor for user({})",
+                self.user
+            )));
+        }
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("delete from totp where user_id = $1")
+            .bind(self.user)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("delete from backup_codes where user_id = $1")
+            .bind(self.user)
+            .execute(&mut *tx)
+            .await?;
+        audit::record(
+            &mut *tx,
+            origin,
+            Some(self.user),
+            Action::DisableTotp,
+            None,
+            None,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+pub(crate) async fn enabled_for(db: &PgPool, user_id: i64) -> sqlx::Result<bool> {
+    Ok(
+        sqlx::query("select 1 from totp where user_id = $1 and enabled")
+            .bind(user_id)
+            .fetch_optional(db)
+            .await?
+            .is_some(),
+    )
+}
+
+/// Checks a TOTP code, or else consumes a matching backup code, which is recorded in the audit log.
+pub(crate) async fn verify(db: &PgPool, user_id: i64, code: &str, origin: &Origin) -> Result<bool> {
+    let code = code.trim().to_uppercase();
+
+    let secret: Option<Vec<u8>> =
+        sqlx::query("select secret from totp where user_id = $1 and enabled")
+            .bind(user_id)
+            .fetch_optional(db)
+            .await?
+            .map(|r| r.get(0));
+    let Some(secret) = secret else {
+        return Ok(false);
+    };
+
+    if let Some(step) = matching_step(&totp(secret, ""), &code, now()) {
+        // Accepting only later steps keeps an observed code from being replayed.
+        let accepted = sqlx::query(
+            "update totp set last_step = $2 \
+             where user_id = $1 and (last_step is null or last_step < $2)",
+        )
+        .bind(user_id)
+        .bind(step as i64)
+        .execute(db)
+        .await?;
+        return Ok(accepted.rows_affected() != 0);
+    }
+
+    let hashes: Vec<String> = sqlx::query("select code_hash from backup_codes where user_id = $1")
+        .bind(user_id)
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|r| r.get(0))
+        .collect();
+    let matched = task::spawn_blocking(move || {
+        hashes
+            .into_iter()
+            .find(|hash| password_auth::verify_password(&code, hash).is_ok())
+    })
+    .await?;
+    let Some(matched) = matched else {
+        return Ok(false);
+    };
+
+    let mut tx = db.begin().await?;
+    let consumed = sqlx::query("delete from backup_codes where user_id = $1 and code_hash = $2")
+        .bind(user_id)
+        .bind(matched)
+        .execute(&mut *tx)
+        .await?;
+    if consumed.rows_affected() == 0 {
+        return Ok(false);
+    }
+    audit::record(
+        &mut *tx,
+        origin,
+        Some(user_id),
+        Action::UseBackupCode,
+        None,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA1 secret of RFC 6238's test vectors.
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn rfc_6238_vectors() {
+        let totp = totp(SECRET.to_vec(), "tester");
+        assert_eq!(totp.generate(59), "287082");
+        assert_eq!(totp.generate(1_111_111_109), "081804");
+    }
+
+    #[test]
+    fn allows_one_step_of_skew() {
+        let totp = totp(SECRET.to_vec(), "tester");
+        let step = 1_111_111_109 / STEP;
+        assert_eq!(matching_step(&totp, "081804", 1_111_111_109), Some(step));
+        assert_eq!(
+            matching_step(&totp, "081804", 1_111_111_109 + STEP),
+            Some(step)
+        );
+        assert_eq!(
+            matching_step(&totp, "081804", 1_111_111_109 - STEP),
+            Some(step)
+        );
+        assert_eq!(
+            matching_step(&totp, "081804", 1_111_111_109 + 2 * STEP),
+            None
+        );
+    }
+}
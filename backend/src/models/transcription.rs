@@ -1,15 +1,32 @@
-use std::{borrow::Cow, iter};
+use std::{borrow::Cow, iter, time::Instant};
 
-use crate::{env::I18n, models};
+use types::zerra::{Avatar, Persona};
+
+use crate::{
+    env::I18n,
+    models::{self, error::Error, unauthenticated::AVATAR_MIMES},
+    utils::typst_str,
+};
 
 pub(crate) async fn transcribe(
+    db: &sqlx::PgPool,
+    zerra_id: &str,
+    i18n: &I18n,
+) -> models::Result<impl axum::response::IntoResponse + use<>> {
+    let pdf = render(db, zerra_id, i18n).await?;
+    Ok(axum_extra::response::Attachment::new(pdf).content_type("application/pdf"))
+}
+
+/// Typesets the transcript of a finished zerra as PDF.
+pub(crate) async fn render(
     db: &sqlx::PgPool,
     zerra_id: &str,
     I18n {
         index_format,
         page_number_format,
+        ..
     }: &I18n,
-) -> models::Result<impl axum::response::IntoResponse + use<>> {
+) -> models::Result<Vec<u8>> {
     let blob = models::unauthenticated::fetch_blob(db, zerra_id).await?;
 
     let types::zerra::Zerra {
@@ -19,16 +36,16 @@ pub(crate) async fn transcribe(
         ..
     } = rmp_serde::from_slice(&blob)?;
 
-    let models::user::User {
-        display_name: conductor_name,
-        is_male: conductor_is_male,
-        ..
-    } = models::unauthenticated::owner_of(db, zerra_id).await?;
+    let conductor = models::unauthenticated::conductor_of(db, zerra_id).await?;
+    let conductor_persona = models::unauthenticated::persona_of(db, &conductor).await?;
+    let conductor_name = conductor.display_name;
     let types::zerra::Progress::Finished {
-        participant_name, ..
+        participant_name,
+        participant_persona,
+        ..
     } = progress
     else {
-        unreachable!()
+        return Err(Error::NotFound(format!("zerra {zerra_id} is not finished")));
     };
 
     fn flow_to_array(flow: &[types::zerra::Query]) -> String {
@@ -50,23 +67,53 @@ pub(crate) async fn transcribe(
     ) -> Option<String> {
         visible.then(move || {
             format!(
-                r#"(question: "{question}", answer: "{answer}", revelation: "{revelation}", subflow: {subflow}),"#,
-                answer = answer.as_deref().unwrap_or_default(),
+                r#"(question: {question}, answer: {answer}, revelation: {revelation}, subflow: {subflow}),"#,
+                question = typst_str(question),
+                answer = typst_str(answer.as_deref().unwrap_or_default()),
+                revelation = typst_str(revelation),
                 subflow = flow_to_array(subflow),
             )
         })
     }
 
+    fn pronouns_to_str(Persona { pronouns, .. }: &Persona) -> String {
+        pronouns
+            .as_deref()
+            .map_or_else(|| "none".to_string(), typst_str)
+    }
+
+    fn avatar_to_ico(Persona { avatar, .. }: &Persona) -> Cow<'static, str> {
+        match avatar {
+            Avatar::Man => Cow::Borrowed("icoMan"),
+            Avatar::Woman => Cow::Borrowed("icoWoman"),
+            // Formats Typst cannot decode would fail the whole transcript.
+            Avatar::Image { mime, data }
+                if !data.is_empty() && AVATAR_MIMES.contains(&mime.as_str()) =>
+            {
+                Cow::Owned(format!(
+                    "ico(bytes(({})))",
+                    data.iter().map(|b| format!("{b},")).collect::<String>()
+                ))
+            }
+            _ => Cow::Borrowed("icoNeutral"),
+        }
+    }
+
     let flow_array = flow_to_array(&flow);
+    let conductor_pronouns = pronouns_to_str(&conductor_persona);
+    let attendee_pronouns = pronouns_to_str(&participant_persona);
+    let conductor_avatar = avatar_to_ico(&conductor_persona);
+    let attendee_avatar = avatar_to_ico(&participant_persona);
 
     let typst_doc = format!(
         r#"
-#let zerra_title = "{title}"
+#let zerra_title = {title}
 
-#let conductor_name = "{conductor_name}"
-#let attendee_name = "{participant_name}"
+#let conductor_name = {conductor_name}
+#let attendee_name = {participant_name}
 
-#let conductor_is_male = {conductor_is_male}
+#let conductor_pronouns = {conductor_pronouns}
+#let attendee_pronouns = {attendee_pronouns}
 
 #let flow = {flow_array}
 
@@ -78,40 +125,50 @@ pub(crate) async fn transcribe(
 #show heading.where(level: 1): set align(center)
 #set page(paper: "a5", numbering: page_number_format)
 
-#let icoMan = box(
-  image(
-    bytes(
-      `<?xml version="1.0" encoding="utf-8"?>
+#let ico(source) = box(image(source, height: 1em), baseline: 0.125em)
+
+#let icoMan = ico(
+  bytes(
+    `<?xml version="1.0" encoding="utf-8"?>
 <svg viewBox="0 0 36 36" xmlns="http://www.w3.org/2000/svg">
     <path fill="black"
         d="M -12 16 a 12 9 0 0 1 24 0 a 12 13 0 0 1 -24 0 Z M 12 16 a 12 15 0 0 0 -24 0 a 12 19 0 0 0 24 -0 Z"
         transform="translate(18)">
     </path>
 </svg>`.text,
-    ),
-    height: 1em,
   ),
-  baseline: 0.125em,
 )
 
-#let icoWoman = box(
-  image(
-    bytes(
-      `<?xml version="1.0" encoding="utf-8"?>
+#let icoWoman = ico(
+  bytes(
+    `<?xml version="1.0" encoding="utf-8"?>
 <svg viewBox="0 0 36 36" xmlns="http://www.w3.org/2000/svg">
     <path fill="black"
         d="M -9 15 a 10.5 10.5 0 0 1 18 0 a 15 15 0 0 1 -18 0 Z M -12 20 a 12 15 0 0 0 24 0 a 12 19 0 0 0 -24 0 Z"
         transform="translate(18)">
     </path>
 </svg>`.text,
-    ),
-    height: 1em,
   ),
-  baseline: 0.125em,
 )
 
-#let icoConductor = if conductor_is_male {{ icoMan }} else {{ icoWoman }}
-#let icoAttendee = if not conductor_is_male {{ icoMan }} else {{ icoWoman }}
+#let icoNeutral = ico(
+  bytes(
+    `<?xml version="1.0" encoding="utf-8"?>
+<svg viewBox="0 0 36 36" xmlns="http://www.w3.org/2000/svg">
+    <path fill="black"
+        d="M -8 10 a 8 8 0 0 1 16 0 a 8 8 0 0 1 -16 0 Z M -13 34 a 13 14 0 0 1 26 0 Z"
+        transform="translate(18)">
+    </path>
+</svg>`.text,
+  ),
+)
+
+#let icoConductor = {conductor_avatar}
+#let icoAttendee = {attendee_avatar}
+
+#let withPronouns(name, pronouns) = if pronouns == none [#name] else [
+  #name #text(size: 0.8em, style: "italic")[(#pronouns)]
+]
 
 #let question_index = counter("question_index")
 #let dotty = tiling(size: (2pt, 2pt), relative: "parent", place(dx: 0.25pt, dy: 0.25pt, circle(
@@ -145,17 +202,24 @@ pub(crate) async fn transcribe(
   columns: (1fr, 2fr, 1fr, 2fr, 1fr),
   [],
   [
-    #icoConductor #conductor_name],
+    #icoConductor #withPronouns(conductor_name, conductor_pronouns)],
   [],
-  align(end)[#icoAttendee #attendee_name],
+  align(end)[#icoAttendee #withPronouns(attendee_name, attendee_pronouns)],
   [],
 )
 
 #defFlow(1, flow)
-"#
+"#,
+        title = typst_str(&title),
+        conductor_name = typst_str(&conductor_name),
+        participant_name = typst_str(&participant_name),
     );
     let compiler = tokape::TypstCompiler::new()?;
-    let pdf = compiler.compile_to_pdf(typst_doc)?;
-
-    Ok(axum_extra::response::Attachment::new(pdf).content_type("application/pdf"))
+    let started = Instant::now();
+    let pdf = compiler.compile_to_pdf(typst_doc);
+    metrics::histogram!("zerra_typst_compile_seconds").record(started.elapsed());
+    if pdf.is_err() {
+        metrics::counter!("zerra_typst_failures_total").increment(1);
+    }
+    Ok(pdf?)
 }
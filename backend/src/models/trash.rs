@@ -0,0 +1,292 @@
+use std::{sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use sqlx::{PgPool, Row};
+use types::{TrashItem, jiff::Timestamp};
+
+use crate::models::{
+    Result,
+    audit::{self, Action, Origin},
+    authenticated::Context,
+    collaboration::Edition,
+    conduction::Conduction,
+    error::Error,
+};
+
+/// How often the trash is checked for zerrae past their retention.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+impl Context<'_> {
+    /// Moves a zerra of the user to their trash. Its editors are closed, but a live zerra must be
+    /// let go first.
+    ///
+    /// A trashed zerra can no longer be attended, as its blob is not fetched anymore, so a
+    /// conduction can only slip in while it is being trashed. That is checked again before the
+    /// zerra is let go, which leaves only an attendee who fetched the blob just before and joins
+    /// just after. Such a conduction carries on, its answers saved in the trash.
+    pub(crate) async fn delete(
+        self,
+        id: &str,
+        origin: &Origin,
+        under_conduction: &DashMap<String, Conduction>,
+        under_edition: &DashMap<String, Arc<Edition>>,
+    ) -> Result {
+        let live = || Error::Conflict(format!("zerra {id} is live"));
+        if under_conduction.contains_key(id) {
+            return Err(live());
+        }
+        let mut tx = self.db.begin().await?;
+        let trashed = sqlx::query(
+            "update vus set deleted_at = $1 \
+             where owner = $2 and id = $3 and deleted_at is null",
+        )
+        .bind(Timestamp::now().as_second())
+        .bind(self.user)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        if trashed.rows_affected() == 0 {
+            return Err(Error::NotFound(format!(
+                "zerra {id} of user({})",
+                self.user
+            )));
+        }
+        if under_conduction.contains_key(id) {
+            return Err(live());
+        }
+        tx.commit().await?;
+        Edition::evict(under_edition, id);
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::TrashZerra,
+            Some(id),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// The zerrae in the user's trash, most recently deleted first. `retention` is in seconds.
+    pub(crate) async fn trash(self, retention: i64) -> Result<Vec<TrashItem>> {
+        #[derive(serde::Deserialize)]
+        struct Metadata {
+            title: String,
+        }
+
+        let timestamp =
+            |second| Timestamp::from_second(second).map_err(|e| sqlx::Error::Decode(e.into()));
+        Ok(sqlx::query(
+            "select id, data, deleted_at from vus \
+             where owner = $1 and deleted_at is not null order by deleted_at desc",
+        )
+        .bind(self.user)
+        .try_map(|r: sqlx::postgres::PgRow| {
+            let deleted_at: i64 = r.try_get("deleted_at")?;
+            let metadata: Metadata = rmp_serde::from_slice(r.try_get("data")?)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?;
+            Ok(TrashItem {
+                id: r.try_get("id")?,
+                name: metadata.title,
+                deleted_at: timestamp(deleted_at)?,
+                purge_at: timestamp(deleted_at + retention)?,
+            })
+        })
+        .fetch_all(self.db)
+        .await?)
+    }
+
+    pub(crate) async fn restore(self, id: &str, origin: &Origin) -> Result {
+        let restored = sqlx::query(
+            "update vus set deleted_at = null \
+             where owner = $1 and id = $2 and deleted_at is not null",
+        )
+        .bind(self.user)
+        .bind(id)
+        .execute(self.db)
+        .await?;
+        if restored.rows_affected() == 0 {
+            return Err(Error::NotFound(format!(
+                "zerra {id} in the trash of user({})",
+                self.user
+            )));
+        }
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::RestoreZerra,
+            Some(id),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes a zerra in the user's trash for good, without waiting for its retention to end.
+    pub(crate) async fn purge(self, id: &str, origin: &Origin) -> Result {
+        let purged =
+            sqlx::query("delete from vus where owner = $1 and id = $2 and deleted_at is not null")
+                .bind(self.user)
+                .bind(id)
+                .execute(self.db)
+                .await?;
+        if purged.rows_affected() == 0 {
+            return Err(Error::NotFound(format!(
+                "zerra {id} in the trash of user({})",
+                self.user
+            )));
+        }
+        audit::record(
+            self.db,
+            origin,
+            Some(self.user),
+            Action::DeleteZerra,
+            Some(id),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Deletes for good every zerra that has been in the trash for longer than `retention` seconds.
+/// A zerra that cannot be purged is left for the next time, without holding up the others.
+async fn purge_expired(db: &PgPool, retention: i64) -> sqlx::Result<()> {
+    let cutoff = Timestamp::now().as_second() - retention;
+    let expired: Vec<String> = sqlx::query_scalar("select id from vus where deleted_at <= $1")
+        .bind(cutoff)
+        .fetch_all(db)
+        .await?;
+    for id in expired {
+        if let Err(e) = purge_one(db, &id, cutoff).await {
+            tracing::error!(?e, id, "error in purging a zerra");
+        }
+    }
+    Ok(())
+}
+
+/// Deletes a zerra and records it in the audit log, neither without the other.
+async fn purge_one(db: &PgPool, id: &str, cutoff: i64) -> sqlx::Result<()> {
+    let mut tx = db.begin().await?;
+    let owner: Option<i64> =
+        sqlx::query_scalar("delete from vus where id = $1 and deleted_at <= $2 returning owner")
+            .bind(id)
+            .bind(cutoff)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if let Some(owner) = owner {
+        audit::record(
+            &mut *tx,
+            &Origin::server(),
+            Some(owner),
+            Action::DeleteZerra,
+            Some(id),
+            Some("retention ended"),
+        )
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Purges the trash every `PURGE_INTERVAL`, for as long as the server runs.
+pub(crate) fn spawn_purger(db: PgPool, retention: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_expired(&db, retention).await {
+                tracing::error!(?e, "error in purging the trash");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn zerra(db: &PgPool, id: &str) -> sqlx::Result<()> {
+        let blob = rmp_serde::to_vec_named(&types::zerra::Zerra {
+            id: id.to_string(),
+            title: id.to_string(),
+            progress: types::zerra::Progress::None,
+            flow: Vec::new(),
+        })
+        .unwrap();
+        sqlx::query("insert into vus (id, owner, data, last_modified) values ($1, 1, $2, 0)")
+            .bind(id)
+            .bind(blob)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn trash_and_restore(db: PgPool) -> Result {
+        zerra(&db, "kept").await?;
+        let ctx = Context { db: &db, user: 1 };
+        let origin = Origin::server();
+        ctx.delete("kept", &origin, &DashMap::new(), &DashMap::new())
+            .await?;
+        assert!(
+            crate::models::unauthenticated::fetch_blob(&db, "kept")
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            ctx.trash(60)
+                .await?
+                .iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>(),
+            ["kept"]
+        );
+        assert!(matches!(
+            ctx.delete("kept", &origin, &DashMap::new(), &DashMap::new())
+                .await,
+            Err(Error::NotFound(_))
+        ));
+
+        ctx.restore("kept", &origin).await?;
+        assert!(ctx.trash(60).await?.is_empty());
+        crate::models::unauthenticated::fetch_blob(&db, "kept").await?;
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn purge_after_retention(db: PgPool) -> Result {
+        zerra(&db, "old").await?;
+        zerra(&db, "recent").await?;
+        zerra(&db, "purged").await?;
+        let now = Timestamp::now().as_second();
+        sqlx::query("update vus set deleted_at = case id when 'old' then $1 else $2 end")
+            .bind(now - 120)
+            .bind(now)
+            .execute(&db)
+            .await?;
+
+        let ctx = Context { db: &db, user: 1 };
+        ctx.purge("purged", &Origin::server()).await?;
+        purge_expired(&db, 60).await?;
+
+        let left: Vec<String> = sqlx::query_scalar("select id from vus order by id")
+            .fetch_all(&db)
+            .await?;
+        assert_eq!(left, ["recent"]);
+        let audited: Vec<(String, Option<String>)> = sqlx::query_as(
+            "select zerra_id, detail from audit_log where action = 'delete_zerra' order by zerra_id",
+        )
+        .fetch_all(&db)
+        .await?;
+        assert_eq!(
+            audited,
+            [
+                ("old".to_string(), Some("retention ended".to_string())),
+                ("purged".to_string(), None),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,107 @@
+use std::time::Instant;
+
 use sqlx::{PgPool, Row};
+use types::{
+    ZerraStatus,
+    zerra::{Avatar, Persona, Progress, Query, Zerra},
+};
 
-pub(crate) async fn owner_of(db: &PgPool, id: &str) -> sqlx::Result<crate::models::user::User> {
-    sqlx::query_as("select users.* from vus join users on vus.owner = users.id where vus.id = $1")
-        .bind(id)
-        .fetch_one(db)
-        .await
+use crate::models::{self, user::User};
+
+const MAX_PRONOUNS_LEN: usize = 32;
+const MAX_AVATAR_SIZE: usize = 256 * 1024;
+/// Image formats Typst can decode, and so can be put in transcripts.
+pub(crate) const AVATAR_MIMES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/svg+xml"];
+
+/// The part of a zerra blob that concerns its attendance.
+#[derive(serde::Deserialize)]
+pub(crate) struct Metadata {
+    pub(crate) progress: Progress,
+}
+
+/// What the list of zerrae shows of one, kept in columns beside its blob so that the list can be
+/// filtered, sorted and paged by the database.
+#[derive(Debug)]
+pub(crate) struct Summary {
+    pub(crate) title: String,
+    pub(crate) status: &'static str,
+    /// Who attended the zerra, once they have introduced themselves.
+    pub(crate) participant: Option<String>,
+}
+
+impl Summary {
+    pub(crate) fn of(zerra: &Zerra) -> Self {
+        Self::new(zerra.title.clone(), &zerra.progress)
+    }
+
+    /// Summarises a blob without keeping its flow.
+    pub(crate) fn of_blob(blob: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        #[derive(serde::Deserialize)]
+        struct Head {
+            title: String,
+            progress: Progress,
+        }
+        let Head { title, progress } = rmp_serde::from_slice(blob)?;
+        Ok(Self::new(title, &progress))
+    }
+
+    fn new(title: String, progress: &Progress) -> Self {
+        let participant = match progress {
+            Progress::Ongoing {
+                participant_name, ..
+            }
+            | Progress::Finished {
+                participant_name, ..
+            } => Some(participant_name.clone()),
+            Progress::None | Progress::Intro { .. } => None,
+        };
+        Summary {
+            title,
+            status: ZerraStatus::from(progress).as_str(),
+            participant,
+        }
+    }
+}
+
+/// Summarises the zerrae written before their summaries were kept.
+pub(crate) async fn summarize_all(db: &PgPool) -> sqlx::Result<()> {
+    let unsummarized = sqlx::query("select id, data from vus where status is null")
+        .fetch_all(db)
+        .await?;
+    for row in unsummarized {
+        let id: String = row.get("id");
+        let summary = match Summary::of_blob(row.get("data")) {
+            Ok(summary) => summary,
+            Err(e) => {
+                tracing::warn!(%e, "cannot summarise zerra {id}");
+                continue;
+            }
+        };
+        sqlx::query("update vus set title = $1, status = $2, participant = $3 where id = $4")
+            .bind(summary.title)
+            .bind(summary.status)
+            .bind(summary.participant)
+            .bind(&id)
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Who conducted a zerra, falling back to its owner if it was never conducted.
+pub(crate) async fn conductor_of(db: &PgPool, id: &str) -> sqlx::Result<User> {
+    sqlx::query_as(
+        "select users.* from vus \
+         join users on coalesce(vus.conducted_by, vus.owner) = users.id \
+         where vus.id = $1",
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await
 }
 
 pub(crate) async fn fetch_blob(db: &PgPool, zerra_id: &str) -> sqlx::Result<Vec<u8>> {
-    sqlx::query("select data from vus where id = $1")
+    sqlx::query("select data from vus where id = $1 and deleted_at is null")
         .bind(zerra_id)
         .fetch_one(db)
         .await
@@ -16,11 +109,111 @@ pub(crate) async fn fetch_blob(db: &PgPool, zerra_id: &str) -> sqlx::Result<Vec<
 }
 
 pub(crate) async fn update_blob(db: &PgPool, zerra_id: &str, blob: &[u8]) -> sqlx::Result<()> {
-    sqlx::query("update vus set data = $1, last_modified = $2 where id = $3")
-        .bind(blob)
-        .bind(types::jiff::Timestamp::now().as_second())
+    let started = Instant::now();
+    // A blob that cannot be summarised keeps the summary the zerra had.
+    let summary = Summary::of_blob(blob).ok();
+    sqlx::query(
+        "update vus set data = $1, last_modified = $2, \
+         title = coalesce($4, title), status = coalesce($5, status), \
+         participant = case when $5::text is null then participant else $6 end \
+         where id = $3",
+    )
+    .bind(blob)
+    .bind(types::jiff::Timestamp::now().as_second())
+    .bind(zerra_id)
+    .bind(summary.as_ref().map(|s| &s.title))
+    .bind(summary.as_ref().map(|s| s.status))
+    .bind(summary.as_ref().and_then(|s| s.participant.as_deref()))
+    .execute(db)
+    .await?;
+    metrics::histogram!("zerra_update_blob_seconds").record(started.elapsed());
+    Ok(())
+}
+
+/// Takes the answers and comments out of a flow, leaving its script.
+pub(crate) fn clear_answers(flow: &mut [Query]) {
+    for query in flow {
+        query.answer = None;
+        query.comment = None;
+        clear_answers(&mut query.subflow);
+    }
+}
+
+/// Forgets the attendee of a zerra and their answers, so that it can be attended afresh.
+pub(crate) async fn reset_progress(db: &PgPool, zerra_id: &str) -> models::Result {
+    let mut zerra: Zerra = rmp_serde::from_slice(&fetch_blob(db, zerra_id).await?)?;
+    zerra.progress = Progress::None;
+    clear_answers(&mut zerra.flow);
+
+    let summary = Summary::of(&zerra);
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        "update vus set data = $1, last_modified = $2, \
+         title = $4, status = $5, participant = $6 where id = $3",
+    )
+    .bind(rmp_serde::to_vec_named(&zerra)?)
+    .bind(types::jiff::Timestamp::now().as_second())
+    .bind(zerra_id)
+    .bind(summary.title)
+    .bind(summary.status)
+    .bind(summary.participant)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("delete from recovery_codes where zerra_id = $1")
         .bind(zerra_id)
-        .execute(db)
+        .execute(&mut *tx)
         .await?;
+    sqlx::query("update invitations set claimed_by = null where zerra_id = $1")
+        .bind(zerra_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
     Ok(())
 }
+
+/// Checks a persona the way the client does, since attendees introduce themselves through the
+/// conductor's blob rather than through their own account.
+pub(crate) fn check_persona(Persona { pronouns, avatar }: &Persona) -> Result<(), String> {
+    if pronouns
+        .as_ref()
+        .is_some_and(|p| p.chars().count() > MAX_PRONOUNS_LEN)
+    {
+        return Err(format!(
+            "pronouns must be at most {MAX_PRONOUNS_LEN} characters"
+        ));
+    }
+    if let Avatar::Image { mime, data } = avatar {
+        if !AVATAR_MIMES.contains(&mime.as_str()) {
+            return Err(format!(
+                "{mime} is not one of the image formats {}",
+                AVATAR_MIMES.join(", ")
+            ));
+        }
+        if data.is_empty() || data.len() > MAX_AVATAR_SIZE {
+            return Err(format!(
+                "avatar image must be between 1 and {MAX_AVATAR_SIZE} bytes"
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn persona_of(db: &PgPool, user: &User) -> sqlx::Result<Persona> {
+    let avatar = match user.avatar.as_str() {
+        "man" => Avatar::Man,
+        "woman" => Avatar::Woman,
+        "image" => sqlx::query("select mime, data from avatars where user_id = $1")
+            .bind(user.id)
+            .fetch_optional(db)
+            .await?
+            .map_or(Avatar::Neutral, |r| Avatar::Image {
+                mime: r.get(0),
+                data: r.get(1),
+            }),
+        _ => Avatar::Neutral,
+    };
+    Ok(Persona {
+        pronouns: user.pronouns.clone(),
+        avatar,
+    })
+}
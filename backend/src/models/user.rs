@@ -8,7 +8,8 @@ pub(crate) struct User {
     pub(crate) id: i64,
     pub(crate) username: String,
     pub(crate) display_name: String,
-    pub(crate) is_male: bool,
+    pub(crate) pronouns: Option<String>,
+    pub(crate) avatar: String,
     auth_hash: String,
 }
 
@@ -18,7 +19,8 @@ impl std::fmt::Debug for User {
             .field("id", &self.id)
             .field("username", &self.username)
             .field("display_name", &self.display_name)
-            .field("is_male", &self.is_male)
+            .field("pronouns", &self.pronouns)
+            .field("avatar", &self.avatar)
             .finish_non_exhaustive()
     }
 }
@@ -97,3 +99,40 @@ impl AuthnBackend for AuthBackend {
 }
 
 pub(crate) type AuthSession = axum_login::AuthSession<AuthBackend>;
+
+/// Creates a user with the given password, returning their ID.
+pub(crate) async fn create(
+    db: &PgPool,
+    username: &str,
+    display_name: &str,
+    password: String,
+) -> Result<i64, Error> {
+    let auth_hash = task::spawn_blocking(move || password_auth::generate_hash(password)).await?;
+    let id = sqlx::query(
+        "insert into users (id, username, auth_hash, display_name) \
+         select coalesce(max(id), 0) + 1, $1, $2, $3 from users returning id",
+    )
+    .bind(username)
+    .bind(auth_hash)
+    .bind(display_name)
+    .fetch_one(db)
+    .await?
+    .get(0);
+    Ok(id)
+}
+
+/// Replaces the password of a user, which also ends all of their sessions. Returns whether the
+/// user exists.
+pub(crate) async fn set_password(
+    db: &PgPool,
+    username: &str,
+    password: String,
+) -> Result<bool, Error> {
+    let auth_hash = task::spawn_blocking(move || password_auth::generate_hash(password)).await?;
+    let updated = sqlx::query("update users set auth_hash = $2 where username = $1")
+        .bind(username)
+        .bind(auth_hash)
+        .execute(db)
+        .await?;
+    Ok(updated.rows_affected() != 0)
+}
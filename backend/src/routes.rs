@@ -1,49 +1,94 @@
 use std::time::Duration as StdDuration;
 
 use axum::{
-    Router,
-    extract::Request,
+    Extension, Router,
+    extract::{Request, State},
+    http::header,
     middleware,
-    routing::{any, get_service},
+    response::Response,
+    routing::{get, get_service},
 };
 use axum_extra::extract::CookieJar;
 use axum_login::AuthManagerLayerBuilder;
 use tower_http::{compression::CompressionLayer, timeout::TimeoutLayer, trace::TraceLayer};
 use tower_sessions::{
-    Expiry, SessionManagerLayer,
-    cookie::{Cookie, Key, time},
+    ExpiredDeletion, Expiry, SessionManagerLayer,
+    cookie::{Cookie, Key},
 };
-use tower_sessions_moka_store::MokaStore;
+use tower_sessions_sqlx_store::PostgresStore;
 
-use crate::{env, models::user::AuthBackend, utils::after_a_month};
+use crate::{
+    env,
+    models::{
+        Result,
+        audit::TrustedProxies,
+        error::Error,
+        throttle::{self, Throttle},
+        trash,
+        user::AuthBackend,
+    },
+    telemetry,
+};
 
 pub(crate) mod api;
 pub(crate) mod auth;
+pub(crate) mod health;
+
+/// Starts the tasks that tidy the database for as long as the server runs. Each `main` calls it
+/// once, after building its router, which creates the tables of the sessions.
+pub fn spawn_housekeeping(state: &env::AppState) {
+    tokio::spawn(
+        PostgresStore::new(state.db.clone())
+            .continuously_delete_expired(StdDuration::from_secs(60 * 60)),
+    );
+    trash::spawn_purger(state.db.clone(), state.config.trash.retention());
+    throttle::spawn_pruner(state.db.clone(), state.config.login.limits());
+}
 
-pub(crate) async fn router(state: env::AppState) -> anyhow::Result<Router> {
-    let session_layer = SessionManagerLayer::new(MokaStore::new(Some(1024)))
-        .with_secure(false)
-        .with_expiry(Expiry::OnInactivity(time::Duration::days(1)))
-        .with_signed(Key::generate());
+pub async fn router(state: env::AppState, session_key: Key) -> anyhow::Result<Router> {
+    let config = state.config.clone();
+    telemetry::install()?;
+    let session_store = PostgresStore::new(state.db.clone());
+    session_store.migrate().await?;
+
+    let session_layer = SessionManagerLayer::new(session_store)
+        .with_secure(config.session.secure)
+        .with_same_site(config.session.same_site.into())
+        .with_expiry(Expiry::OnInactivity(config.session.expiry()))
+        .with_signed(session_key);
 
     let serve_index = state.serve_index();
     let serve_assets = state.serve_assets();
     let db = state.db.clone();
+    let throttle = Throttle::new(db.clone(), config.login.limits());
     Ok(Router::new()
         .fallback_service(auth::guard(serve_index.clone()))
-        .nest("/api", api::routes().with_state(state))
-        .route("/logout", any(auth::logout))
-        .route("/login", auth::login(serve_index.clone()))
+        .nest("/api", api::routes().with_state(state.clone()))
+        .route("/logout", auth::logout(db.clone()))
+        .route(
+            "/login",
+            auth::login(serve_index.clone(), db.clone(), throttle.clone()),
+        )
+        .route("/login/totp", auth::second_factor(db.clone(), throttle))
         .route("/attend/{id}", get_service(serve_index))
         .route("/assets/{*any}", get_service(serve_assets))
-        .layer(middleware::from_fn(lang_tagger))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz).with_state(state.clone()))
+        .route("/metrics", get(telemetry::render).with_state(state.clone()))
+        .route_layer(middleware::from_fn(telemetry::track_http))
+        .layer(middleware::from_fn_with_state(state, lang_tagger))
+        .layer(middleware::from_fn(same_origin))
         .layer(AuthManagerLayerBuilder::new(AuthBackend::new(db), session_layer).build())
         .layer(CompressionLayer::new().gzip(true))
-        .layer(TimeoutLayer::new(StdDuration::from_secs(30)))
+        .layer(Extension(TrustedProxies(
+            config.trusted_proxies.clone().into(),
+        )))
+        .layer(TimeoutLayer::new(config.request_timeout()))
         .layer(TraceLayer::new_for_http()))
 }
 
 async fn lang_tagger(
+    State(state): State<env::AppState>,
     mut jar: CookieJar,
     req: Request,
     next: middleware::Next,
@@ -52,8 +97,37 @@ async fn lang_tagger(
         jar = jar.add(
             Cookie::build(("lang", "en"))
                 .path("/")
-                .expires(after_a_month()),
+                .expires(state.config.cookie_expiry()),
         );
     }
     (jar, next.run(req).await)
 }
+
+/// Refuses state-changing requests and WebSocket upgrades that a browser reports as coming from
+/// another site. Requests without `Sec-Fetch-Site` or `Origin` are not from a browser, so they
+/// cannot be forged with a user's cookies and are let through.
+async fn same_origin(req: Request, next: middleware::Next) -> Result<Response> {
+    let headers = req.headers();
+    if req.method().is_safe() && !headers.contains_key(header::UPGRADE) {
+        return Ok(next.run(req).await);
+    }
+
+    let value = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let same_origin = match (value("sec-fetch-site"), value(header::ORIGIN.as_str())) {
+        (Some(site), _) => site == "same-origin" || site == "none",
+        (None, Some(origin)) => origin
+            .split_once("://")
+            .is_some_and(|(_, authority)| Some(authority) == value(header::HOST.as_str())),
+        (None, None) => true,
+    };
+
+    if same_origin {
+        Ok(next.run(req).await)
+    } else {
+        Err(Error::Forbidden(format!(
+            "cross-origin {} {}",
+            req.method(),
+            req.uri().path()
+        )))
+    }
+}
@@ -1,206 +1,1007 @@
 use axum::{
     Json, Router,
-    extract::{Path, State, WebSocketUpgrade},
-    response::IntoResponse,
-    routing::{get, post},
+    extract::{Path, Query, State, WebSocketUpgrade},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use axum_extra::{extract::CookieJar, response::Attachment};
+use types::{
+    ListQuery,
+    invitation::InvitationRequest,
+    sharing::{Grant, Grantee, Role},
+    token::TokenRequest,
+    zerra::{Persona, Progress},
 };
-use axum_extra::extract::CookieJar;
-use types::zerra::Progress;
 
+use crate::env::{AppState, i18n};
 use crate::models::{
-    Result, authenticated,
-    conduction::{Attendee, Conductor, Participant},
+    Result,
+    audit::{self, Action, Origin},
+    authenticated,
+    collaboration::Edition,
+    conduction::{Attendee, Conductor, ConnectionStatus, Participant},
     error::Error,
+    import, invitation, recovery, report, spreadsheet,
+    throttle::Throttle,
+    token::Caller,
     transcription::transcribe,
-    unauthenticated,
+    unauthenticated::{self, Metadata},
     user::AuthSession,
 };
-use crate::{
-    env::{AppState, i18n},
-    utils::after_a_month,
-};
+
+/// Seconds within which the same rejected attendance from the same client is recorded only once.
+const REJECTION_AUDIT_WINDOW: i64 = 10 * 60;
 
 pub(crate) fn routes() -> Router<AppState> {
     Router::new()
         .route("/", get(list_zerrae))
-        .route("/new", get(new_zerra))
-        .route("/copy/{id}", get(copy_zerra))
+        .route("/new", post(new_zerra))
+        .route("/copy/{id}", post(copy_zerra))
+        .route("/export", get(export_finished))
         .route("/export/{id}", get(export_zerra))
         .route("/import", post(import_zerra))
-        .route("/delete/{id}", get(delete_zerra))
+        .route("/import/outline", post(import_outline))
+        .route("/delete/{id}", delete(delete_zerra))
+        .route("/trash", get(list_trash))
+        .route("/restore/{id}", post(restore_zerra))
+        .route("/purge/{id}", delete(purge_zerra))
         .route("/edit/{id}", get(edit_zerra))
         .route("/conduct/{id}", get(conduct_zerra))
+        .route("/invite/{id}", post(invite_to_zerra))
+        .route("/uninvite/{id}", post(revoke_invitations))
+        .route("/invitation/{id}", get(inspect_invitation))
+        .route("/passcode/{id}", post(enter_passcode))
         .route("/attend/{id}", get(attend_zerra))
         .route("/transcript/{id}", get(transcribe_zerra))
+        .route("/recovery/{id}", post(recovery_code))
+        .route("/rebind/{id}", post(rebind_attendee))
+        .route("/recover/{id}", post(recover_attendee))
+        .route("/persona", get(get_persona).post(set_persona))
+        .route("/totp", get(totp_status))
+        .route("/totp/enrol", post(enrol_totp))
+        .route("/totp/confirm", post(confirm_totp))
+        .route("/totp/disable", post(disable_totp))
+        .route("/tokens", get(list_tokens).post(mint_token))
+        .route("/tokens/{id}", delete(revoke_token))
+        .route("/share/{id}", get(list_grants).post(share_zerra))
+        .route("/unshare/{id}", post(unshare_zerra))
+        .route("/live", get(list_live))
+        .route("/teams", get(list_teams).post(new_team))
+        .route("/teams/{id}/add", post(add_team_member))
+        .route("/teams/{id}/remove", post(remove_team_member))
+        .route("/folders", get(list_folders).post(new_folder))
+        .route("/folders/{id}", post(rename_folder).delete(delete_folder))
+        .route("/file/{id}", post(file_zerra))
+        .route("/tags", get(list_tags))
+        .route("/tags/{id}", post(tag_zerra))
+        .route("/templates", get(list_templates).post(new_template))
+        .route("/templates/from/{id}", post(template_from_zerra))
+        .route("/start/{id}", post(start_interview))
+        .route("/report/{id}", get(report_template))
 }
 
 async fn list_zerrae(
+    caller: Caller,
+    Query(query): Query<ListQuery>,
+    State(AppState {
+        db,
+        under_conduction,
+        ..
+    }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .fetch_zerrae(&under_conduction, &query)
+        .await
+        .map(Json)
+}
+
+async fn list_live(
+    caller: Caller,
+    State(AppState {
+        db,
+        under_conduction,
+        ..
+    }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .live_conductions(&under_conduction)
+        .await
+        .map(Json)
+}
+
+async fn get_persona(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .persona()
+        .await
+        .map(Json)
+}
+
+async fn set_persona(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+    Json(persona): Json<Persona>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .set_persona(persona)
+        .await
+}
+
+// Tokens and second factors are managed only from a login session, so a leaked token cannot be
+// used to entrench itself.
+
+#[derive(serde::Deserialize)]
+struct TotpCode {
+    code: String,
+}
+
+async fn totp_status(
     auth_session: AuthSession,
     State(AppState { db, .. }): State<AppState>,
 ) -> Result<impl IntoResponse> {
     authenticated::Context::authenticate(auth_session.user, &db)?
         .1
-        .fetch_zerrae()
+        .totp_status()
         .await
         .map(Json)
 }
 
-async fn new_zerra(
+async fn enrol_totp(
+    auth_session: AuthSession,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    let (user, ctx) = authenticated::Context::authenticate(auth_session.user, &db)?;
+    ctx.enrol_totp(&user.username).await.map(Json)
+}
+
+async fn confirm_totp(
     auth_session: AuthSession,
+    origin: Origin,
     State(AppState { db, .. }): State<AppState>,
+    Json(TotpCode { code }): Json<TotpCode>,
 ) -> Result<impl IntoResponse> {
     authenticated::Context::authenticate(auth_session.user, &db)?
         .1
-        .new_zerra()
+        .confirm_totp(&code, &origin)
         .await
+        .map(Json)
 }
 
-async fn copy_zerra(
+async fn disable_totp(
     auth_session: AuthSession,
-    Path(zerra_id): Path<String>,
+    origin: Origin,
     State(AppState { db, .. }): State<AppState>,
+    Json(TotpCode { code }): Json<TotpCode>,
 ) -> Result<impl IntoResponse> {
     authenticated::Context::authenticate(auth_session.user, &db)?
         .1
-        .copy(&zerra_id)
+        .disable_totp(&code, &origin)
         .await
 }
 
-async fn export_zerra(
+async fn list_tokens(
     auth_session: AuthSession,
-    Path(zerra_id): Path<String>,
     State(AppState { db, .. }): State<AppState>,
 ) -> Result<impl IntoResponse> {
     authenticated::Context::authenticate(auth_session.user, &db)?
         .1
-        .export(&zerra_id)
+        .tokens()
         .await
+        .map(Json)
 }
 
-async fn import_zerra(
+async fn mint_token(
     auth_session: AuthSession,
+    origin: Origin,
     State(AppState { db, .. }): State<AppState>,
-    yaml: String,
+    Json(request): Json<TokenRequest>,
 ) -> Result<impl IntoResponse> {
     authenticated::Context::authenticate(auth_session.user, &db)?
         .1
-        .import(&yaml)
+        .mint_token(request, &origin)
         .await
+        .map(Json)
 }
 
-async fn delete_zerra(
+async fn revoke_token(
     auth_session: AuthSession,
-    Path(zerra_id): Path<String>,
+    origin: Origin,
+    Path(token_id): Path<i64>,
     State(AppState { db, .. }): State<AppState>,
 ) -> Result<impl IntoResponse> {
     authenticated::Context::authenticate(auth_session.user, &db)?
         .1
-        .delete(&zerra_id)
+        .revoke_token(token_id, &origin)
+        .await
+}
+
+async fn new_zerra(
+    caller: Caller,
+    origin: Origin,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .new_zerra(&origin)
+        .await
+}
+
+async fn copy_zerra(
+    caller: Caller,
+    origin: Origin,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .copy(&zerra_id, &origin)
+        .await
+}
+
+async fn export_zerra(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .export(&zerra_id)
+        .await
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SheetFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+/// `ids` separates the zerrae to export by commas.
+#[derive(serde::Deserialize)]
+struct ExportParams {
+    ids: Option<String>,
+    template: Option<String>,
+    #[serde(default)]
+    format: SheetFormat,
+}
+
+async fn export_finished(
+    caller: Caller,
+    Query(ExportParams {
+        ids,
+        template,
+        format,
+    }): Query<ExportParams>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    let ids: Vec<String> = ids
+        .iter()
+        .flat_map(|ids| ids.split(','))
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    let lines = authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .tabulate(&ids, template.as_deref())
+        .await?;
+    Ok(match format {
+        SheetFormat::Csv => Attachment::new(spreadsheet::to_csv(&lines)?)
+            .content_type("text/csv")
+            .filename("zerrae.csv"),
+        SheetFormat::Xlsx => Attachment::new(spreadsheet::to_xlsx(&lines)?)
+            .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+            .filename("zerrae.xlsx"),
+    })
+}
+
+/// With `dry_run`, the zerra is read and checked but only returned, not stored.
+#[derive(serde::Deserialize)]
+struct ImportParams {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn import_zerra(
+    caller: Caller,
+    origin: Origin,
+    Query(ImportParams { dry_run }): Query<ImportParams>,
+    State(AppState { db, .. }): State<AppState>,
+    yaml: String,
+) -> Result<Response> {
+    let ctx = authenticated::Context::authenticate(caller.user, &db)?.1;
+    let zerra = import::from_yaml(&yaml)?;
+    if dry_run {
+        return Ok(Json(zerra).into_response());
+    }
+    ctx.store_import(zerra, &origin).await?;
+    Ok(().into_response())
+}
+
+async fn import_outline(
+    caller: Caller,
+    origin: Origin,
+    Query(ImportParams { dry_run }): Query<ImportParams>,
+    State(AppState { db, .. }): State<AppState>,
+    text: String,
+) -> Result<Response> {
+    let ctx = authenticated::Context::authenticate(caller.user, &db)?.1;
+    let zerra = import::from_outline(&text)?;
+    if dry_run {
+        return Ok(Json(zerra).into_response());
+    }
+    ctx.store_import(zerra, &origin).await?;
+    Ok(().into_response())
+}
+
+async fn delete_zerra(
+    caller: Caller,
+    origin: Origin,
+    Path(zerra_id): Path<String>,
+    State(AppState {
+        db,
+        under_conduction,
+        under_edition,
+        ..
+    }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .delete(&zerra_id, &origin, &under_conduction, &under_edition)
+        .await
+}
+
+async fn list_trash(
+    caller: Caller,
+    State(AppState { db, config, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .trash(config.trash.retention())
         .await
         .map(Json)
 }
 
+async fn restore_zerra(
+    caller: Caller,
+    origin: Origin,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .restore(&zerra_id, &origin)
+        .await
+}
+
+async fn purge_zerra(
+    caller: Caller,
+    origin: Origin,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .purge(&zerra_id, &origin)
+        .await
+}
+
 async fn edit_zerra(
-    auth_session: AuthSession,
+    caller: Caller,
     Path(zerra_id): Path<String>,
     ws: WebSocketUpgrade,
-    State(AppState { db, .. }): State<AppState>,
+    State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    let (_, ctx) = authenticated::Context::authenticate(auth_session.user, &db)?;
-    ctx.owns(&zerra_id).await?;
-    let blob = unauthenticated::fetch_blob(&db, &zerra_id).await?;
-    Ok(ws.on_upgrade(ctx.edit(zerra_id, blob)))
+    let draining = state.shutdown.admit()?;
+    let (user, ctx) = authenticated::Context::authenticate(caller.user, &state.db)?;
+    ctx.authorize(&zerra_id, Role::Editor).await?;
+    if state.under_conduction.contains_key(&zerra_id) {
+        return Err(Error::Conflict(format!("zerra {zerra_id} is live")));
+    }
+    let blob = unauthenticated::fetch_blob(&state.db, &zerra_id).await?;
+    Ok(ws.on_upgrade(ctx.edit(zerra_id, user.display_name, blob, state.clone(), draining)))
 }
 
 async fn conduct_zerra(
-    auth_session: AuthSession,
+    caller: Caller,
     Path(zerra_id): Path<String>,
     ws: WebSocketUpgrade,
     State(AppState {
         db,
+        config,
         under_conduction,
+        under_edition,
+        shutdown,
         ..
     }): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    let (_, ctx) = authenticated::Context::authenticate(auth_session.user, &db)?;
-    ctx.owns(&zerra_id).await?;
+    let draining = shutdown.admit()?;
+    let (_, ctx) = authenticated::Context::authenticate(caller.user, &db)?;
+    ctx.authorize(&zerra_id, Role::Conductor).await?;
+    // A second conductor is turned away before it can take the zerra over. Joining checks again,
+    // for a conductor that arrives in between.
+    if under_conduction
+        .get(&zerra_id)
+        .is_some_and(|c| !matches!(c.status, ConnectionStatus::WaitingForConductor(..)))
+    {
+        return Err(Error::Conflict("zerra already has a conductor".to_string()));
+    }
+    ctx.conducts(&zerra_id).await?;
+    Edition::evict(&under_edition, &zerra_id);
     let blob = unauthenticated::fetch_blob(&db, &zerra_id).await?;
-    Ok(ws.on_upgrade(
-        Participant::<Conductor>::new(db, zerra_id, under_conduction).participate(blob),
-    ))
+    let conductor =
+        Participant::<Conductor>::new(db, zerra_id, under_conduction, config.live, draining)?;
+    Ok(ws.on_upgrade(conductor.participate(blob)))
+}
+
+async fn list_grants(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .grants(&zerra_id)
+        .await
+        .map(Json)
+}
+
+async fn share_zerra(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(grant): Json<Grant>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .share(&zerra_id, grant)
+        .await
+}
+
+async fn unshare_zerra(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(grantee): Json<Grantee>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .unshare(&zerra_id, grantee)
+        .await
 }
 
 #[derive(serde::Deserialize)]
-struct Metadata {
-    progress: Progress,
+struct TeamName {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Member {
+    username: String,
+}
+
+async fn list_teams(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .teams()
+        .await
+        .map(Json)
+}
+
+async fn new_team(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+    Json(TeamName { name }): Json<TeamName>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .new_team(&name)
+        .await
+}
+
+async fn add_team_member(
+    caller: Caller,
+    Path(team_id): Path<i64>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(Member { username }): Json<Member>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .add_member(team_id, &username)
+        .await
+}
+
+async fn remove_team_member(
+    caller: Caller,
+    Path(team_id): Path<i64>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(Member { username }): Json<Member>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .remove_member(team_id, &username)
+        .await
+}
+
+#[derive(serde::Deserialize)]
+struct FolderName {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Filing {
+    folder: Option<i64>,
+}
+
+async fn list_folders(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .folders()
+        .await
+        .map(Json)
+}
+
+async fn new_folder(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+    Json(FolderName { name }): Json<FolderName>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .new_folder(&name)
+        .await
+        .map(Json)
+}
+
+async fn rename_folder(
+    caller: Caller,
+    Path(folder_id): Path<i64>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(FolderName { name }): Json<FolderName>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .rename_folder(folder_id, &name)
+        .await
+}
+
+async fn delete_folder(
+    caller: Caller,
+    Path(folder_id): Path<i64>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .delete_folder(folder_id)
+        .await
+}
+
+async fn file_zerra(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(Filing { folder }): Json<Filing>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .file(&zerra_id, folder)
+        .await
+}
+
+async fn list_tags(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .tags()
+        .await
+        .map(Json)
+}
+
+async fn tag_zerra(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(tags): Json<Vec<String>>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .set_tags(&zerra_id, tags)
+        .await
+}
+
+async fn list_templates(
+    caller: Caller,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .templates()
+        .await
+        .map(Json)
+}
+
+async fn new_template(
+    caller: Caller,
+    origin: Origin,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .new_template(None, &origin)
+        .await
+        .map(Json)
+}
+
+async fn template_from_zerra(
+    caller: Caller,
+    origin: Origin,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .new_template(Some(&zerra_id), &origin)
+        .await
+        .map(Json)
+}
+
+async fn start_interview(
+    caller: Caller,
+    origin: Origin,
+    Path(template_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .start_interview(&template_id, &origin)
+        .await
+        .map(Json)
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+    Pdf,
+}
+
+#[derive(serde::Deserialize)]
+struct ReportParams {
+    #[serde(default)]
+    format: ReportFormat,
+}
+
+async fn report_template(
+    caller: Caller,
+    jar: CookieJar,
+    Path(template_id): Path<String>,
+    Query(ReportParams { format }): Query<ReportParams>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<Response> {
+    let report = authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .report(&template_id)
+        .await?;
+    Ok(match format {
+        ReportFormat::Json => Json(report).into_response(),
+        ReportFormat::Csv => Attachment::new(report::to_csv(&report)?)
+            .content_type("text/csv")
+            .filename(format!("{template_id}.csv"))
+            .into_response(),
+        ReportFormat::Pdf => {
+            let lang = i18n(jar.get("lang").map_or("en", |c| c.value_trimmed()));
+            let pdf = tokio::task::spawn_blocking(move || report::render(&report, lang)).await??;
+            Attachment::new(pdf)
+                .content_type("application/pdf")
+                .filename(format!("{template_id}.pdf"))
+                .into_response()
+        }
+    })
+}
+
+async fn invite_to_zerra(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState {
+        db, invitation_key, ..
+    }): State<AppState>,
+    Json(request): Json<InvitationRequest>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .invite(&invitation_key, &zerra_id, request)
+        .await
+        .map(Json)
+}
+
+async fn revoke_invitations(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .revoke_invitations(&zerra_id)
+        .await
+}
+
+#[derive(serde::Deserialize)]
+struct InvitationParams {
+    invitation: Option<String>,
+}
+
+async fn inspect_invitation(
+    Path(zerra_id): Path<String>,
+    Query(InvitationParams { invitation }): Query<InvitationParams>,
+    State(AppState {
+        db, invitation_key, ..
+    }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    let invitation = invitation.ok_or_else(|| Error::BadRequest("missing invitation".into()))?;
+    invitation::inspect(&db, &invitation_key, &zerra_id, &invitation)
+        .await
+        .map(Json)
+}
+
+#[derive(serde::Deserialize)]
+struct Passcode {
+    invitation: String,
+    passcode: String,
+}
+
+/// Checks the passcode of an invitation ahead of attending, keeping the proof of it in a cookie
+/// rather than putting the passcode in the URL of the socket.
+async fn enter_passcode(
+    jar: CookieJar,
+    Path(zerra_id): Path<String>,
+    State(AppState {
+        db,
+        config,
+        invitation_key,
+        ..
+    }): State<AppState>,
+    Json(Passcode {
+        invitation,
+        passcode,
+    }): Json<Passcode>,
+) -> Result<impl IntoResponse> {
+    let proof =
+        invitation::enter_passcode(&db, &invitation_key, &zerra_id, &invitation, passcode).await?;
+    Ok(jar.add(
+        tower_sessions::cookie::Cookie::build((format!("passcode_{zerra_id}"), proof))
+            .path("/")
+            .http_only(true)
+            .expires(config.cookie_expiry()),
+    ))
 }
 
 async fn attend_zerra(
     jar: CookieJar,
+    origin: Origin,
     Path(zerra_id): Path<String>,
+    Query(InvitationParams { invitation }): Query<InvitationParams>,
     ws: WebSocketUpgrade,
     State(AppState {
         db,
+        config,
         under_conduction,
+        invitation_key,
+        shutdown,
         ..
     }): State<AppState>,
 ) -> Result<impl IntoResponse> {
+    let draining = shutdown.admit()?;
     let blob = unauthenticated::fetch_blob(&db, &zerra_id).await?;
     let Metadata { progress } = rmp_serde::from_slice(&blob)?;
     let cookie_name = format!("attendee_{zerra_id}");
 
-    match progress {
-        Progress::None => Ok((
-            jar.add(
-                tower_sessions::cookie::Cookie::build((
-                    cookie_name.clone(),
-                    uuid::Uuid::new_v4().as_simple().to_string(),
-                ))
-                .path("/")
-                .expires(after_a_month()),
-            ),
-            ws.on_upgrade(
-                Participant::<Attendee>::new(db, zerra_id, under_conduction).participate(blob),
-            ),
-        )),
-        Progress::Intro { participant_uuid }
-        | Progress::Ongoing {
-            participant_uuid, ..
-        }
-        | Progress::Finished {
-            participant_uuid, ..
-        } => {
-            let cookie_uuid = jar.get(&cookie_name).ok_or_else(|| {
-                Error::Unauthorized(format!(
-                    "zerra {zerra_id} already attended by {participant_uuid}"
-                ))
-            })?;
-            if cookie_uuid.value_trimmed() == participant_uuid {
+    // Whether the attendee claims the zerra just now, rather than coming back to it.
+    let claim = async {
+        match progress {
+            Progress::None => {
+                // A returning attendee keeps their identity, so a single-use invitation they
+                // already claimed still lets them in.
+                let returning = jar.get(&cookie_name).map(|c| c.value_trimmed().to_string());
+                let first = returning.is_none();
+                let attendee_uuid =
+                    returning.unwrap_or_else(|| uuid::Uuid::new_v4().as_simple().to_string());
+                let invitation = invitation.ok_or_else(|| {
+                    Error::Unauthorized(format!("zerra {zerra_id} needs an invitation to attend"))
+                })?;
+                let claimed = invitation::redeem(
+                    &db,
+                    &invitation_key,
+                    &zerra_id,
+                    &invitation,
+                    jar.get(&format!("passcode_{zerra_id}"))
+                        .map(|c| c.value_trimmed()),
+                    &attendee_uuid,
+                )
+                .await?;
                 Ok((
-                    jar,
-                    ws.on_upgrade(
-                        Participant::<Attendee>::new(db, zerra_id, under_conduction)
-                            .participate(blob),
+                    jar.add(
+                        tower_sessions::cookie::Cookie::build((
+                            cookie_name.clone(),
+                            attendee_uuid.clone(),
+                        ))
+                        .path("/")
+                        .expires(config.cookie_expiry()),
                     ),
+                    attendee_uuid,
+                    first || claimed,
                 ))
-            } else {
-                Err(Error::Unauthorized(format!(
-                    "zerra {zerra_id} already attended by {participant_uuid}; {} cannot attend",
-                    cookie_uuid.value_trimmed(),
-                )))
+            }
+            Progress::Intro { participant_uuid }
+            | Progress::Ongoing {
+                participant_uuid, ..
+            }
+            | Progress::Finished {
+                participant_uuid, ..
+            } => {
+                let cookie_uuid = jar.get(&cookie_name).ok_or_else(|| {
+                    Error::Unauthorized(format!(
+                        "zerra {zerra_id} already attended by {participant_uuid}"
+                    ))
+                })?;
+                if cookie_uuid.value_trimmed() == participant_uuid {
+                    Ok((jar, participant_uuid, false))
+                } else {
+                    Err(Error::Unauthorized(format!(
+                        "zerra {zerra_id} already attended by {participant_uuid}; {} cannot attend",
+                        cookie_uuid.value_trimmed(),
+                    )))
+                }
             }
         }
     }
+    .await;
+
+    // Only first claims are recorded, not every reconnection. Rejections are recorded with their
+    // reason, which names the attendees involved, once per client in a while. Failing to record
+    // neither lets anyone in nor keeps them out.
+    let recorded = match &claim {
+        Ok((_, attendee_uuid, true)) => {
+            audit::record(
+                &db,
+                &origin,
+                None,
+                Action::ClaimAttendance,
+                Some(&zerra_id),
+                Some(attendee_uuid),
+            )
+            .await
+        }
+        Ok(_) => Ok(()),
+        Err(e) => audit::record_unless_recent(
+            &db,
+            &origin,
+            Action::RejectAttendance,
+            Some(&zerra_id),
+            Some(&e.to_string()),
+            REJECTION_AUDIT_WINDOW,
+        )
+        .await
+        .map(|_| ()),
+    };
+    if let Err(e) = recorded {
+        tracing::error!(?e, "cannot record attendance in the audit log");
+    }
+
+    let (jar, ..) = claim?;
+    let attendee =
+        Participant::<Attendee>::new(db, zerra_id, under_conduction, config.live, draining)?;
+    Ok((jar, ws.on_upgrade(attendee.participate(blob))))
+}
+
+async fn recovery_code(
+    jar: CookieJar,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    let cookie_uuid = jar.get(&format!("attendee_{zerra_id}"));
+    recovery::recovery_code(&db, &zerra_id, cookie_uuid.map(|c| c.value_trimmed())).await
+}
+
+async fn rebind_attendee(
+    caller: Caller,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Result<impl IntoResponse> {
+    authenticated::Context::authenticate(caller.user, &db)?
+        .1
+        .rebind(&zerra_id)
+        .await
+}
+
+#[derive(serde::Deserialize)]
+struct RecoveryCode {
+    code: String,
+}
+
+async fn recover_attendee(
+    jar: CookieJar,
+    origin: Origin,
+    Path(zerra_id): Path<String>,
+    State(AppState { db, config, .. }): State<AppState>,
+    Json(RecoveryCode { code }): Json<RecoveryCode>,
+) -> Result<impl IntoResponse> {
+    // Codes are guessed like passwords, so guesses are throttled like logins.
+    let throttle = Throttle::recovery(db.clone(), config.login.limits());
+    if let Some(retry_after) = throttle.admit(&zerra_id, &origin.client).await? {
+        return Err(Error::Throttled(retry_after));
+    }
+    let participant_uuid = recovery::recover(&db, &zerra_id, code).await?;
+    throttle.forgive(&zerra_id, &origin.client).await?;
+    Ok(jar.add(
+        tower_sessions::cookie::Cookie::build((format!("attendee_{zerra_id}"), participant_uuid))
+            .path("/")
+            .expires(config.cookie_expiry()),
+    ))
+}
+
+/// Records a transcript download before it is rendered. Failing to record does not keep anyone
+/// from their transcript.
+async fn record_download(
+    db: &sqlx::PgPool,
+    origin: &Origin,
+    actor: Option<i64>,
+    zerra_id: &str,
+    detail: Option<&str>,
+) {
+    if let Err(e) = audit::record(
+        db,
+        origin,
+        actor,
+        Action::DownloadTranscript,
+        Some(zerra_id),
+        detail,
+    )
+    .await
+    {
+        tracing::error!(?e, "cannot record transcript download in the audit log");
+    }
 }
 
 async fn transcribe_zerra(
+    caller: Caller,
     jar: CookieJar,
+    origin: Origin,
     Path(zerra_id): Path<String>,
     State(AppState { db, .. }): State<AppState>,
 ) -> Result<impl IntoResponse> {
     let blob = unauthenticated::fetch_blob(&db, &zerra_id).await?;
     let Metadata { progress } = rmp_serde::from_slice(&blob)?;
     let cookie_name = format!("attendee_{zerra_id}");
+    let lang = i18n(jar.get("lang").map_or("en", |c| c.value_trimmed()));
+
+    // Anyone with access to the zerra may archive its transcript, printable or not.
+    if let Some(user) = caller.user {
+        let (_, ctx) = authenticated::Context::authenticate(Some(user), &db)?;
+        if ctx.role_in(&zerra_id).await?.is_some() {
+            let Progress::Finished { .. } = progress else {
+                return Err(Error::NotFound(format!("zerra {zerra_id} is not finished")));
+            };
+            record_download(&db, &origin, Some(ctx.user), &zerra_id, None).await;
+            return transcribe(&db, &zerra_id, lang).await;
+        }
+    }
 
     match progress {
         Progress::Finished {
@@ -215,12 +1016,8 @@ async fn transcribe_zerra(
             })?;
             if cookie_uuid.value_trimmed() == participant_uuid {
                 if printable {
-                    transcribe(
-                        &db,
-                        &zerra_id,
-                        i18n(jar.get("lang").map_or("en", |c| c.value_trimmed())),
-                    )
-                    .await
+                    record_download(&db, &origin, None, &zerra_id, Some(&participant_uuid)).await;
+                    transcribe(&db, &zerra_id, lang).await
                 } else {
                     Err(Error::Forbidden(format!(
                         "zerra {zerra_id} is not printable"
@@ -3,13 +3,22 @@ use axum::{
     extract::Request,
     http::StatusCode,
     response::{IntoResponse, Redirect},
-    routing::{MethodRouter, any, get},
+    routing::{MethodRouter, any, get, post},
 };
 use axum_extra::extract::CookieJar;
+use axum_login::AuthnBackend as _;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use tower::ServiceExt;
 use tower_http::services::ServeFile;
+use types::jiff::Timestamp;
 
-use crate::models::user::{AuthSession, Credentials};
+use crate::models::{
+    audit::{self, Action, Origin},
+    throttle::Throttle,
+    totp,
+    user::{AuthSession, Credentials, User},
+};
 
 pub(crate) fn guard(index: ServeFile) -> MethodRouter {
     any(|auth_session: AuthSession, req: Request| async move {
@@ -21,7 +30,7 @@ pub(crate) fn guard(index: ServeFile) -> MethodRouter {
     })
 }
 
-pub(crate) fn login(index: ServeFile) -> MethodRouter {
+pub(crate) fn login(index: ServeFile, db: PgPool, throttle: Throttle) -> MethodRouter {
     get(|auth_session: AuthSession, req: Request| async move {
         if auth_session.user.is_some() {
             Redirect::to("/").into_response()
@@ -29,49 +38,287 @@ pub(crate) fn login(index: ServeFile) -> MethodRouter {
             index.oneshot(req).await.into_response()
         }
     })
-    .post(login_post)
+    .post(
+        |auth_session: AuthSession, origin: Origin, jar: CookieJar, form: Form<Credentials>| {
+            login_post(db, throttle, auth_session, origin, jar, form)
+        },
+    )
+}
+
+pub(crate) fn second_factor(db: PgPool, throttle: Throttle) -> MethodRouter {
+    post(
+        |auth_session: AuthSession, origin: Origin, jar: CookieJar, form: Form<SecondFactor>| {
+            second_factor_post(db, throttle, auth_session, origin, jar, form)
+        },
+    )
+}
+
+/// Only by POST, so that a cross-site link or image cannot log anyone out.
+pub(crate) fn logout(db: PgPool) -> MethodRouter {
+    post(
+        |auth_session: AuthSession, origin: Origin, jar: CookieJar| {
+            logout_post(db, auth_session, origin, jar)
+        },
+    )
+}
+
+/// Records a login or logout, which must not go unnoticed.
+async fn audit(
+    db: &PgPool,
+    origin: &Origin,
+    actor: Option<i64>,
+    action: Action,
+    detail: Option<&str>,
+) -> Result<(), StatusCode> {
+    audit::record(db, origin, actor, action, None, detail)
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "error in audit log");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+fn login_url(next: Option<&str>, retry_after: Option<i64>, second_factor: bool) -> String {
+    let mut params = Vec::new();
+    if let Some(next) = next {
+        params.push(format!("next={next}"));
+    }
+    if let Some(retry_after) = retry_after {
+        params.push(format!("retry_after={retry_after}"));
+    }
+    if second_factor {
+        params.push("second_factor=true".to_string());
+    }
+
+    if params.is_empty() {
+        "/login".to_string()
+    } else {
+        format!("/login?{}", params.join("&"))
+    }
+}
+
+/// Session key of a login whose password was right but whose second factor is yet to be checked.
+const PENDING_LOGIN: &str = "pending_login";
+
+/// Seconds allowed between the password and the second factor.
+const PENDING_LOGIN_TIMEOUT: i64 = 5 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    user_id: i64,
+    username: String,
+    next: Option<String>,
+    since: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SecondFactor {
+    code: String,
 }
 
 async fn login_post(
-    mut auth_session: AuthSession,
+    db: PgPool,
+    throttle: Throttle,
+    auth_session: AuthSession,
+    origin: Origin,
     jar: CookieJar,
     Form(creds): Form<Credentials>,
-) -> impl IntoResponse {
+) -> Result<(CookieJar, Redirect), StatusCode> {
+    let client = &origin.client;
+    match throttle.admit(&creds.username, client).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => {
+            let detail = format!("{} throttled", creds.username);
+            audit(&db, &origin, None, Action::FailedLogin, Some(&detail)).await?;
+            return Ok((
+                jar,
+                Redirect::to(&login_url(creds.next.as_deref(), Some(retry_after), false)),
+            ));
+        }
+        Err(e) => {
+            tracing::error!(?e, "error in login throttle");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
     let user = match auth_session.authenticate(creds.clone()).await {
         Ok(Some(user)) => user,
         Ok(None) => {
             tracing::error!("invalid credentials");
+            if let Err(e) = throttle.fail(&creds.username, client).await {
+                tracing::error!(?e, "error in login throttle");
+            }
+            let detail = format!("{} gave a wrong password", creds.username);
+            audit(&db, &origin, None, Action::FailedLogin, Some(&detail)).await?;
+            return Ok((
+                jar,
+                Redirect::to(&login_url(creds.next.as_deref(), None, false)),
+            ));
+        }
+        Err(e) => {
+            tracing::error!(?e, "error in auth backend");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
 
-            let login_url = if let Some(next) = creds.next {
-                format!("/login?next={next}")
-            } else {
-                "/login".to_string()
+    match totp::enabled_for(&db, user.id).await {
+        Ok(false) => {}
+        Ok(true) => {
+            // The username's failures are only forgotten once the second factor is given too.
+            if let Err(e) = throttle.forgive(&user.username, client).await {
+                tracing::error!(?e, "error in login throttle");
+            }
+            let pending = PendingLogin {
+                user_id: user.id,
+                username: user.username,
+                next: creds.next,
+                since: Timestamp::now().as_second(),
             };
+            if let Err(e) = auth_session.session.insert(PENDING_LOGIN, pending).await {
+                tracing::error!(?e, "error in session store");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+            return Ok((jar, Redirect::to(&login_url(None, None, true))));
+        }
+        Err(e) => {
+            tracing::error!(?e, "error in second factor lookup");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
 
-            return Ok((jar, Redirect::to(&login_url)));
+    finish_login(db, throttle, auth_session, origin, jar, user, creds.next).await
+}
+
+async fn second_factor_post(
+    db: PgPool,
+    throttle: Throttle,
+    auth_session: AuthSession,
+    origin: Origin,
+    jar: CookieJar,
+    Form(SecondFactor { code }): Form<SecondFactor>,
+) -> Result<(CookieJar, Redirect), StatusCode> {
+    let pending = match auth_session
+        .session
+        .get::<PendingLogin>(PENDING_LOGIN)
+        .await
+    {
+        Ok(pending) => pending
+            .filter(|pending| Timestamp::now().as_second() - pending.since < PENDING_LOGIN_TIMEOUT),
+        Err(e) => {
+            tracing::error!(?e, "error in session store");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let Some(pending) = pending else {
+        return Ok((jar, Redirect::to("/login")));
+    };
+
+    let client = &origin.client;
+    match throttle.admit(&pending.username, client).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => {
+            let detail = format!("{} throttled at second factor", pending.username);
+            audit(
+                &db,
+                &origin,
+                Some(pending.user_id),
+                Action::FailedLogin,
+                Some(&detail),
+            )
+            .await?;
+            return Ok((jar, Redirect::to(&login_url(None, Some(retry_after), true))));
         }
+        Err(e) => {
+            tracing::error!(?e, "error in login throttle");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match totp::verify(&db, pending.user_id, &code, &origin).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::error!("invalid second factor");
+            if let Err(e) = throttle.fail(&pending.username, client).await {
+                tracing::error!(?e, "error in login throttle");
+            }
+            let detail = format!("{} gave a wrong second factor", pending.username);
+            audit(
+                &db,
+                &origin,
+                Some(pending.user_id),
+                Action::FailedLogin,
+                Some(&detail),
+            )
+            .await?;
+            return Ok((jar, Redirect::to(&login_url(None, None, true))));
+        }
+        Err(e) => {
+            tracing::error!(%e, "error in second factor verification");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if let Err(e) = auth_session
+        .session
+        .remove::<PendingLogin>(PENDING_LOGIN)
+        .await
+    {
+        tracing::error!(?e, "error in session store");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let user = match auth_session.backend.get_user(&pending.user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok((jar, Redirect::to("/login"))),
         Err(e) => {
             tracing::error!(?e, "error in auth backend");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
 
+    finish_login(db, throttle, auth_session, origin, jar, user, pending.next).await
+}
+
+async fn finish_login(
+    db: PgPool,
+    throttle: Throttle,
+    mut auth_session: AuthSession,
+    origin: Origin,
+    jar: CookieJar,
+    user: User,
+    next: Option<String>,
+) -> Result<(CookieJar, Redirect), StatusCode> {
+    if let Err(e) = throttle.succeed(&user.username, &origin.client).await {
+        tracing::error!(?e, "error in login throttle");
+    }
+
     if let Err(e) = auth_session.login(&user).await {
         tracing::error!(?e, "error in login backend");
         Err(StatusCode::INTERNAL_SERVER_ERROR)
     } else {
+        audit(&db, &origin, Some(user.id), Action::Login, None).await?;
         Ok((
             jar.add(("username", user.display_name)),
-            Redirect::to(creds.next.as_deref().unwrap_or("/")),
+            Redirect::to(next.as_deref().unwrap_or("/")),
         ))
     }
 }
 
-pub(crate) async fn logout(mut auth_session: AuthSession, jar: CookieJar) -> impl IntoResponse {
-    if let Err(e) = auth_session.logout().await {
-        tracing::error!(?e, "error in auth backend");
-        StatusCode::INTERNAL_SERVER_ERROR.into_response()
-    } else {
-        (jar.remove("username"), Redirect::to("/login")).into_response()
+async fn logout_post(
+    db: PgPool,
+    mut auth_session: AuthSession,
+    origin: Origin,
+    jar: CookieJar,
+) -> Result<(CookieJar, Redirect), StatusCode> {
+    match auth_session.logout().await {
+        Ok(user) => {
+            if let Some(user) = user {
+                audit(&db, &origin, Some(user.id), Action::Logout, None).await?;
+            }
+            Ok((jar.remove("username"), Redirect::to("/login")))
+        }
+        Err(e) => {
+            tracing::error!(?e, "error in auth backend");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
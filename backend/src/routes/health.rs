@@ -0,0 +1,47 @@
+use axum::{extract::State, http::StatusCode};
+use sqlx::{PgPool, Row};
+
+use crate::env::AppState;
+
+/// The process is up.
+pub(crate) async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// The server can serve: the database answers, every migration is applied and the frontend is
+/// built. Otherwise, lists what is wrong, leaving the details to the log.
+pub(crate) async fn readyz(State(state): State<AppState>) -> (StatusCode, String) {
+    let mut problems = Vec::new();
+
+    match applied_migrations(&state.db).await {
+        Ok(applied) => problems.extend(
+            sqlx::migrate!()
+                .iter()
+                .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+                .map(|m| format!("migration {} is not applied", m.version)),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "readiness check cannot reach the database");
+            problems.push("database is unreachable".to_string());
+        }
+    }
+
+    let index = state.config.assets_dir.join("index.html");
+    if !index.is_file() {
+        tracing::error!("readiness check finds no {}", index.display());
+        problems.push("frontend is not built".to_string());
+    }
+
+    if problems.is_empty() {
+        (StatusCode::OK, "ok".to_string())
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, problems.join("\n"))
+    }
+}
+
+async fn applied_migrations(db: &PgPool) -> sqlx::Result<Vec<i64>> {
+    sqlx::query("select version from _sqlx_migrations where success")
+        .try_map(|r: sqlx::postgres::PgRow| r.try_get(0))
+        .fetch_all(db)
+        .await
+}
@@ -0,0 +1,94 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::extract::ws::Message;
+use tokio::sync::watch;
+use types::tx::MessageToClient;
+
+use crate::{
+    models::{Result, error::Error},
+    utils::ResultExt as _,
+};
+
+/// Winds down live sockets when the server stops: new ones are refused, open ones are told to
+/// reconnect later and then waited for. Every change a client sends is stored as it arrives, and a
+/// client sends what it has left before closing, so closing is what tells that nothing is lost.
+#[derive(Debug, Clone)]
+pub struct Shutdown(Arc<watch::Sender<bool>>);
+
+/// Held by a live socket for as long as it is open.
+#[derive(Debug)]
+pub(crate) struct Draining(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        Shutdown(Arc::new(watch::Sender::new(false)))
+    }
+
+    /// Lets a new live socket in, unless the server is shutting down.
+    pub(crate) fn admit(&self) -> Result<Draining> {
+        if *self.0.borrow() {
+            return Err(Error::Unavailable("server is restarting".into()));
+        }
+        Ok(Draining(self.0.subscribe()))
+    }
+
+    /// Tells every live socket to wind down, then waits up to `limit` for all of them to close.
+    pub async fn drain(&self, limit: Duration) {
+        self.0.send_replace(true);
+        tracing::info!(live = self.0.receiver_count(), "draining live sockets");
+        if tokio::time::timeout(limit, self.0.closed()).await.is_err() {
+            tracing::warn!(
+                live = self.0.receiver_count(),
+                "gave up waiting for live sockets"
+            );
+        }
+    }
+}
+
+impl Draining {
+    /// Resolves once the server starts shutting down.
+    pub(crate) async fn begun(&mut self) {
+        if self.0.wait_for(|&draining| draining).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Tells a client that the server is stopping and when to reconnect.
+pub(crate) fn restarting(reconnect_secs: u32) -> Option<Message> {
+    rmp_serde::to_vec_named(&MessageToClient::Restarting(reconnect_secs))
+        .traced()
+        .ok()
+        .map(Message::binary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_waits_for_live_sockets() {
+        let shutdown = Shutdown::new();
+        let mut live = shutdown.admit().unwrap();
+        tokio::spawn(async move {
+            live.begun().await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        });
+
+        let started = tokio::time::Instant::now();
+        shutdown.drain(Duration::from_secs(30)).await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(matches!(shutdown.admit(), Err(Error::Unavailable(_))));
+    }
+
+    #[tokio::test]
+    async fn drain_gives_up() {
+        let shutdown = Shutdown::new();
+        let _stuck = shutdown.admit().unwrap();
+
+        let started = tokio::time::Instant::now();
+        shutdown.drain(Duration::from_millis(50)).await;
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+}
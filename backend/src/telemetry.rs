@@ -0,0 +1,145 @@
+use std::{
+    net::IpAddr,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use scopeguard::{ScopeGuard, guard};
+
+use crate::{
+    env::AppState,
+    models::{Result, audit::Origin, conduction::ConnectionStatus, error::Error},
+};
+
+/// Seconds bounding the latency histograms, from a quick query to a slow Typst compilation.
+const BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+static INSTALLING: Mutex<()> = Mutex::new(());
+
+/// Makes the `metrics` macros record for Prometheus. The recorder is global to the process, so
+/// building another router keeps the one installed first.
+pub(crate) fn install() -> anyhow::Result<()> {
+    let _installing = INSTALLING.lock().unwrap_or_else(|e| e.into_inner());
+    if HANDLE.get().is_some() {
+        return Ok(());
+    }
+    let handle = PrometheusBuilder::new()
+        .set_buckets(BUCKETS)?
+        .install_recorder()?;
+    HANDLE
+        .set(handle)
+        .map_err(|_| anyhow::anyhow!("metrics recorder is already installed"))
+}
+
+/// Counts requests and measures their latency per route. Only requests that match a route are
+/// seen, so that the labels stay few.
+pub(crate) async fn track_http(req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(String::new, |path| path.as_str().to_string());
+    let method = req.method().to_string();
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status,
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "route" => route,
+    )
+    .record(started.elapsed());
+    response
+}
+
+/// Counts a WebSocket of `party` as open until the returned guard is dropped.
+pub(crate) fn connection(
+    party: &'static str,
+) -> ScopeGuard<&'static str, impl FnOnce(&'static str)> {
+    metrics::gauge!("zerra_websockets", "party" => party).increment(1);
+    guard(party, |party| {
+        metrics::gauge!("zerra_websockets", "party" => party).decrement(1)
+    })
+}
+
+/// Renders every metric in the Prometheus text format. When `metrics_token` is configured, it must
+/// be given as a bearer token. Otherwise only clients on the server's own host or among the trusted
+/// proxies are served.
+pub(crate) async fn render(
+    State(state): State<AppState>,
+    origin: Origin,
+    headers: HeaderMap,
+) -> Result<String> {
+    if let Some(token) = &state.config.metrics_token {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if bearer != Some(token.as_str()) {
+            return Err(Error::Unauthorized("wrong metrics token".into()));
+        }
+    } else if !origin.client.parse().is_ok_and(|client: IpAddr| {
+        client.is_loopback()
+            || state
+                .config
+                .trusted_proxies
+                .iter()
+                .any(|range| range.contains(client))
+    }) {
+        return Err(Error::Forbidden(format!(
+            "metrics without a token for {}",
+            origin.client
+        )));
+    }
+
+    let (mut waiting_for_conductor, mut waiting_for_attendee, mut established) = (0, 0, 0);
+    for conduction in state.under_conduction.iter() {
+        match conduction.status {
+            ConnectionStatus::WaitingForConductor(..) => waiting_for_conductor += 1,
+            ConnectionStatus::WaitingForAttedee(..) => waiting_for_attendee += 1,
+            ConnectionStatus::Established => established += 1,
+        }
+    }
+    for (status, count) in [
+        ("waiting_for_conductor", waiting_for_conductor),
+        ("waiting_for_attendee", waiting_for_attendee),
+        ("established", established),
+    ] {
+        metrics::gauge!("zerra_conductions", "status" => status).set(f64::from(count));
+    }
+    metrics::gauge!("zerra_editions").set(state.under_edition.len() as f64);
+
+    Ok(HANDLE
+        .get()
+        .map(PrometheusHandle::render)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_twice() {
+        install().unwrap();
+        install().unwrap();
+    }
+}
@@ -1,5 +1,3 @@
-use tower_sessions::cookie::time::OffsetDateTime;
-
 pub(crate) trait ResultExt {
     fn traced(self) -> Self;
     fn void(self);
@@ -17,6 +15,35 @@ impl<T, E: std::error::Error> ResultExt for Result<T, E> {
     fn void(self) {}
 }
 
-pub(crate) fn after_a_month() -> OffsetDateTime {
-    OffsetDateTime::now_utc() + tower_sessions::cookie::time::Duration::days(30)
+/// Quotes text as a Typst string literal, so that nobody's text is read as markup.
+pub(crate) fn typst_str(text: &str) -> String {
+    format!(
+        r#""{}""#,
+        text.replace('\\', r"\\")
+            .replace('"', r#"\""#)
+            .replace('\n', r"\n")
+    )
+}
+
+/// Defuses text for a CSV cell, so that a spreadsheet does not take it for a formula.
+pub(crate) fn csv_cell(text: &str) -> std::borrow::Cow<'_, str> {
+    if text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{text}").into()
+    } else {
+        text.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formulas_are_defused() {
+        assert_eq!(csv_cell("=HYPERLINK(\"x\")"), "'=HYPERLINK(\"x\")");
+        assert_eq!(csv_cell("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_cell("-1"), "'-1");
+        assert_eq!(csv_cell("a = b"), "a = b");
+        assert_eq!(csv_cell(""), "");
+    }
 }
@@ -1,59 +1,41 @@
-import { pack, unpack } from 'msgpackr';
-import { useEffect, useRef, useState } from 'react';
-import useWebSocket, { ReadyState } from 'react-use-websocket';
-import { useImmerReducer } from 'use-immer';
+import { useCallback } from 'react';
 
-import { DocAction, Zerra, doAction } from '../model';
-import { Debouncer } from '../utils/debouncer';
+import { MessageFromEditor } from '../../../types/bindings/MessageFromEditor';
+import { useZerraParticipant } from './participate';
 
 export default function useZerraEditor(uuid: string) {
-  const ws = useWebSocket(`/api/edit/${uuid}`, { disableJson: true });
-
-  const [shouldSend, setShouldSend] = useState(false);
-
-  const debouncer = useRef(new Debouncer());
-  const enqueueDebounced = (key: string | [number[], string]) =>
-    debouncer.current.debounce(key, () => setShouldSend(true));
-
-  const [doc, dispatchDoc] = useImmerReducer(
-    (doc, action: DocAction) =>
-      doAction(action, doc, (key) =>
-        key === undefined ? setShouldSend(true) : enqueueDebounced(key),
-      ),
-    null as Zerra | null,
-  );
-
-  useEffect(() => {
-    void (async () => {
-      if (ws.lastMessage !== null && doc === null) {
-        try {
-          dispatchDoc({
-            action: 'setDoc',
-            doc: unpack(new Uint8Array(await (ws.lastMessage.data as Blob).arrayBuffer())) as Zerra,
-          });
-        } catch (e) {
-          console.error(e, ws.lastMessage);
-        }
+  const editor = useZerraParticipant<MessageFromEditor>(
+    'edit',
+    uuid,
+    false,
+    useCallback((shouldSend, sendMessage, _, patch, pushPatch, setShouldSend) => {
+      if (shouldSend) {
+        if (patch !== null) sendMessage({ patch });
+        pushPatch(null);
+        setShouldSend(false);
       }
-    })();
-  }, [dispatchDoc, doc, ws.lastMessage]);
+    }, []),
+    useCallback(() => {}, []),
+  );
 
-  useEffect(() => {
-    if (shouldSend) {
-      ws.sendMessage(pack(doc));
-      setShouldSend(false);
-    }
-  }, [doc, shouldSend, ws]);
+  const { sendMessage } = editor;
+  const focus = useCallback(
+    (path: number[] | null) => sendMessage({ focus: path }),
+    [sendMessage],
+  );
 
   return {
-    doc,
-    dispatchDoc,
+    doc: editor.doc,
+    dispatchDoc: editor.dispatchDoc,
     get state() {
       return (
-        ws.readyState < ReadyState.OPEN ? 'connecting'
-        : ws.readyState == ReadyState.OPEN ? 'connected'
+        editor.status === 'connecting' || editor.status === 'waiting' ? 'connecting'
+        : editor.status === 'connected' ? 'connected'
         : 'disconnected'
       );
     },
+    coeditors:
+      editor.awareness?.editors.filter(({ id }) => id !== editor.awareness?.you) ?? [],
+    focus,
   };
 }
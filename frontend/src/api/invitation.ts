@@ -0,0 +1,44 @@
+import { useMutation, useQuery } from '@tanstack/react-query';
+import axios from 'axios';
+
+import { Invitation } from '../../../types/bindings/Invitation';
+import { InvitationInfo } from '../../../types/bindings/InvitationInfo';
+import { InvitationRequest } from '../../../types/bindings/InvitationRequest';
+
+const inspectInvitation = (uuid: string, invitation: string) =>
+  axios
+    .get(`/api/invitation/${uuid}`, { params: { invitation }, responseType: 'json' })
+    .then((res) => res.data as InvitationInfo);
+const enterPasscode = (uuid: string, invitation: string, passcode: string) =>
+  axios.post(`/api/passcode/${uuid}`, { invitation, passcode });
+const invite = (uuid: string, request: InvitationRequest) =>
+  axios
+    .post(`/api/invite/${uuid}`, request, { responseType: 'json' })
+    .then((res) => res.data as Invitation);
+
+export function useInvitationInfo(uuid: string, invitation: string | undefined) {
+  return useQuery({
+    queryKey: ['invitation', uuid, invitation],
+    queryFn: () => inspectInvitation(uuid, invitation!),
+    enabled: invitation !== undefined,
+    retry: false,
+  });
+}
+
+/** Leaves a proof of the passcode in a cookie, so that the passcode itself stays out of URLs. */
+export function usePasscode(uuid: string, invitation: string | undefined) {
+  return useMutation({
+    mutationFn: (passcode: string) => enterPasscode(uuid, invitation!, passcode),
+  });
+}
+
+/** A single-use invitation valid for a day, issued once per conduction page. */
+export function useInvitation(uuid: string) {
+  return useQuery({
+    queryKey: ['invite', uuid],
+    queryFn: () =>
+      invite(uuid, { valid_for: 24 * 60, single_use: true, passcode: null, invitee_name: null }),
+    staleTime: Infinity,
+    gcTime: Infinity,
+  });
+}
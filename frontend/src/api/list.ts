@@ -1,40 +1,98 @@
-import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
-import axios from 'axios';
+import { keepPreviousData, useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import axios, { isAxiosError } from 'axios';
 import { match } from 'ts-pattern';
 
-import { ListItem } from '../../../types/bindings/ListItem';
+import { Folder } from '../../../types/bindings/Folder';
+import { ImportProblem } from '../../../types/bindings/ImportProblem';
+import { ListPage } from '../../../types/bindings/ListPage';
+import { ListQuery } from '../../../types/bindings/ListQuery';
 
-const listZerrae = () =>
-  axios.get('/api', { responseType: 'json' }).then((res) => res.data as ListItem[]);
-const newZerra = () => axios.get('/api/new');
-const importZerra = async (yaml: File) => axios.post('/api/import', await yaml.text());
-const copyZerra = (uuid: string) => axios.get(`/api/copy/${uuid}`);
-const deleteZerra = (uuid: string) => axios.get(`/api/delete/${uuid}`);
+const listZerrae = (query: ListQuery) =>
+  axios
+    .get('/api', { params: query, responseType: 'json' })
+    .then((res) => res.data as ListPage);
+const newZerra = () => axios.post('/api/new');
+// Markdown and plain text are read as outlines, anything else as an exported zerra.
+const importZerra = async (file: File) =>
+  axios.post(
+    /\.(md|markdown|txt)$/i.test(file.name) ? '/api/import/outline' : '/api/import',
+    await file.text(),
+  );
+const copyZerra = (uuid: string) => axios.post(`/api/copy/${uuid}`);
+const deleteZerra = (uuid: string) => axios.delete(`/api/delete/${uuid}`);
+const fileZerra = (uuid: string, folder: number | null) =>
+  axios.post(`/api/file/${uuid}`, { folder });
+const tagZerra = (uuid: string, tags: string[]) => axios.post(`/api/tags/${uuid}`, tags);
+const templateFrom = (uuid: string) => axios.post(`/api/templates/from/${uuid}`);
+
+const listFolders = () =>
+  axios.get('/api/folders', { responseType: 'json' }).then((res) => res.data as Folder[]);
+const listTags = () =>
+  axios.get('/api/tags', { responseType: 'json' }).then((res) => res.data as string[]);
+const newFolder = (name: string) => axios.post('/api/folders', { name });
+const deleteFolder = (id: number) => axios.delete(`/api/folders/${id}`);
 
 export type ListAction =
   | { action: 'new' }
-  | { action: 'import'; yaml: File }
-  | ({ uuid: string } & ({ action: 'copy' } | { action: 'delete' }));
+  | { action: 'import'; file: File }
+  | ({ uuid: string } & (
+      | { action: 'copy' }
+      | { action: 'delete' }
+      | { action: 'file'; folder: number | null }
+      | { action: 'tag'; tags: string[] }
+      | { action: 'template' }
+    ))
+  | { action: 'newFolder'; name: string }
+  | { action: 'deleteFolder'; id: number };
 
-type Options = { onActionError: (action: ListAction) => void };
+type Options = {
+  query: ListQuery;
+  onActionError: (action: ListAction, problems?: ImportProblem[]) => void;
+};
 
-export function useZerraList({ onActionError }: Options) {
+export function useZerraList({ query, onActionError }: Options) {
   const queryClient = useQueryClient();
-  const listItems = useQuery({ queryKey: ['zerrae'], queryFn: listZerrae });
+  const listItems = useQuery({
+    queryKey: ['zerrae', query],
+    queryFn: () => listZerrae(query),
+    placeholderData: keepPreviousData,
+  });
+  const folders = useQuery({ queryKey: ['folders'], queryFn: listFolders });
+  const tags = useQuery({ queryKey: ['tags'], queryFn: listTags });
   const listMutation = useMutation({
     mutationFn: (action: ListAction) =>
       match(action)
         .with({ action: 'new' }, () => newZerra())
-        .with({ action: 'import' }, ({ yaml }) => importZerra(yaml))
+        .with({ action: 'import' }, ({ file }) => importZerra(file))
         .with({ action: 'copy' }, ({ uuid }) => copyZerra(uuid))
         .with({ action: 'delete' }, ({ uuid }) => deleteZerra(uuid))
+        .with({ action: 'file' }, ({ uuid, folder }) => fileZerra(uuid, folder))
+        .with({ action: 'tag' }, ({ uuid, tags }) => tagZerra(uuid, tags))
+        .with({ action: 'template' }, ({ uuid }) => templateFrom(uuid))
+        .with({ action: 'newFolder' }, ({ name }) => newFolder(name))
+        .with({ action: 'deleteFolder' }, ({ id }) => deleteFolder(id))
         .exhaustive(),
-    onSuccess: () => void queryClient.invalidateQueries({ queryKey: ['zerrae'] }),
-    onError: (_, action) => onActionError(action),
+    onSuccess: () => {
+      void queryClient.invalidateQueries({ queryKey: ['zerrae'] });
+      void queryClient.invalidateQueries({ queryKey: ['trash'] });
+      void queryClient.invalidateQueries({ queryKey: ['folders'] });
+      void queryClient.invalidateQueries({ queryKey: ['tags'] });
+      void queryClient.invalidateQueries({ queryKey: ['templates'] });
+    },
+    onError: (error, action) =>
+      onActionError(
+        action,
+        isAxiosError(error) && error.response?.status === 422 ?
+          (error.response.data as ImportProblem[])
+        : undefined,
+      ),
   });
 
   return {
-    items: listItems.data,
+    items: listItems.data?.items,
+    total: listItems.data?.total ?? 0,
+    folders: folders.data ?? [],
+    tags: tags.data ?? [],
     fetchStatus: listItems.status,
     dispatchAction: listMutation.mutate,
   };
@@ -1,24 +1,38 @@
 import * as Y from 'yjs';
 import cookie from 'js-cookie';
 import { pack, unpack } from 'msgpackr';
-import { useCallback, useEffect, useMemo, useReducer, useState, useSyncExternalStore } from 'react';
+import {
+  useCallback,
+  useEffect,
+  useMemo,
+  useReducer,
+  useRef,
+  useState,
+  useSyncExternalStore,
+} from 'react';
 import useWebSocket from 'react-use-websocket';
 import { P, match } from 'ts-pattern';
 
+import { EditorPresence } from '../../../types/bindings/EditorPresence';
 import { MessageFromAttendee } from '../../../types/bindings/MessageFromAttendee';
 import { MessageFromConductor } from '../../../types/bindings/MessageFromConductor';
 import { MessageToClient } from '../../../types/bindings/MessageToClient';
 import { DocAction, Progress, Zerra, doAction } from '../model';
 import { Debouncer } from '../utils/debouncer';
-import { bind } from '../utils/immer-yjs/index';
+import { Binder, bind } from '../utils/immer-yjs/index';
 
 type Status = 'connecting' | 'waiting' | 'connected' | 'disconnected'; // | 'not-found'; // TODO
 
-const docSource = new Y.Doc();
-const docBound = bind<Zerra>(docSource.getMap());
+type Replica = { source: Y.Doc; bound: Binder<Zerra> };
 
-function useZerraParticipant<Msg>(
-  party: 'conduct' | 'attend',
+function replicate(update?: Uint8Array): Replica {
+  const source = new Y.Doc();
+  if (update !== undefined) Y.applyUpdate(source, update, 'remote');
+  return { source, bound: bind<Zerra>(source.getMap()) };
+}
+
+export function useZerraParticipant<Msg>(
+  party: 'conduct' | 'attend' | 'edit',
   uuid: string,
   sendOnPatch: boolean,
   onEdit: (
@@ -33,6 +47,7 @@ function useZerraParticipant<Msg>(
     progress: Progress | undefined,
     dispatchDoc: (action: DocAction) => void,
   ) => void,
+  search = '',
 ) {
   const [patch, pushPatch] = useReducer(
     (existing: Uint8Array | null, incoming: Uint8Array | null) =>
@@ -41,13 +56,16 @@ function useZerraParticipant<Msg>(
       : Y.mergeUpdates([existing, incoming]),
     null,
   );
-  useEffect(
-    () =>
-      void docSource.on('update', (patch, origin) =>
-        origin !== 'remote' ? pushPatch(patch) : undefined,
-      ),
-    [],
-  );
+  // Editors start over from the server's document whenever they connect, so that their patches
+  // apply to the same items as everyone else's. Messages read the latest replica from the ref.
+  const [replica, setReplica] = useState(() => replicate());
+  const current = useRef(replica);
+  useEffect(() => {
+    const onUpdate = (patch: Uint8Array, origin: unknown) =>
+      origin !== 'remote' ? pushPatch(patch) : undefined;
+    replica.source.on('update', onUpdate);
+    return () => replica.source.off('update', onUpdate);
+  }, [replica]);
 
   const debouncer = useMemo(() => new Debouncer(), []);
   const enqueueDebounced = useCallback(
@@ -57,7 +75,7 @@ function useZerraParticipant<Msg>(
 
   const dispatchDoc = useCallback(
     (action: DocAction) =>
-      docBound.update((doc) =>
+      current.current.bound.update((doc) =>
         doAction(action, doc, (key) =>
           key === undefined ? setShouldSend(true) : enqueueDebounced(key),
         ),
@@ -65,12 +83,21 @@ function useZerraParticipant<Msg>(
     [enqueueDebounced],
   );
 
-  const doc = useSyncExternalStore(docBound.subscribe, docBound.get);
+  const doc = useSyncExternalStore(replica.bound.subscribe, replica.bound.get);
 
   const [status, setStatus] = useState('connecting' as Status);
 
   const [shouldSend, setShouldSend] = useState(false);
 
+  const [awareness, setAwareness] = useState(
+    null as { you: number; editors: EditorPresence[] } | null,
+  );
+
+  // A restarting server asks to be left alone for a while. Pending changes are sent before hanging
+  // up, and the server, which handles messages in order, stores them before it sees the close.
+  const reconnectDelay = useRef(1000);
+  const [restarting, setRestarting] = useState(false);
+
   const onMessage = useCallback(
     (data: Uint8Array<ArrayBuffer>) => {
       const message = unpack(data) as MessageToClient;
@@ -79,27 +106,45 @@ function useZerraParticipant<Msg>(
         .with({ blob: P.select() }, (blob) =>
           dispatchDoc({ action: 'setDoc', doc: unpack(new Uint8Array(blob)) as Zerra }),
         )
+        .with({ document: P.select() }, (document) => {
+          const next = replicate(new Uint8Array(document));
+          current.current.bound.unbind();
+          current.current = next;
+          setReplica(next);
+          pushPatch(null);
+        })
         .with({ presence: P.any }, () => setStatus('connected'))
         .with({ patch: P.select() }, (patch) => {
-          Y.applyUpdate(docSource, new Uint8Array(patch), 'remote');
+          Y.applyUpdate(current.current.source, new Uint8Array(patch), 'remote');
           if (sendOnPatch) setShouldSend(true);
         })
+        .with({ awareness: P.select() }, setAwareness)
+        .with({ restarting: P.select() }, (secs) => {
+          reconnectDelay.current = secs * 1000;
+          setRestarting(true);
+          setShouldSend(true);
+        })
         .exhaustive();
     },
     [dispatchDoc, sendOnPatch],
   );
 
-  const { sendMessage: wsSendMsg } = useWebSocket(`/api/${party}/${uuid}`, {
+  const { sendMessage: wsSendMsg, getWebSocket } = useWebSocket(`/api/${party}/${uuid}${search}`, {
     disableJson: true,
     heartbeat: { returnMessage: 'pong', interval: 1000, timeout: 60000 },
-    reconnectInterval: 1000,
+    reconnectInterval: () => reconnectDelay.current,
     shouldReconnect: () => true,
-    onOpen: () => setStatus('waiting'),
+    onOpen: () => {
+      reconnectDelay.current = 1000;
+      setRestarting(false);
+      setStatus('waiting');
+    },
     onClose: () => setStatus('disconnected'),
     async onMessage(e) {
       if (e.data instanceof Blob) onMessage(new Uint8Array(await e.data.arrayBuffer()));
     },
   });
+
   const sendMessage = useCallback(
     (msg: Msg) => {
       console.log('sendMessage', new Date().toISOString(), msg);
@@ -112,6 +157,10 @@ function useZerraParticipant<Msg>(
     () => onEdit(shouldSend, sendMessage, doc, patch, pushPatch, setShouldSend),
     [doc, onEdit, patch, sendMessage, shouldSend],
   );
+  // Runs after the effect above, which sends any pending changes.
+  useEffect(() => {
+    if (restarting) getWebSocket()?.close();
+  }, [getWebSocket, restarting]);
 
   useEffect(
     () => onProgressToIntro(doc.progress, dispatchDoc),
@@ -124,6 +173,8 @@ function useZerraParticipant<Msg>(
     },
     dispatchDoc,
     status,
+    awareness,
+    sendMessage,
   };
 }
 
@@ -143,7 +194,10 @@ export function useZerraConductor(uuid: string) {
   );
 }
 
-export function useZerraAttendee(uuid: string) {
+export function useZerraAttendee(uuid: string, invitation?: string) {
+  const search = new URLSearchParams();
+  if (invitation !== undefined) search.set('invitation', invitation);
+
   return useZerraParticipant<MessageFromAttendee>(
     'attend',
     uuid,
@@ -165,5 +219,6 @@ export function useZerraAttendee(uuid: string) {
       },
       [uuid],
     ),
+    search.size === 0 ? '' : `?${search}`,
   );
 }
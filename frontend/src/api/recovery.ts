@@ -0,0 +1,38 @@
+import { useMutation, useQuery } from '@tanstack/react-query';
+import axios from 'axios';
+
+const issueRecoveryCode = (uuid: string) =>
+  axios.post(`/api/recovery/${uuid}`).then((res) => res.data as string);
+const rebindAttendee = (uuid: string) =>
+  axios.post(`/api/rebind/${uuid}`).then((res) => res.data as string);
+const recoverAttendee = (uuid: string, code: string) =>
+  axios.post(`/api/recover/${uuid}`, { code });
+
+export const recoveryLink = (uuid: string, code: string) =>
+  `${window.location.origin}/attend/${uuid}?${new URLSearchParams({ recovery: code })}`;
+
+/** Issued once the attendee has been declared, which the server may learn of a moment later. */
+export function useRecoveryCode(uuid: string, declared: boolean) {
+  return useQuery({
+    queryKey: ['recovery', uuid],
+    queryFn: () => issueRecoveryCode(uuid),
+    enabled: declared,
+    staleTime: Infinity,
+    gcTime: Infinity,
+  });
+}
+
+export function useRebind(uuid: string) {
+  return useMutation({ mutationFn: () => rebindAttendee(uuid) });
+}
+
+export function useRecovery(uuid: string, code: string | undefined) {
+  return useQuery({
+    queryKey: ['recover', uuid, code],
+    queryFn: () => recoverAttendee(uuid, code!).then(() => null),
+    enabled: code !== undefined,
+    retry: false,
+    staleTime: Infinity,
+    gcTime: Infinity,
+  });
+}
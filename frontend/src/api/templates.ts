@@ -0,0 +1,43 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import axios from 'axios';
+import { match } from 'ts-pattern';
+
+import { TemplateItem } from '../../../types/bindings/TemplateItem';
+
+const listTemplates = () =>
+  axios.get('/api/templates', { responseType: 'json' }).then((res) => res.data as TemplateItem[]);
+const newTemplate = () => axios.post('/api/templates');
+const startInterview = (uuid: string) =>
+  axios
+    .post(`/api/start/${uuid}`, null, { responseType: 'json' })
+    .then((res) => res.data as string);
+
+export type TemplateAction = { action: 'new' } | { action: 'start'; uuid: string };
+
+type Options = {
+  onStarted: (uuid: string) => void;
+  onActionError: (action: TemplateAction) => void;
+};
+
+export function useTemplates(enabled: boolean, { onStarted, onActionError }: Options) {
+  const queryClient = useQueryClient();
+  const templates = useQuery({ queryKey: ['templates'], queryFn: listTemplates, enabled });
+  const templateMutation = useMutation({
+    mutationFn: (action: TemplateAction) =>
+      match(action)
+        .with({ action: 'new' }, () => newTemplate().then(() => null))
+        .with({ action: 'start' }, ({ uuid }) => startInterview(uuid))
+        .exhaustive(),
+    onSuccess: (started) => {
+      void queryClient.invalidateQueries({ queryKey: ['templates'] });
+      void queryClient.invalidateQueries({ queryKey: ['zerrae'] });
+      if (started !== null) onStarted(started);
+    },
+    onError: (_, action) => onActionError(action),
+  });
+
+  return {
+    items: templates.data,
+    dispatchAction: templateMutation.mutate,
+  };
+}
@@ -0,0 +1,25 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import axios from 'axios';
+
+import { ApiToken } from '../../../types/bindings/ApiToken';
+import { MintedToken } from '../../../types/bindings/MintedToken';
+import { TokenRequest } from '../../../types/bindings/TokenRequest';
+
+const listTokens = () =>
+  axios.get('/api/tokens', { responseType: 'json' }).then((res) => res.data as ApiToken[]);
+const mintToken = (request: TokenRequest) =>
+  axios
+    .post('/api/tokens', request, { responseType: 'json' })
+    .then((res) => res.data as MintedToken);
+const revokeToken = (id: number) => axios.delete(`/api/tokens/${id}`);
+
+export function useApiTokens(enabled: boolean) {
+  const queryClient = useQueryClient();
+  const invalidate = () => void queryClient.invalidateQueries({ queryKey: ['tokens'] });
+
+  const tokens = useQuery({ queryKey: ['tokens'], queryFn: listTokens, enabled });
+  const mint = useMutation({ mutationFn: mintToken, onSuccess: invalidate });
+  const revoke = useMutation({ mutationFn: revokeToken, onSuccess: invalidate });
+
+  return { tokens: tokens.data, mint, revoke };
+}
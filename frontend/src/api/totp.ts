@@ -0,0 +1,29 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import axios from 'axios';
+
+import { TotpEnrolment } from '../../../types/bindings/TotpEnrolment';
+import { TotpStatus } from '../../../types/bindings/TotpStatus';
+
+const totpStatus = () =>
+  axios.get('/api/totp', { responseType: 'json' }).then((res) => res.data as TotpStatus);
+const enrolTotp = () =>
+  axios
+    .post('/api/totp/enrol', null, { responseType: 'json' })
+    .then((res) => res.data as TotpEnrolment);
+const confirmTotp = (code: string) =>
+  axios
+    .post('/api/totp/confirm', { code }, { responseType: 'json' })
+    .then((res) => res.data as string[]);
+const disableTotp = (code: string) => axios.post('/api/totp/disable', { code });
+
+export function useTotp(enabled: boolean) {
+  const queryClient = useQueryClient();
+  const invalidate = () => void queryClient.invalidateQueries({ queryKey: ['totp'] });
+
+  const status = useQuery({ queryKey: ['totp'], queryFn: totpStatus, enabled });
+  const enrol = useMutation({ mutationFn: enrolTotp });
+  const confirm = useMutation({ mutationFn: confirmTotp, onSuccess: invalidate });
+  const disable = useMutation({ mutationFn: disableTotp, onSuccess: invalidate });
+
+  return { status: status.data, enrol, confirm, disable };
+}
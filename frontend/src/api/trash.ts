@@ -0,0 +1,36 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import axios from 'axios';
+import { match } from 'ts-pattern';
+
+import { TrashItem } from '../../../types/bindings/TrashItem';
+
+const listTrash = () =>
+  axios.get('/api/trash', { responseType: 'json' }).then((res) => res.data as TrashItem[]);
+const restoreZerra = (uuid: string) => axios.post(`/api/restore/${uuid}`);
+const purgeZerra = (uuid: string) => axios.delete(`/api/purge/${uuid}`);
+
+export type TrashAction = { uuid: string; action: 'restore' | 'purge' };
+
+type Options = { onActionError: (action: TrashAction) => void };
+
+export function useTrash(enabled: boolean, { onActionError }: Options) {
+  const queryClient = useQueryClient();
+  const trashItems = useQuery({ queryKey: ['trash'], queryFn: listTrash, enabled });
+  const trashMutation = useMutation({
+    mutationFn: (action: TrashAction) =>
+      match(action)
+        .with({ action: 'restore' }, ({ uuid }) => restoreZerra(uuid))
+        .with({ action: 'purge' }, ({ uuid }) => purgeZerra(uuid))
+        .exhaustive(),
+    onSuccess: () => {
+      void queryClient.invalidateQueries({ queryKey: ['trash'] });
+      void queryClient.invalidateQueries({ queryKey: ['zerrae'] });
+    },
+    onError: (_, action) => onActionError(action),
+  });
+
+  return {
+    items: trashItems.data,
+    dispatchAction: trashMutation.mutate,
+  };
+}
@@ -0,0 +1,78 @@
+import * as icons from '@heroicons/react/24/outline';
+import { RefObject, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+
+import { TokenScope } from '../../../types/bindings/TokenScope';
+import { useApiTokens } from '../api/tokens';
+
+type Props = { dialog: RefObject<HTMLDialogElement | null>; open: boolean };
+
+export default function ApiTokens({ dialog, open }: Props) {
+  const { t } = useTranslation();
+
+  const { tokens, mint, revoke } = useApiTokens(open);
+  const [name, setName] = useState('');
+  const [scope, setScope] = useState<TokenScope>('read');
+
+  return (
+    <dialog className="modal" ref={dialog}>
+      <div className="modal-box flex flex-col gap-4">
+        <h3 className="text-lg font-bold">{t('API tokens')}</h3>
+        <ul className="flex flex-col gap-2">
+          {tokens?.map((token) => (
+            <li key={token.id} className="flex place-items-center gap-2">
+              <div className="flex-1">
+                {token.name} <span className="badge badge-sm">{t(token.scope)}</span>
+              </div>
+              <button
+                className="btn btn-square btn-ghost btn-xs"
+                aria-label={t('Revoke')}
+                onClick={() => revoke.mutate(token.id)}
+              >
+                <icons.TrashIcon className="size-4" />
+              </button>
+            </li>
+          ))}
+        </ul>
+        {mint.data !== undefined && (
+          <div role="alert" className="alert">
+            <div>
+              {t('Copy this token now')}
+              <div className="font-mono text-sm break-all">{mint.data.token}</div>
+            </div>
+          </div>
+        )}
+        <form className="flex gap-2" onSubmit={(e) => e.preventDefault()}>
+          <input
+            type="text"
+            className="input flex-1"
+            placeholder={t('Name')}
+            maxLength={64}
+            value={name}
+            onChange={(e) => setName(e.target.value)}
+          />
+          <select
+            className="select w-32"
+            value={scope}
+            onChange={(e) => setScope(e.target.value as TokenScope)}
+          >
+            <option value="read">{t('read')}</option>
+            <option value="write">{t('write')}</option>
+          </select>
+          <button
+            className="btn btn-primary"
+            disabled={name.trim().length === 0 || mint.isPending}
+            onClick={() => mint.mutate({ name, scope }, { onSuccess: () => setName('') })}
+          >
+            {t('Create')}
+          </button>
+        </form>
+        <div className="modal-action">
+          <form method="dialog">
+            <button className="btn">{t('Close')}</button>
+          </form>
+        </div>
+      </div>
+    </dialog>
+  );
+}
@@ -0,0 +1,130 @@
+import * as icons from '@heroicons/react/24/outline';
+import { useTranslation } from 'react-i18next';
+
+import { Folder } from '../../../types/bindings/Folder';
+import { ListQuery } from '../../../types/bindings/ListQuery';
+import { ListSort } from '../../../types/bindings/ListSort';
+import { ZerraStatus } from '../../../types/bindings/ZerraStatus';
+
+type Props = {
+  query: ListQuery;
+  setQuery: (query: ListQuery) => void;
+  folders: Folder[];
+  tags: string[];
+  onNewFolder: () => void;
+  onDeleteFolder: (id: number) => void;
+};
+
+const statuses: ZerraStatus[] = ['draft', 'live', 'finished'];
+const sorts: ListSort[] = ['lastModified', 'name', 'status'];
+
+export default function ListControls({
+  query,
+  setQuery,
+  folders,
+  tags,
+  onNewFolder,
+  onDeleteFolder,
+}: Props) {
+  const { t } = useTranslation();
+
+  // Any change of filter starts again from the first page.
+  const narrow = (changes: ListQuery) => setQuery({ ...query, ...changes, offset: undefined });
+
+  return (
+    <div className="flex flex-wrap gap-2">
+      <label className="input input-sm flex-1">
+        <icons.MagnifyingGlassIcon className="size-4 opacity-50" />
+        <input
+          type="search"
+          placeholder={t('Search')}
+          value={query.search ?? ''}
+          onChange={(e) => narrow({ search: e.target.value || undefined })}
+        />
+      </label>
+      <select
+        className="select w-auto select-sm"
+        value={query.status ?? ''}
+        onChange={(e) => narrow({ status: (e.target.value || undefined) as ZerraStatus })}
+      >
+        <option value="">{t('All statuses')}</option>
+        {statuses.map((status) => (
+          <option key={status} value={status}>
+            {t(status)}
+          </option>
+        ))}
+      </select>
+      <div className="join">
+        <select
+          className="select join-item w-auto select-sm"
+          value={query.folder ?? ''}
+          onChange={(e) =>
+            narrow({ folder: e.target.value === '' ? undefined : Number(e.target.value) })
+          }
+        >
+          <option value="">{t('All folders')}</option>
+          {folders.map(({ id, name }) => (
+            <option key={id} value={id}>
+              {name}
+            </option>
+          ))}
+        </select>
+        {query.folder === undefined ?
+          <button
+            className="btn join-item btn-sm"
+            aria-label={t('New folder')}
+            onClick={onNewFolder}
+          >
+            <icons.FolderPlusIcon className="size-4" />
+          </button>
+        : <button
+            className="btn join-item btn-sm"
+            aria-label={t('Delete folder')}
+            onClick={() => {
+              onDeleteFolder(query.folder!);
+              narrow({ folder: undefined });
+            }}
+          >
+            <icons.FolderMinusIcon className="size-4" />
+          </button>
+        }
+      </div>
+      <select
+        className="select w-auto select-sm"
+        value={query.tag ?? ''}
+        onChange={(e) => narrow({ tag: e.target.value || undefined })}
+      >
+        <option value="">{t('All tags')}</option>
+        {tags.map((tag) => (
+          <option key={tag} value={tag}>
+            {tag}
+          </option>
+        ))}
+      </select>
+      <div className="join">
+        <select
+          className="select join-item w-auto select-sm"
+          value={query.sort ?? 'lastModified'}
+          onChange={(e) => narrow({ sort: e.target.value as ListSort, descending: undefined })}
+        >
+          {sorts.map((sort) => (
+            <option key={sort} value={sort}>
+              {t(sort)}
+            </option>
+          ))}
+        </select>
+        <button
+          className="btn join-item btn-sm"
+          aria-label={t('Reverse order')}
+          onClick={() =>
+            narrow({
+              descending: !(query.descending ?? (query.sort ?? 'lastModified') === 'lastModified'),
+            })
+          }
+        >
+          <icons.ArrowsUpDownIcon className="size-4" />
+        </button>
+      </div>
+    </div>
+  );
+}
@@ -1,9 +1,11 @@
 import * as icons from '@heroicons/react/24/outline';
 import cookie from 'js-cookie';
-import { ReactNode } from 'react';
+import { ReactNode, useRef, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 
 import i18n from '../i18n';
+import ApiTokens from './ApiTokens';
+import TwoFactor from './TwoFactor';
 
 type Props = {
   title?: ReactNode;
@@ -52,6 +54,11 @@ export function NavbarUser() {
 
   const username = cookie.get('username') || t('User');
 
+  const twoFactorModal = useRef<HTMLDialogElement>(null);
+  const [twoFactorOpen, setTwoFactorOpen] = useState(false);
+  const apiTokensModal = useRef<HTMLDialogElement>(null);
+  const [apiTokensOpen, setApiTokensOpen] = useState(false);
+
   return (
     <div className="dropdown dropdown-end">
       <div tabIndex={0} role="button" className="btn btn-circle btn-ghost">
@@ -64,12 +71,35 @@ export function NavbarUser() {
           <span className="text-lg text-base-content">{username}</span>
         </li>
         <li>
-          <a href="/logout">
+          <button
+            onClick={() => {
+              setTwoFactorOpen(true);
+              twoFactorModal.current?.showModal();
+            }}
+          >
+            <icons.ShieldCheckIcon className="size-4" /> {t('Two-factor authentication')}
+          </button>
+        </li>
+        <li>
+          <button
+            onClick={() => {
+              setApiTokensOpen(true);
+              apiTokensModal.current?.showModal();
+            }}
+          >
+            <icons.KeyIcon className="size-4" /> {t('API tokens')}
+          </button>
+        </li>
+        <li>
+          <button type="submit" form="logout">
             <icons.ArrowRightStartOnRectangleIcon aria-label={t("Logout")} className="size-4" />{' '}
             {t('Logout')}
-          </a>
+          </button>
         </li>
       </ul>
+      <form id="logout" method="post" action="/logout" className="hidden" />
+      <TwoFactor dialog={twoFactorModal} open={twoFactorOpen} />
+      <ApiTokens dialog={apiTokensModal} open={apiTokensOpen} />
     </div>
   );
 }
@@ -4,6 +4,7 @@ import { useEffect, useRef, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 import { P, match } from 'ts-pattern';
 
+import { EditorPresence } from '../../../types/bindings/EditorPresence';
 import {
   Query,
   QueryAction,
@@ -20,9 +21,18 @@ type Props = {
   path: number[];
   readonly doc: Zerra;
   dispatchDoc: (action: QueryAction) => void;
+  coeditors?: EditorPresence[];
+  onFocus?: (path: number[]) => void;
 };
 
-export default function QueryCard({ query, path, doc, dispatchDoc }: Props) {
+export default function QueryCard({
+  query,
+  path,
+  doc,
+  dispatchDoc,
+  coeditors,
+  onFocus,
+}: Props) {
   const { t } = useTranslation();
 
   const { key, question, revelation, answer, comment, subflow, skippable, visible } = query;
@@ -44,8 +54,12 @@ export default function QueryCard({ query, path, doc, dispatchDoc }: Props) {
         .otherwise(isSubPath)
     : isSubPath(doc.progress.view));
 
+  const focusedBy = (coeditors ?? []).filter(
+    ({ focus }) => focus !== null && isSubPath(focus),
+  );
+
   return (
-    <div id={idOf(path)}>
+    <div id={idOf(path)} onFocusCapture={() => onFocus?.(path)}>
       <div className="join ps-4 *:rounded-b-none">
         <button
           className="btn join-item btn-square btn-xs"
@@ -102,7 +116,14 @@ export default function QueryCard({ query, path, doc, dispatchDoc }: Props) {
         )}
       </div>
 
-      <div className="join ps-4 text-sm">{displayOf(path, t)}</div>
+      <div className="join ps-4 text-sm">
+        {displayOf(path, t)}
+        {focusedBy.map(({ id, name }) => (
+          <span key={id} className="ms-2 badge badge-sm badge-accent">
+            {name}
+          </span>
+        ))}
+      </div>
 
       <div
         className={
@@ -161,6 +182,8 @@ export default function QueryCard({ query, path, doc, dispatchDoc }: Props) {
             path={[...path, index]}
             doc={doc}
             dispatchDoc={dispatchDoc}
+            coeditors={coeditors}
+            onFocus={onFocus}
           />
         ))}
 
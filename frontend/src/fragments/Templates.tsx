@@ -0,0 +1,92 @@
+import * as icons from '@heroicons/react/24/outline';
+import { RefObject } from 'react';
+import { useTranslation } from 'react-i18next';
+import { toast } from 'sonner';
+import { match } from 'ts-pattern';
+
+import { useTemplates } from '../api/templates';
+
+import Toast from './Toast';
+
+type Props = { dialog: RefObject<HTMLDialogElement | null>; open: boolean };
+
+export default function Templates({ dialog, open }: Props) {
+  const { t } = useTranslation();
+
+  const templates = useTemplates(open, {
+    onStarted: (uuid) => window.location.assign(`/conduct/${uuid}`),
+    onActionError: (action) => {
+      toast.custom(() => (
+        <Toast>
+          {match(action)
+            .with({ action: 'new' }, () => t('Failed to create template'))
+            .with({ action: 'start' }, ({ uuid }) =>
+              t('Failed to start interview', {
+                name: templates.items?.find((z) => z.id == uuid)?.name,
+              }),
+            )
+            .exhaustive()}
+        </Toast>
+      ));
+    },
+  });
+
+  return (
+    <dialog className="modal" ref={dialog}>
+      <div className="modal-box flex flex-col gap-4">
+        <h3 className="text-lg font-bold">{t('Templates')}</h3>
+        {templates.items?.length === 0 && <p>{t('No templates yet')}</p>}
+        <ul className="flex flex-col gap-2">
+          {templates.items?.map(({ id, name, role, version, instances }) => (
+            <li key={id} className="flex place-items-center gap-2">
+              <div className="flex-1">
+                {name}
+                <div className="text-sm opacity-60">
+                  {version === null ?
+                    t('Never used')
+                  : t('Template usage', { version, instances })}
+                </div>
+              </div>
+              {instances > 0 && (
+                <>
+                  <a
+                    className="btn btn-square btn-ghost btn-xs"
+                    href={`/api/report/${id}?format=pdf`}
+                  >
+                    <icons.ChartBarIcon aria-label={t('Report')} className="size-4" />
+                  </a>
+                  <a
+                    className="btn btn-square btn-ghost btn-xs"
+                    href={`/api/report/${id}?format=csv`}
+                  >
+                    <icons.TableCellsIcon aria-label={t('Report as CSV')} className="size-4" />
+                  </a>
+                </>
+              )}
+              {role !== 'viewer' && (
+                <a className="btn btn-square btn-ghost btn-xs" href={`/edit/${id}`}>
+                  <icons.PencilIcon aria-label={t('Edit')} className="size-4" />
+                </a>
+              )}
+              <button
+                className="btn btn-square btn-ghost btn-xs"
+                aria-label={t('Start interview')}
+                onClick={() => templates.dispatchAction({ action: 'start', uuid: id })}
+              >
+                <icons.PlayIcon className="size-4" />
+              </button>
+            </li>
+          ))}
+        </ul>
+        <div className="modal-action">
+          <button className="btn" onClick={() => templates.dispatchAction({ action: 'new' })}>
+            <icons.PlusIcon className="size-5" /> {t('New template')}
+          </button>
+          <form method="dialog">
+            <button className="btn">{t('Close')}</button>
+          </form>
+        </div>
+      </div>
+    </dialog>
+  );
+}
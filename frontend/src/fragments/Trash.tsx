@@ -0,0 +1,69 @@
+import * as icons from '@heroicons/react/24/outline';
+import { RefObject } from 'react';
+import { useTranslation } from 'react-i18next';
+import { toast } from 'sonner';
+import { match } from 'ts-pattern';
+
+import { useTrash } from '../api/trash';
+
+import Toast from './Toast';
+
+type Props = { dialog: RefObject<HTMLDialogElement | null>; open: boolean };
+
+export default function Trash({ dialog, open }: Props) {
+  const { t, i18n } = useTranslation();
+
+  const trash = useTrash(open, {
+    onActionError: (action) => {
+      const name = trash.items?.find((z) => z.id == action.uuid)?.name;
+      toast.custom(() => (
+        <Toast>
+          {match(action.action)
+            .with('restore', () => t('Failed to restore zerra', { name }))
+            .with('purge', () => t('Failed to purge zerra', { name }))
+            .exhaustive()}
+        </Toast>
+      ));
+    },
+  });
+
+  return (
+    <dialog className="modal" ref={dialog}>
+      <div className="modal-box flex flex-col gap-4">
+        <h3 className="text-lg font-bold">{t('Trash')}</h3>
+        {trash.items?.length === 0 && <p>{t('Trash is empty')}</p>}
+        <ul className="flex flex-col gap-2">
+          {trash.items?.map(({ id, name, purge_at }) => (
+            <li key={id} className="flex place-items-center gap-2">
+              <div className="flex-1">
+                {name}
+                <div className="text-sm opacity-60">
+                  {t('Purged on', { date: new Date(purge_at).toLocaleDateString(i18n.language) })}
+                </div>
+              </div>
+              <button
+                className="btn btn-square btn-ghost btn-xs"
+                aria-label={t('Restore')}
+                onClick={() => trash.dispatchAction({ action: 'restore', uuid: id })}
+              >
+                <icons.ArrowUturnLeftIcon className="size-4" />
+              </button>
+              <button
+                className="btn btn-square btn-ghost btn-xs"
+                aria-label={t('Delete forever')}
+                onClick={() => trash.dispatchAction({ action: 'purge', uuid: id })}
+              >
+                <icons.XMarkIcon className="size-4" />
+              </button>
+            </li>
+          ))}
+        </ul>
+        <div className="modal-action">
+          <form method="dialog">
+            <button className="btn">{t('Close')}</button>
+          </form>
+        </div>
+      </div>
+    </dialog>
+  );
+}
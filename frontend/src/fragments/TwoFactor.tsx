@@ -0,0 +1,87 @@
+import { RefObject, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+
+import { useTotp } from '../api/totp';
+
+type Props = { dialog: RefObject<HTMLDialogElement | null>; open: boolean };
+
+export default function TwoFactor({ dialog, open }: Props) {
+  const { t } = useTranslation();
+
+  const { status, enrol, confirm, disable } = useTotp(open);
+  const [code, setCode] = useState('');
+
+  const codeInput = (
+    <input
+      type="text"
+      className="input w-full"
+      autoComplete="one-time-code"
+      placeholder={t('Authenticator or backup code')}
+      value={code}
+      onChange={(e) => setCode(e.target.value)}
+    />
+  );
+
+  const content =
+    confirm.data !== undefined ?
+      <>
+        <p>{t('Keep these backup codes somewhere safe')}</p>
+        <ul className="grid grid-cols-2 gap-2 font-mono">
+          {confirm.data.map((backupCode) => (
+            <li key={backupCode}>{backupCode}</li>
+          ))}
+        </ul>
+      </>
+    : status?.enabled ?
+      <>
+        <p>{t('Backup codes left', { count: status.backup_codes_left })}</p>
+        {codeInput}
+        <button
+          className="btn btn-error"
+          disabled={code.trim().length === 0 || disable.isPending}
+          onClick={() => disable.mutate(code, { onSuccess: () => setCode('') })}
+        >
+          {t('Disable two-factor authentication')}
+        </button>
+        {disable.isError && <p className="text-error">{t('Wrong code')}</p>}
+      </>
+    : enrol.data !== undefined ?
+      <>
+        <p>{t('Scan this with an authenticator app')}</p>
+        <div
+          className="mx-auto w-48 bg-white"
+          dangerouslySetInnerHTML={{ __html: enrol.data.qr_svg }}
+        />
+        <p className="text-center font-mono text-sm break-all">{enrol.data.secret}</p>
+        {codeInput}
+        <button
+          className="btn btn-primary"
+          disabled={code.trim().length === 0 || confirm.isPending}
+          onClick={() => confirm.mutate(code, { onSuccess: () => setCode('') })}
+        >
+          {t('Okay')}
+        </button>
+        {confirm.isError && <p className="text-error">{t('Wrong code')}</p>}
+      </>
+    : <button
+        className="btn btn-primary"
+        disabled={status === undefined || enrol.isPending}
+        onClick={() => enrol.mutate()}
+      >
+        {t('Enable two-factor authentication')}
+      </button>;
+
+  return (
+    <dialog className="modal" ref={dialog}>
+      <div className="modal-box flex flex-col gap-4">
+        <h3 className="text-lg font-bold">{t('Two-factor authentication')}</h3>
+        {content}
+        <div className="modal-action">
+          <form method="dialog">
+            <button className="btn">{t('Close')}</button>
+          </form>
+        </div>
+      </div>
+    </dialog>
+  );
+}
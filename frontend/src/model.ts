@@ -1,6 +1,7 @@
 import { TFunction } from 'i18next';
 import { P, match } from 'ts-pattern';
 
+import { Persona } from '../../types/bindings/Persona';
 import { Progress } from '../../types/bindings/Progress';
 import { Query } from '../../types/bindings/Query';
 import { QueryStatus } from '../../types/bindings/QueryStatus';
@@ -10,6 +11,8 @@ export * from '../../types/bindings/QueryStatus';
 export * from '../../types/bindings/Zerra';
 export * from '../../types/bindings/Query';
 export * from '../../types/bindings/Progress';
+export * from '../../types/bindings/Persona';
+export * from '../../types/bindings/Avatar';
 
 export function idOf(path: number[]) {
   return path.join('_');
@@ -56,7 +59,7 @@ export type DocwideAction =
   | { action: 'setDoc'; doc: Zerra }
   | { action: 'setTitle'; title: string }
   | { action: 'declareAttendee'; uuid: string }
-  | { action: 'introduceAttendee'; name: string }
+  | { action: 'introduceAttendee'; name: string; persona: Persona }
   | { action: 'approveQuery' }
   | { action: 'submitAnswer'; answer: string }
   | { action: 'okFromConductor' }
@@ -183,12 +186,13 @@ export function doAction<Doc extends Zerra | null>(
         enque();
       }
     })
-    .with({ action: 'introduceAttendee' }, ({ name }) => {
+    .with({ action: 'introduceAttendee' }, ({ name, persona }) => {
       if (doc!.progress.status === 'intro') {
         doc!.progress = {
           status: 'ongoing',
           participant_uuid: doc!.progress.participant_uuid,
           participant_name: name,
+          participant_persona: persona,
           view: match(doc!.flow.entries().find(([, q]) => queryCanBeShown(q)))
             .with(P.nullish, () => [])
             .otherwise(([i]) => [i]),
@@ -305,6 +309,7 @@ function finish(
     status: 'finished',
     participant_uuid: progress.participant_uuid,
     participant_name: progress.participant_name,
+    participant_persona: progress.participant_persona,
     printable: false,
   };
 }
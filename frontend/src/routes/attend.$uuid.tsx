@@ -5,24 +5,114 @@ import { Trans, useTranslation } from 'react-i18next';
 import Markdown from 'react-markdown';
 import { P, match } from 'ts-pattern';
 
+import { useInvitationInfo, usePasscode } from '../api/invitation';
 import { useZerraAttendee } from '../api/participate';
+import { recoveryLink, useRecovery, useRecoveryCode } from '../api/recovery';
 
 import Message from '../fragments/Message';
 import Navbar from '../fragments/Navbar';
 import Reload from '../fragments/Reload';
 import WithSpinner from '../fragments/WithSpinner';
 
-import { DocAction, QueryStatus, enVal, withQuery } from '../model';
+import { Avatar, DocAction, QueryStatus, enVal, withQuery } from '../model';
 
-export const Route = createFileRoute('/attend/$uuid')({ component: Attend });
+export const Route = createFileRoute('/attend/$uuid')({
+  component: Attend,
+  validateSearch: (
+    search: Record<string, unknown>,
+  ): { invitation?: string; recovery?: string } => ({
+    invitation: typeof search.invitation === 'string' ? search.invitation : undefined,
+    recovery: typeof search.recovery === 'string' ? search.recovery : undefined,
+  }),
+});
 
 function Attend() {
   const { t } = useTranslation();
 
   const { uuid } = Route.useParams();
-  const zerra = useZerraAttendee(uuid);
+  const { invitation, recovery } = Route.useSearch();
+  const info = useInvitationInfo(uuid, recovery === undefined ? invitation : undefined);
+  const recovered = useRecovery(uuid, recovery);
 
-  const [editField, setEditField] = useState('');
+  const [passcodeField, setPasscodeField] = useState('');
+  const passcode = usePasscode(uuid, invitation);
+
+  if (recovery !== undefined)
+    return recovered.status === 'success' ?
+        <Attendance uuid={uuid} inviteeName="" />
+      : <>
+          <Navbar items={null} showHome={false} />
+          <div className="flex w-full flex-1 flex-col place-content-center place-items-center gap-4 p-4 sm:w-5/6 lg:w-2/3">
+            <Message>
+              {recovered.status === 'pending' ?
+                <WithSpinner>{t('Loading')}</WithSpinner>
+              : t('Recovery code is invalid')}
+            </Message>
+          </div>
+        </>;
+
+  // Returning attendees are recognised by their cookie and need no invitation.
+  if (invitation === undefined || (info.data && (!info.data.needs_passcode || passcode.isSuccess)))
+    return (
+      <Attendance
+        uuid={uuid}
+        invitation={invitation}
+        inviteeName={info.data?.invitee_name ?? ''}
+      />
+    );
+
+  return (
+    <>
+      <Navbar items={null} showHome={false} />
+      <div className="flex w-full flex-1 flex-col place-content-center place-items-center gap-4 p-4 sm:w-5/6 lg:w-2/3">
+        {match(info.status)
+          .with('pending', () => (
+            <Message>
+              <WithSpinner>{t('Loading')}</WithSpinner>
+            </Message>
+          ))
+          .with('error', () => <Message>{t('Invitation is invalid or expired')}</Message>)
+          .with('success', () => (
+            <form className="m-4 fieldset w-full sm:w-80" onSubmit={(e) => e.preventDefault()}>
+              <input
+                type="password"
+                className="input w-full"
+                placeholder={t('Passcode')}
+                onChange={(e) => setPasscodeField(e.target.value)}
+              />
+              {passcode.isError && <p className="text-error">{t('Wrong passcode')}</p>}
+              <button
+                className="btn btn-primary"
+                disabled={passcodeField.length === 0 || passcode.isPending}
+                onClick={() => passcode.mutate(passcodeField)}
+              >
+                {t('Okay')}
+              </button>
+            </form>
+          ))
+          .exhaustive()}
+      </div>
+    </>
+  );
+}
+
+function Attendance({
+  uuid,
+  invitation,
+  inviteeName,
+}: {
+  uuid: string;
+  invitation?: string;
+  inviteeName: string;
+}) {
+  const { t } = useTranslation();
+
+  const zerra = useZerraAttendee(uuid, invitation);
+  const recoveryCode = useRecoveryCode(uuid, zerra.doc?.progress.status === 'intro');
+
+  const [editField, setEditField] = useState(inviteeName);
+  const [pronouns, setPronouns] = useState('');
+  const [avatar, setAvatar] = useState<Avatar>({ kind: 'neutral' });
 
   const dispatchEdit = useCallback(
     (action: DocAction) => {
@@ -56,15 +146,64 @@ function Attend() {
               type="text"
               className="input w-full"
               placeholder={t('Name')}
+              defaultValue={inviteeName}
               onChange={(e) => setEditField(e.target.value)}
             />
+            <input
+              type="text"
+              className="input w-full"
+              placeholder={t('Pronouns')}
+              maxLength={32}
+              onChange={(e) => setPronouns(e.target.value)}
+            />
+            <select
+              className="select w-full"
+              value={avatar.kind}
+              onChange={(e) =>
+                setAvatar({ kind: e.target.value as Exclude<Avatar['kind'], 'image'> })
+              }
+            >
+              <option value="neutral">{t('Neutral')}</option>
+              <option value="man">{t('Man')}</option>
+              <option value="woman">{t('Woman')}</option>
+              {avatar.kind === 'image' && <option value="image">{t('Image')}</option>}
+            </select>
+            <input
+              type="file"
+              accept="image/png,image/jpeg,image/svg+xml"
+              className="file-input w-full"
+              onChange={(e) => {
+                const file = e.target.files?.[0];
+                if (file === undefined || file.size > 256 * 1024) return;
+                void file
+                  .arrayBuffer()
+                  .then((data) =>
+                    setAvatar({ kind: 'image', mime: file.type, data: new Uint8Array(data) }),
+                  );
+              }}
+            />
             <button
               className="btn btn-primary"
               disabled={editField.trim().length === 0}
-              onClick={() => dispatchEdit({ action: 'introduceAttendee', name: editField })}
+              onClick={() =>
+                dispatchEdit({
+                  action: 'introduceAttendee',
+                  name: editField,
+                  persona: { pronouns: pronouns.trim() || null, avatar },
+                })
+              }
             >
               {t('Begin')}
             </button>
+            {recoveryCode.data !== undefined && (
+              <div className="mt-4 rounded-box bg-base-200 p-4 text-sm">
+                {t('Keep this recovery code to continue on another device')}
+                <div className="font-mono text-lg">{recoveryCode.data}</div>
+                <a href={recoveryLink(uuid, recoveryCode.data)} className="link break-all">
+                  {recoveryLink(uuid, recoveryCode.data)}
+                </a>
+              </div>
+            )}
           </form>
         ))
         .with({ status: 'ongoing' }, (progress) =>
@@ -4,7 +4,9 @@ import { QRCodeSVG } from 'qrcode.react';
 import { useTranslation } from 'react-i18next';
 import { P, match } from 'ts-pattern';
 
+import { useInvitation } from '../api/invitation';
 import { useZerraConductor } from '../api/participate';
+import { recoveryLink, useRebind } from '../api/recovery';
 
 import Message from '../fragments/Message';
 import Navbar from '../fragments/Navbar';
@@ -21,6 +23,8 @@ function Conduct() {
 
   const { uuid } = Route.useParams();
   const zerra = useZerraConductor(uuid);
+  const invitation = useInvitation(uuid);
+  const rebind = useRebind(uuid);
 
   const message = match([zerra.doc?.progress.status, zerra.status])
     .with([P.any, 'connecting'], () => <WithSpinner>{t('Connecting')}</WithSpinner>)
@@ -31,7 +35,9 @@ function Conduct() {
     .with([P.any, 'disconnected'], () => <Reload>{t('Connection lost')}</Reload>)
     .exhaustive();
 
-  const attendLink = window.location.href.split('#')[0].replace('conduct', 'attend');
+  const attendLink =
+    invitation.data &&
+    `${window.location.href.split('#')[0].replace('conduct', 'attend')}?${new URLSearchParams({ invitation: invitation.data.token })}`;
   const bannerContent = match(zerra.doc?.progress)
     .with(P.union(P.nullish, { status: 'none' }), () => null)
     .with({ status: 'intro' }, () => <WithSpinner>{t('Waiting for introduction')}</WithSpinner>)
@@ -143,13 +149,32 @@ function Conduct() {
         <div className="flex w-full flex-col content-center justify-stretch gap-4 p-4 sm:w-5/6 lg:w-2/3">
           {zerra.status === 'connecting' || zerra.status === 'waiting' ?
             <div className="flex place-content-center place-items-center gap-4">
-              <QRCodeSVG value={attendLink} marginSize={4} level="H" />
-              <div className="text-lg">{attendLink}</div>
+              {attendLink !== undefined ?
+                <>
+                  <QRCodeSVG value={attendLink} marginSize={4} level="H" />
+                  <div className="text-lg break-all">{attendLink}</div>
+                </>
+              : <WithSpinner>{t('Loading')}</WithSpinner>}
             </div>
           : null}
           {bannerContent !== null ?
             <div className="flex place-content-center place-items-center gap-4 px-4">
               {bannerContent}
+              <button
+                className="btn btn-xs"
+                disabled={rebind.isPending}
+                onClick={() => rebind.mutate()}
+              >
+                <icons.LinkIcon className="size-4" /> {t('Rebind attendee')}
+              </button>
+            </div>
+          : null}
+          {rebind.data !== undefined ?
+            <div className="flex place-content-center place-items-center gap-4 px-4">
+              {t('Send this link to the attendee to continue on another device')}
+              <a href={recoveryLink(uuid, rebind.data)} className="link break-all">
+                {recoveryLink(uuid, rebind.data)}
+              </a>
             </div>
           : null}
         </div>
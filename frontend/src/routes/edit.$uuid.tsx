@@ -141,6 +141,8 @@ function Edit() {
             path={[index]}
             doc={zerra.doc!}
             dispatchDoc={zerra.dispatchDoc}
+            coeditors={zerra.coeditors}
+            onFocus={zerra.focus}
           />
         ))}
 
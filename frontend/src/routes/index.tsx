@@ -5,34 +5,74 @@ import { useTranslation } from 'react-i18next';
 import { Toaster, toast } from 'sonner';
 import { P, match } from 'ts-pattern';
 
+import { ListQuery } from '../../../types/bindings/ListQuery';
+import { Role } from '../../../types/bindings/Role';
 import { useZerraList } from '../api/list';
 
+import ListControls from '../fragments/ListControls';
 import Message from '../fragments/Message';
 import Navbar from '../fragments/Navbar';
 import Reload from '../fragments/Reload';
 import Toast from '../fragments/Toast';
+import Templates from '../fragments/Templates';
+import Trash from '../fragments/Trash';
 import WithSpinner from '../fragments/WithSpinner';
 
 export const Route = createFileRoute('/')({ component: Index });
 
 type DeleteArgs = { uuid: string; title: string };
+type TagArgs = { uuid: string; tags: string };
+
+const PAGE = 50;
+
+const roles: Role[] = ['viewer', 'editor', 'conductor', 'owner'];
+const atLeast = (role: Role, required: Role) => roles.indexOf(role) >= roles.indexOf(required);
 
 function Index() {
   const { t } = useTranslation();
 
+  const [query, setQuery] = useState<ListQuery>({ limit: PAGE });
+  const offset = query.offset ?? 0;
+
   const zerrae = useZerraList({
-    onActionError: (action) => {
+    query,
+    onActionError: (action, problems) => {
+      const nameOf = (uuid: string) => zerrae.items?.find((z) => z.id == uuid)?.name;
       toast.custom(() => (
         <Toast>
           {match(action)
             .with({ action: 'new' }, () => t('Failed to create new zerra'))
-            .with({ action: 'import' }, () => t('Failed to import the file'))
+            .with({ action: 'import' }, () => (
+              <>
+                {t('Failed to import the file')}
+                <ul className="list-inside list-disc">
+                  {problems?.map(({ line, path, message }, i) => (
+                    <li key={i}>
+                      {line !== null
+                        ? t('On line', { line, message })
+                        : path !== null
+                          ? t('At path', { path, message })
+                          : message}
+                    </li>
+                  ))}
+                </ul>
+              </>
+            ))
             .with({ action: 'copy' }, ({ uuid }) =>
-              t('Failed to copy zerra', { name: zerrae.items!.find((z) => z.id == uuid)!.name }),
+              t('Failed to copy zerra', { name: nameOf(uuid) }),
             )
             .with({ action: 'delete' }, ({ uuid }) =>
-              t('Failed to delete zerra', { name: zerrae.items!.find((z) => z.id == uuid)!.name }),
+              t('Failed to delete zerra', { name: nameOf(uuid) }),
+            )
+            .with({ action: 'file' }, ({ uuid }) =>
+              t('Failed to file zerra', { name: nameOf(uuid) }),
             )
+            .with({ action: 'tag' }, ({ uuid }) => t('Failed to tag zerra', { name: nameOf(uuid) }))
+            .with({ action: 'template' }, ({ uuid }) =>
+              t('Failed to save as template', { name: nameOf(uuid) }),
+            )
+            .with({ action: 'newFolder' }, ({ name }) => t('Failed to create folder', { name }))
+            .with({ action: 'deleteFolder' }, () => t('Failed to delete folder'))
             .exhaustive()}
         </Toast>
       ));
@@ -51,42 +91,196 @@ function Index() {
     deleteModal.current?.showModal();
   };
 
+  const tagModal = useRef<HTMLDialogElement>(null);
+  const [tagArgs, setTagArgs] = useState(null as TagArgs | null);
+  const attemptTag = (uuid: string, tags: string[]) => {
+    setTagArgs({ uuid, tags: tags.join(', ') });
+    tagModal.current?.showModal();
+  };
+
+  const folderModal = useRef<HTMLDialogElement>(null);
+  const [folderName, setFolderName] = useState('');
+
   const importFile = useRef<HTMLInputElement>(null);
 
+  const trashModal = useRef<HTMLDialogElement>(null);
+  const [trashOpen, setTrashOpen] = useState(false);
+
+  const templatesModal = useRef<HTMLDialogElement>(null);
+  const [templatesOpen, setTemplatesOpen] = useState(false);
+
+  // Finished zerrae picked for a bulk export.
+  const [selected, setSelected] = useState<string[]>([]);
+  const toggleSelected = (uuid: string) =>
+    setSelected(selected.includes(uuid) ? selected.filter((s) => s !== uuid) : [...selected, uuid]);
+  const exportUrl = (format: 'csv' | 'xlsx') =>
+    `/api/export?${new URLSearchParams({ ids: selected.join(','), format })}`;
+
   return (
     <>
       <Navbar showHome={false} />
       <div className="flex w-full flex-1 flex-col gap-4 p-4 sm:w-5/6 lg:w-2/3">
+        <ListControls
+          query={query}
+          setQuery={setQuery}
+          folders={zerrae.folders}
+          tags={zerrae.tags}
+          onNewFolder={() => {
+            setFolderName('');
+            folderModal.current?.showModal();
+          }}
+          onDeleteFolder={(id) => zerrae.dispatchAction({ action: 'deleteFolder', id })}
+        />
+
         <Message>{message}</Message>
 
-        {zerrae.items
-          ?.sort((a, b) => Date.parse(b.last_modified) - Date.parse(a.last_modified))
-          .map(({ name, id }) => (
-            <div key={id} className="flex gap-4 rounded-box bg-base-200 p-4">
-              <div className="my-auto flex-1 text-lg">{name}</div>
+        {selected.length > 0 && (
+          <div className="flex place-items-center gap-2">
+            <span className="flex-1">{t('Selected', { count: selected.length })}</span>
+            <div className="join">
+              <a className="btn join-item btn-sm" href={exportUrl('csv')}>
+                <icons.TableCellsIcon className="size-4" /> CSV
+              </a>
+              <a className="btn join-item btn-sm" href={exportUrl('xlsx')}>
+                <icons.TableCellsIcon className="size-4" /> XLSX
+              </a>
+              <button className="btn join-item btn-sm" onClick={() => setSelected([])}>
+                {t('Clear selection')}
+              </button>
+            </div>
+          </div>
+        )}
+
+        {zerrae.items?.map(
+          ({ name, id, role, live, status, participant, folder, tags, template_version }) => (
+            <div key={id} className="flex flex-wrap gap-4 rounded-box bg-base-200 p-4">
+              {status === 'finished' && (
+                <input
+                  type="checkbox"
+                  className="checkbox my-auto"
+                  aria-label={t('Select for export')}
+                  checked={selected.includes(id)}
+                  onChange={() => toggleSelected(id)}
+                />
+              )}
+              <div className="my-auto flex-1">
+                <div className="text-lg">
+                  {name}
+                  <span className="ms-2 badge">{t(status)}</span>
+                  {live && <span className="ms-2 badge badge-error">{t('Live')}</span>}
+                </div>
+                {participant !== null && (
+                  <div className="text-sm opacity-60">
+                    {t('Participated by ', { participant_name: participant })}
+                  </div>
+                )}
+                {template_version !== null && (
+                  <div className="text-sm opacity-60">
+                    {t('From template version', { version: template_version })}
+                  </div>
+                )}
+                {tags.length > 0 && (
+                  <div className="mt-1 flex flex-wrap gap-1">
+                    {tags.map((tag) => (
+                      <button
+                        key={tag}
+                        className="badge badge-outline badge-sm"
+                        onClick={() => setQuery({ ...query, tag, offset: undefined })}
+                      >
+                        {tag}
+                      </button>
+                    ))}
+                  </div>
+                )}
+              </div>
+              <select
+                className="select my-auto w-auto select-sm"
+                aria-label={t('Folder')}
+                value={folder ?? ''}
+                onChange={(e) =>
+                  zerrae.dispatchAction({
+                    action: 'file',
+                    uuid: id,
+                    folder: e.target.value === '' ? null : Number(e.target.value),
+                  })
+                }
+              >
+                <option value="">{t('No folder')}</option>
+                {zerrae.folders.map((folder) => (
+                  <option key={folder.id} value={folder.id}>
+                    {folder.name}
+                  </option>
+                ))}
+              </select>
+              {atLeast(role, 'editor') && (
+                <button className="btn btn-square" onClick={() => attemptTag(id, tags)}>
+                  <icons.TagIcon aria-label={t('Tags')} className="size-5" />
+                </button>
+              )}
               <a className="btn btn-square" href={`/api/export/${id}`}>
                 <icons.ArrowUpOnSquareStackIcon aria-label={t('Export')} className="size-5" />
               </a>
+              {atLeast(role, 'owner') && (
+                <button
+                  className="btn btn-square"
+                  onClick={() => attemptDelete({ uuid: id, title: name })}
+                >
+                  <icons.XMarkIcon aria-label={t('Delete')} className="size-5" />
+                </button>
+              )}
               <button
                 className="btn btn-square"
-                onClick={() => attemptDelete({ uuid: id, title: name })}
+                onClick={() => zerrae.dispatchAction({ action: 'copy', uuid: id })}
               >
-                <icons.XMarkIcon aria-label={t('Delete')} className="size-5" />
+                <icons.DocumentDuplicateIcon aria-label={t('Copy')} className="size-5" />
               </button>
               <button
                 className="btn btn-square"
-                onClick={() => zerrae.dispatchAction({ action: 'copy', uuid: id })}
+                onClick={() => zerrae.dispatchAction({ action: 'template', uuid: id })}
               >
-                <icons.DocumentDuplicateIcon aria-label={t('Copy')} className="size-5" />
+                <icons.DocumentPlusIcon aria-label={t('Save as template')} className="size-5" />
               </button>
-              <a className="btn btn-square" href={`/conduct/${id}`}>
-                <icons.PlayIcon aria-label={t('Conduct')} className="size-5" />
-              </a>
-              <a className="btn btn-square" href={`/edit/${id}`}>
-                <icons.PencilIcon aria-label={t('Edit')} className="size-5" />
-              </a>
+              {atLeast(role, 'conductor') && (
+                <a className="btn btn-square" href={`/conduct/${id}`}>
+                  <icons.PlayIcon aria-label={t('Conduct')} className="size-5" />
+                </a>
+              )}
+              {atLeast(role, 'editor') && !live && (
+                <a className="btn btn-square" href={`/edit/${id}`}>
+                  <icons.PencilIcon aria-label={t('Edit')} className="size-5" />
+                </a>
+              )}
             </div>
-          ))}
+          ),
+        )}
+
+        {zerrae.total > PAGE && (
+          <div className="join mx-auto">
+            <button
+              className="btn join-item"
+              aria-label={t('Previous page')}
+              disabled={offset === 0}
+              onClick={() => setQuery({ ...query, offset: Math.max(offset - PAGE, 0) })}
+            >
+              <icons.ChevronLeftIcon className="size-5" />
+            </button>
+            <span className="btn join-item pointer-events-none">
+              {t('Page range', {
+                from: offset + 1,
+                to: Math.min(offset + PAGE, zerrae.total),
+                total: zerrae.total,
+              })}
+            </span>
+            <button
+              className="btn join-item"
+              aria-label={t('Next page')}
+              disabled={offset + PAGE >= zerrae.total}
+              onClick={() => setQuery({ ...query, offset: offset + PAGE })}
+            >
+              <icons.ChevronRightIcon className="size-5" />
+            </button>
+          </div>
+        )}
 
         <div className="join flex w-full">
           <button
@@ -98,15 +292,33 @@ function Index() {
           <button className="btn join-item" onClick={() => importFile.current?.click()}>
             <icons.ArrowDownOnSquareStackIcon className="size-5" /> {t('Import')}
           </button>
+          <button
+            className="btn join-item"
+            onClick={() => {
+              setTrashOpen(true);
+              trashModal.current?.showModal();
+            }}
+          >
+            <icons.TrashIcon className="size-5" /> {t('Trash')}
+          </button>
+          <button
+            className="btn join-item"
+            onClick={() => {
+              setTemplatesOpen(true);
+              templatesModal.current?.showModal();
+            }}
+          >
+            <icons.DocumentTextIcon className="size-5" /> {t('Templates')}
+          </button>
         </div>
         <input
           type="file"
           className="hidden"
           ref={importFile}
-          accept=".yml,.yaml,application/yaml,text/yaml,application/x-yaml,text/x-yaml"
+          accept=".yml,.yaml,application/yaml,text/yaml,application/x-yaml,text/x-yaml,.md,.markdown,.txt,text/markdown,text/plain"
           onChange={(e) =>
-            match(e.target.files?.[0]).with(P.nonNullable, (yaml) =>
-              zerrae.dispatchAction({ action: 'import', yaml }),
+            match(e.target.files?.[0]).with(P.nonNullable, (file) =>
+              zerrae.dispatchAction({ action: 'import', file }),
             )
           }
         />
@@ -130,6 +342,68 @@ function Index() {
         </div>
       </dialog>
 
+      <dialog className="modal" ref={tagModal}>
+        <div className="modal-box flex flex-col gap-4">
+          <h3 className="text-lg font-bold">{t('Tags')}</h3>
+          <input
+            type="text"
+            className="input w-full"
+            placeholder={t('Tags separated by commas')}
+            value={tagArgs?.tags ?? ''}
+            onChange={(e) => setTagArgs({ ...tagArgs!, tags: e.target.value })}
+          />
+          <div className="modal-action">
+            <form method="dialog" className="flex flex-row gap-4">
+              <button className="btn">{t('Cancel')}</button>
+              <button
+                className="btn btn-primary"
+                onClick={() =>
+                  zerrae.dispatchAction({
+                    action: 'tag',
+                    uuid: tagArgs!.uuid,
+                    tags: tagArgs!.tags
+                      .split(',')
+                      .map((tag) => tag.trim())
+                      .filter((tag) => tag.length > 0),
+                  })
+                }
+              >
+                {t('Save')}
+              </button>
+            </form>
+          </div>
+        </div>
+      </dialog>
+
+      <dialog className="modal" ref={folderModal}>
+        <div className="modal-box flex flex-col gap-4">
+          <h3 className="text-lg font-bold">{t('New folder')}</h3>
+          <input
+            type="text"
+            className="input w-full"
+            placeholder={t('Name')}
+            maxLength={64}
+            value={folderName}
+            onChange={(e) => setFolderName(e.target.value)}
+          />
+          <div className="modal-action">
+            <form method="dialog" className="flex flex-row gap-4">
+              <button className="btn">{t('Cancel')}</button>
+              <button
+                className="btn btn-primary"
+                disabled={folderName.trim().length === 0}
+                onClick={() => zerrae.dispatchAction({ action: 'newFolder', name: folderName })}
+              >
+                {t('Create')}
+              </button>
+            </form>
+          </div>
+        </div>
+      </dialog>
+
+      <Trash dialog={trashModal} open={trashOpen} />
+      <Templates dialog={templatesModal} open={templatesOpen} />
+
       <Toaster />
     </>
   );
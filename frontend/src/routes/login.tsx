@@ -3,24 +3,59 @@ import { useTranslation } from 'react-i18next';
 
 import Navbar from '../fragments/Navbar';
 
-export const Route = createFileRoute('/login')({ component: Login });
+export const Route = createFileRoute('/login')({
+  component: Login,
+  validateSearch: (
+    search: Record<string, unknown>,
+  ): { retry_after?: number; second_factor?: boolean } => ({
+    retry_after: typeof search.retry_after === 'number' ? search.retry_after : undefined,
+    second_factor: search.second_factor === true ? true : undefined,
+  }),
+});
 
 function Login() {
   const { t } = useTranslation();
+  const { retry_after, second_factor } = Route.useSearch();
 
   return (
     <>
       <Navbar items={null} showHome={false} />
       <div className="grid w-full flex-1 content-center justify-stretch p-4 sm:w-2/3 md:w-1/2 lg:w-1/3">
-        <form className="fieldset w-full" method="post">
+        <form
+          className="fieldset w-full"
+          method="post"
+          action={second_factor ? '/login/totp' : undefined}
+        >
           <div className="text-xl font-light">{t('Sign in')}</div>
-          <input type="text" className="input w-full" name="username" placeholder={t('Name')} />
-          <input
-            type="password"
-            className="input w-full"
-            name="password"
-            placeholder={t('Password')}
-          />
+          {retry_after !== undefined && (
+            <div role="alert" className="alert alert-warning">
+              {t('Too many failed attempts', { seconds: retry_after })}
+            </div>
+          )}
+          {second_factor ?
+            <input
+              type="text"
+              className="input w-full"
+              name="code"
+              autoComplete="one-time-code"
+              placeholder={t('Authenticator or backup code')}
+              autoFocus
+            />
+          : <>
+              <input
+                type="text"
+                className="input w-full"
+                name="username"
+                placeholder={t('Name')}
+              />
+              <input
+                type="password"
+                className="input w-full"
+                name="password"
+                placeholder={t('Password')}
+              />
+            </>
+          }
           <input type="submit" className="btn btn-primary" value={t('Okay')} />
         </form>
       </div>
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TokenScope } from "./TokenScope";
+
+export type ApiToken = { id: number, name: string, scope: TokenScope, created_at: string, last_used_at: string | null, };
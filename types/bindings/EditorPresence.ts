@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Someone with an editor open on a zerra, and the query they are working on.
+ */
+export type EditorPresence = { id: number, name: string, focus: Array<number> | null, };
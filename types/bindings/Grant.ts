@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Grantee } from "./Grantee";
+import type { Role } from "./Role";
+
+export type Grant = { grantee: Grantee, role: Role, };
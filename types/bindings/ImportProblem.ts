@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Something wrong with a zerra being imported, and where.
+ */
+export type ImportProblem = { 
+/**
+ * Counted from 1, if the problem lies on a particular line.
+ */
+line: number | null, 
+/**
+ * Where in the YAML document the problem lies, e.g. `flow[0].subflow[2].key`.
+ */
+path: string | null, message: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What an attendee may learn about an invitation before redeeming it.
+ */
+export type InvitationInfo = { needs_passcode: boolean, invitee_name: string | null, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type InvitationRequest = { 
+/**
+ * How long the invitation stays valid, in minutes.
+ */
+valid_for: number, 
+/**
+ * Whether only the first attendee to redeem the invitation may use it.
+ */
+single_use: boolean, passcode: string | null, invitee_name: string | null, };
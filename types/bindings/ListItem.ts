@@ -1,3 +1,25 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Role } from "./Role";
+import type { ZerraStatus } from "./ZerraStatus";
 
-export type ListItem = { id: string, name: string, last_modified: string, };
+export type ListItem = { id: string, name: string, last_modified: string, role: Role, 
+/**
+ * Whether the zerra is being conducted right now, which rules out editing it.
+ */
+live: boolean, status: ZerraStatus, 
+/**
+ * Who attended the zerra, once they have introduced themselves.
+ */
+participant: string | null, 
+/**
+ * The folder the user keeps the zerra in, if any.
+ */
+folder: number | null, tags: Array<string>, 
+/**
+ * The template the zerra was started from, if any.
+ */
+template: string | null, 
+/**
+ * The version of that template, which later fixes to it leave behind.
+ */
+template_version: number | null, };
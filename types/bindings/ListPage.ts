@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ListItem } from "./ListItem";
+
+/**
+ * A page of the list of zerrae, out of `total` matching the query.
+ */
+export type ListPage = { items: Array<ListItem>, total: number, };
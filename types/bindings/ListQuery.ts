@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ListSort } from "./ListSort";
+import type { ZerraStatus } from "./ZerraStatus";
+
+/**
+ * Narrows down, orders and pages the list of zerrae. Every field is optional.
+ */
+export type ListQuery = { folder?: number, tag?: string, 
+/**
+ * Only the interviews started from this template.
+ */
+template?: string, status?: ZerraStatus, 
+/**
+ * Matched against the names of zerrae and their participants, regardless of case.
+ */
+search?: string, 
+/**
+ * Defaults to `lastModified`.
+ */
+sort?: ListSort, 
+/**
+ * Defaults to ascending names and statuses, but to the latest modification first.
+ */
+descending?: boolean, offset?: number, 
+/**
+ * Defaults to 50, and cannot exceed 200.
+ */
+limit?: number, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A conduction in progress, as seen by the server.
+ */
+export type LiveConduction = { zerra_id: string, 
+/**
+ * The parties connected right now: `conductor`, `attendee` or both.
+ */
+connected: Array<string>, 
+/**
+ * When the first party connected.
+ */
+since: string, 
+/**
+ * How long the connected party has been waiting for the other, if at all.
+ */
+waiting_secs: number | null, last_heartbeat: string, };
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EditorPresence } from "./EditorPresence";
 
-export type MessageToClient = { "presence": number } | { "blob": Uint8Array } | { "patch": Uint8Array };
+export type MessageToClient = { "presence": number } | { "blob": Uint8Array } | { "document": Uint8Array } | { "patch": Uint8Array } | { "awareness": { you: number, editors: Array<EditorPresence>, } } | { "restarting": number };
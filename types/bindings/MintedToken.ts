@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiToken } from "./ApiToken";
+
+/**
+ * A newly minted token, the only time its secret is ever shown.
+ */
+export type MintedToken = { token: string, info: ApiToken, };
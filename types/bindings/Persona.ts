@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Avatar } from "./Avatar";
+
+/**
+ * How a party to a zerra wishes to be referred to and depicted in transcripts.
+ */
+export type Persona = { pronouns: string | null, avatar: Avatar, };
@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Answers } from "./Answers";
+
+export type QuestionReport = { key: number, 
+/**
+ * Where the question stands in the template, or in the interview that first asked it if the
+ * template no longer has it.
+ */
+path: Array<number>, 
+/**
+ * As the template words it now.
+ */
+question: string, skippable: boolean, 
+/**
+ * How many interviews showed the question to their attendee.
+ */
+asked: number, answered: number, 
+/**
+ * How many attendees chose to skip the question.
+ */
+skipped: number, answers: Answers, };
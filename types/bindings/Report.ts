@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { QuestionReport } from "./QuestionReport";
+
+/**
+ * What the finished interviews started from a template answered, question by question. Questions
+ * are matched across interviews by their key, so rewording a question keeps its answers together.
+ */
+export type Report = { template: string, title: string, 
+/**
+ * How many finished interviews the report covers.
+ */
+interviews: number, questions: Array<QuestionReport>, };
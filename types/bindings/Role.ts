@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Access a user holds on a zerra, ordered from least to most privileged.
+ *
+ * Every role includes the capabilities of the ones before it.
+ */
+export type Role = "viewer" | "editor" | "conductor" | "owner";
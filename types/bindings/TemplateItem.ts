@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Role } from "./Role";
+
+/**
+ * A reusable script, from which interviews are started.
+ */
+export type TemplateItem = { id: string, name: string, last_modified: string, role: Role, 
+/**
+ * The latest version an interview was started from, if any was.
+ */
+version: number | null, 
+/**
+ * How many interviews were started from any version of it.
+ */
+instances: number, };
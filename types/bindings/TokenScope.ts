@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What an API token may do: `Read` only allows requests that change nothing.
+ */
+export type TokenScope = "read" | "write";
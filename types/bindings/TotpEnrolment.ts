@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A freshly generated TOTP secret, to be confirmed with a code before it takes effect.
+ */
+export type TotpEnrolment = { 
+/**
+ * The secret in base32, for authenticator apps that cannot scan QR codes.
+ */
+secret: string, url: string, 
+/**
+ * The `otpauth://` URL as a QR code in SVG.
+ */
+qr_svg: string, };
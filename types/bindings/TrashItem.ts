@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A zerra in its owner's trash, from which it can be restored until it is purged.
+ */
+export type TrashItem = { id: string, name: string, deleted_at: string, purge_at: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How far a zerra has come, in the terms of its list.
+ */
+export type ZerraStatus = "draft" | "live" | "finished";
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct InvitationRequest {
+    /// How long the invitation stays valid, in minutes.
+    pub valid_for: u32,
+    /// Whether only the first attendee to redeem the invitation may use it.
+    pub single_use: bool,
+    pub passcode: Option<String>,
+    pub invitee_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Invitation {
+    pub token: String,
+    #[ts(type = "string")]
+    pub expires_at: jiff::Timestamp,
+}
+
+/// What an attendee may learn about an invitation before redeeming it.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct InvitationInfo {
+    pub needs_passcode: bool,
+    pub invitee_name: Option<String>,
+}
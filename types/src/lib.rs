@@ -10,8 +10,189 @@ pub struct ListItem {
     pub name: String,
     #[ts(type = "string")]
     pub last_modified: jiff::Timestamp,
+    pub role: sharing::Role,
+    /// Whether the zerra is being conducted right now, which rules out editing it.
+    pub live: bool,
+    pub status: ZerraStatus,
+    /// Who attended the zerra, once they have introduced themselves.
+    pub participant: Option<String>,
+    /// The folder the user keeps the zerra in, if any.
+    #[ts(type = "number | null")]
+    pub folder: Option<i64>,
+    pub tags: Vec<String>,
+    /// The template the zerra was started from, if any.
+    pub template: Option<String>,
+    /// The version of that template, which later fixes to it leave behind.
+    pub template_version: Option<i32>,
 }
 
+/// How far a zerra has come, in the terms of its list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum ZerraStatus {
+    /// Nobody has attended it yet.
+    Draft,
+    /// An attendee has begun, whether or not they are connected right now.
+    Live,
+    Finished,
+}
+
+impl ZerraStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            ZerraStatus::Draft => "draft",
+            ZerraStatus::Live => "live",
+            ZerraStatus::Finished => "finished",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "draft" => Some(ZerraStatus::Draft),
+            "live" => Some(ZerraStatus::Live),
+            "finished" => Some(ZerraStatus::Finished),
+            _ => None,
+        }
+    }
+}
+
+impl From<&zerra::Progress> for ZerraStatus {
+    fn from(progress: &zerra::Progress) -> Self {
+        match progress {
+            zerra::Progress::None => ZerraStatus::Draft,
+            zerra::Progress::Intro { .. } | zerra::Progress::Ongoing { .. } => ZerraStatus::Live,
+            zerra::Progress::Finished { .. } => ZerraStatus::Finished,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum ListSort {
+    #[default]
+    LastModified,
+    Name,
+    Status,
+}
+
+/// Narrows down, orders and pages the list of zerrae. Every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ListQuery {
+    #[ts(optional, type = "number")]
+    pub folder: Option<i64>,
+    #[ts(optional)]
+    pub tag: Option<String>,
+    /// Only the interviews started from this template.
+    #[ts(optional)]
+    pub template: Option<String>,
+    #[ts(optional)]
+    pub status: Option<ZerraStatus>,
+    /// Matched against the names of zerrae and their participants, regardless of case.
+    #[ts(optional)]
+    pub search: Option<String>,
+    /// Defaults to `lastModified`.
+    #[ts(optional)]
+    pub sort: Option<ListSort>,
+    /// Defaults to ascending names and statuses, but to the latest modification first.
+    #[ts(optional)]
+    pub descending: Option<bool>,
+    #[ts(optional)]
+    pub offset: Option<usize>,
+    /// Defaults to 50, and cannot exceed 200.
+    #[ts(optional)]
+    pub limit: Option<usize>,
+}
+
+/// A page of the list of zerrae, out of `total` matching the query.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ListPage {
+    pub items: Vec<ListItem>,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Folder {
+    #[ts(type = "number")]
+    pub id: i64,
+    pub name: String,
+}
+
+/// A reusable script, from which interviews are started.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TemplateItem {
+    pub id: String,
+    pub name: String,
+    #[ts(type = "string")]
+    pub last_modified: jiff::Timestamp,
+    pub role: sharing::Role,
+    /// The latest version an interview was started from, if any was.
+    pub version: Option<i32>,
+    /// How many interviews were started from any version of it.
+    #[ts(type = "number")]
+    pub instances: i64,
+}
+
+/// Something wrong with a zerra being imported, and where.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportProblem {
+    /// Counted from 1, if the problem lies on a particular line.
+    pub line: Option<usize>,
+    /// Where in the YAML document the problem lies, e.g. `flow[0].subflow[2].key`.
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ImportProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, &self.path) {
+            (Some(line), _) => write!(f, "line {line}: {}", self.message),
+            (None, Some(path)) => write!(f, "{path}: {}", self.message),
+            (None, None) => f.write_str(&self.message),
+        }
+    }
+}
+
+/// A zerra in its owner's trash, from which it can be restored until it is purged.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TrashItem {
+    pub id: String,
+    pub name: String,
+    #[ts(type = "string")]
+    pub deleted_at: jiff::Timestamp,
+    #[ts(type = "string")]
+    pub purge_at: jiff::Timestamp,
+}
+
+/// A conduction in progress, as seen by the server.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct LiveConduction {
+    pub zerra_id: String,
+    /// The parties connected right now: `conductor`, `attendee` or both.
+    pub connected: Vec<String>,
+    /// When the first party connected.
+    #[ts(type = "string")]
+    pub since: jiff::Timestamp,
+    /// How long the connected party has been waiting for the other, if at all.
+    #[ts(type = "number | null")]
+    pub waiting_secs: Option<i64>,
+    #[ts(type = "string")]
+    pub last_heartbeat: jiff::Timestamp,
+}
+
+pub mod invitation;
+pub mod report;
+pub mod sharing;
+pub mod token;
+pub mod totp;
 pub mod zerra;
 
 pub mod tx {
@@ -40,11 +221,24 @@ pub mod tx {
             #[ts(type = "Uint8Array")]
             Vec<u8>,
         ),
+        /// The whole shared document, as a Yjs update, for an editor to start from.
+        Document(
+            #[serde(with = "serde_bytes")]
+            #[ts(type = "Uint8Array")]
+            Vec<u8>,
+        ),
         Patch(
             #[serde(with = "serde_bytes")]
             #[ts(type = "Uint8Array")]
             Vec<u8>,
         ),
+        Awareness {
+            you: u32,
+            editors: Vec<EditorPresence>,
+        },
+        /// The server is stopping: the client should send what it has not yet sent, close, and
+        /// reconnect after this many seconds.
+        Restarting(u32),
     }
 
     #[derive(Debug, Serialize, Deserialize, TS)]
@@ -55,4 +249,25 @@ pub mod tx {
         #[ts(type = "Uint8Array")]
         pub patch: Vec<u8>,
     }
+
+    #[derive(Debug, Serialize, Deserialize, TS)]
+    #[serde(rename_all = "camelCase")]
+    #[ts(export)]
+    pub enum MessageFromEditor {
+        Patch(
+            #[serde(with = "serde_bytes")]
+            #[ts(type = "Uint8Array")]
+            Vec<u8>,
+        ),
+        Focus(Option<Vec<usize>>),
+    }
+
+    /// Someone with an editor open on a zerra, and the query they are working on.
+    #[derive(Debug, Clone, Serialize, Deserialize, TS)]
+    #[ts(export)]
+    pub struct EditorPresence {
+        pub id: u32,
+        pub name: String,
+        pub focus: Option<Vec<usize>>,
+    }
 }
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// What the finished interviews started from a template answered, question by question. Questions
+/// are matched across interviews by their key, so rewording a question keeps its answers together.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Report {
+    pub template: String,
+    pub title: String,
+    /// How many finished interviews the report covers.
+    pub interviews: usize,
+    pub questions: Vec<QuestionReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QuestionReport {
+    pub key: u16,
+    /// Where the question stands in the template, or in the interview that first asked it if the
+    /// template no longer has it.
+    pub path: Vec<usize>,
+    /// As the template words it now.
+    pub question: String,
+    pub skippable: bool,
+    /// How many interviews showed the question to their attendee.
+    pub asked: usize,
+    pub answered: usize,
+    /// How many attendees chose to skip the question.
+    pub skipped: usize,
+    pub answers: Answers,
+}
+
+impl QuestionReport {
+    /// The share of interviews that skipped the question, if any asked it.
+    pub fn skip_rate(&self) -> Option<f64> {
+        (self.asked > 0).then(|| self.skipped as f64 / self.asked as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum Answers {
+    /// Short answers that recur, most frequent first.
+    Choices { counts: Vec<AnswerCount> },
+    /// Answers in the order their interviews were last modified.
+    FreeText { answers: Vec<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AnswerCount {
+    pub answer: String,
+    pub count: usize,
+}
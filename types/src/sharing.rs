@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Access a user holds on a zerra, ordered from least to most privileged.
+///
+/// Every role includes the capabilities of the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Conductor,
+    Owner,
+}
+
+impl Role {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Conductor => "conductor",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "viewer" => Some(Role::Viewer),
+            "editor" => Some(Role::Editor),
+            "conductor" => Some(Role::Conductor),
+            "owner" => Some(Role::Owner),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum Grantee {
+    User(String),
+    Team(#[ts(type = "number")] i64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Grant {
+    pub grantee: Grantee,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Team {
+    #[ts(type = "number")]
+    pub id: i64,
+    pub name: String,
+    pub owned: bool,
+    pub members: Vec<String>,
+}
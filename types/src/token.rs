@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// What an API token may do: `Read` only allows requests that change nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum TokenScope {
+    Read,
+    Write,
+}
+
+impl TokenScope {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "read" => Some(TokenScope::Read),
+            "write" => Some(TokenScope::Write),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TokenRequest {
+    pub name: String,
+    pub scope: TokenScope,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ApiToken {
+    #[ts(type = "number")]
+    pub id: i64,
+    pub name: String,
+    pub scope: TokenScope,
+    #[ts(type = "string")]
+    pub created_at: jiff::Timestamp,
+    #[ts(type = "string | null")]
+    pub last_used_at: Option<jiff::Timestamp>,
+}
+
+/// A newly minted token, the only time its secret is ever shown.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct MintedToken {
+    pub token: String,
+    pub info: ApiToken,
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TotpStatus {
+    pub enabled: bool,
+    pub backup_codes_left: u32,
+}
+
+/// A freshly generated TOTP secret, to be confirmed with a code before it takes effect.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TotpEnrolment {
+    /// The secret in base32, for authenticator apps that cannot scan QR codes.
+    pub secret: String,
+    pub url: String,
+    /// The `otpauth://` URL as a QR code in SVG.
+    pub qr_svg: String,
+}
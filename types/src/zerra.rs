@@ -13,16 +13,45 @@ pub enum Progress {
     Ongoing {
         participant_uuid: String,
         participant_name: String,
+        #[serde(default)]
+        participant_persona: Persona,
         view: Vec<usize>,
         query_status: QueryStatus,
     },
     Finished {
         participant_uuid: String,
         participant_name: String,
+        #[serde(default)]
+        participant_persona: Persona,
         printable: bool,
     },
 }
 
+/// How a party to a zerra wishes to be referred to and depicted in transcripts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(default)]
+#[ts(export)]
+pub struct Persona {
+    pub pronouns: Option<String>,
+    pub avatar: Avatar,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum Avatar {
+    #[default]
+    Neutral,
+    Man,
+    Woman,
+    Image {
+        mime: String,
+        #[serde(with = "serde_bytes")]
+        #[ts(type = "Uint8Array")]
+        data: Vec<u8>,
+    },
+}
+
 #[derive(Debug, Clone, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum QueryStatus {